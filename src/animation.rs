@@ -0,0 +1,55 @@
+// Frames a pulse spends bright before as many dim, a third of a second each
+// at 60 FPS
+pub const PULSE_FRAMES: u64 = 20;
+
+// The time-based touches to how the board looks, counted in drawn frames. It
+// belongs to whatever draws, not to the game state, so the simulation and
+// replays never see it.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Animator {
+    frame: u64,
+    // Nothing blinks or moves on its own, for players who find it hard to
+    // look at
+    reduce_motion: bool,
+}
+
+impl Animator {
+    pub fn new(reduce_motion: bool) -> Self {
+        Self {
+            frame: 0,
+            reduce_motion,
+        }
+    }
+
+    // Once for every frame drawn
+    pub fn advance(&mut self) {
+        self.frame = self.frame.wrapping_add(1);
+    }
+
+    // Whether pulsing things are in their dim half, never with reduced motion
+    pub fn is_dimmed(&self) -> bool {
+        !self.reduce_motion && self.frame / PULSE_FRAMES % 2 == 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pulses_dim_every_other_stretch_of_frames() {
+        let mut animator = Animator::new(false);
+        let mut still = Animator::new(true);
+        let mut dimmed = Vec::new();
+        for _ in 0..PULSE_FRAMES * 4 {
+            dimmed.push(animator.is_dimmed());
+            assert!(!still.is_dimmed());
+            animator.advance();
+            still.advance();
+        }
+        let stretches: Vec<&[bool]> = dimmed.chunks(PULSE_FRAMES as usize).collect();
+        for (index, stretch) in stretches.iter().enumerate() {
+            assert!(stretch.iter().all(|&dim| dim == (index % 2 == 1)));
+        }
+    }
+}
@@ -0,0 +1,740 @@
+use crate::{
+    difficulty::Difficulty,
+    game::{self, FoodBehavior, FruitWeights, GameMode, GameState, WallMode, MIN_BOARD_SIZE},
+    keyboard::KeyBindings,
+    level::{self, Arena, Level},
+    menu::{self, PANEL_WIDTH},
+    relay,
+    speed::Speed,
+};
+use std::{path::PathBuf, str::FromStr, time::Duration};
+
+pub const DEFAULT_WIDTH: u16 = 30;
+pub const DEFAULT_HEIGHT: u16 = 15;
+
+pub const USAGE: &str = "\
+Usage: rust-snake [OPTIONS]
+       rust-snake export-run --last <FILE>
+       rust-snake import-run <FILE>
+
+Commands:
+  export-run --last <FILE>  Save the last game as a shareable run card
+  import-run <FILE>         Show the summary and final board of a run card
+
+Options:
+  --difficulty <LEVEL> 'easy', 'normal' (default) or 'hard', later options
+                       override the speed, board size and walls it picks
+  --width <N>          Board width including the border (min 5, default 30)
+  --height <N>         Board height including the border (min 5, default 15)
+  --auto-fit           Shrink the board if the terminal is too small for it
+  --fullscreen         Make the board as big as the terminal allows, ignoring
+                       --width and --height (alias: --fit)
+  --status-bar         Show the score under the board instead of in the side
+                       panel, the default when only the board fits the terminal
+  --fps <N>            Snake steps per second at the start (alias: --speed)
+  --no-speed-up        Keep the same speed instead of speeding up every 5 points
+  --name <NAME>        Player name, pre-fills the menu instead of the last one
+  --no-menu            Skip the menu and start playing right away
+  --relay              Two players share the snake, swapping control every 10s
+  --relay-seconds <N>  Seconds per relay turn, implies --relay
+  --walls <MODE>       'solid' (default) or 'wrap' to pass through the border
+  --mode <MODE>        'classic' (default), 'lives' to get three lives,
+                       'timed' to score what you can in two minutes or
+                       'shrinking' for walls that close in every 30 seconds,
+                       each mode keeps its own high score and top 10
+  --time-attack <S>    A timed game of S seconds instead of two minutes
+  --practice           Practice the controls: walls, the tail and obstacles are
+                       passed through instead of ending the game, which doesn't
+                       count for the high score
+  --demo               Let the bot play instead of you, for a demo that does not
+                       count for the high score
+  --rival              Race a computer-controlled snake for the food, it looks
+                       for a better way more often on harder difficulties
+  --two-player         A second snake for a second player on WASD, the first
+                       keeps the arrows. Either crashing ends the game, both
+                       at once is a draw
+  --food-count <N>     How many food items are on the board at once (default 1)
+  --start-length <N>   Start with N tail cells behind the head (default 0), as
+                       many as fit before the wall
+  --moving-food        Food wanders the board, taking a step every few ticks
+  --fruit-weights <A,C,B,H> How often apples (1pt), cherries (2pt), bananas
+                       (3pt) and hourglasses (1pt, slow the snake down for a
+                       while, none if left out) spawn relative to each other
+                       (default 12,6,2,1)
+  --theme <NAME|FILE>  A built-in theme: mint (default), classic, high-contrast,
+                       amber, colorblind or light, or colors from a TOML file of
+                       'name = [r, g, b]' lines, any color left out keeps its
+                       default. The theme key (T) switches between them in game
+  --colorblind         Same as --theme colorblind: colors for red-green
+                       colorblindness, a shaded tail and diamond apples
+  --no-color           Only use the 16 basic terminal colors
+  --color <WHEN>       'auto' (default) to go by NO_COLOR, COLORTERM and TERM,
+                       'always' for 24-bit color or 'never' for none at all
+  --ascii              Draw with plain ASCII characters only, the default when
+                       the locale is not UTF-8 or on a legacy Windows console
+  --reduce-motion      Keep the food from pulsing, and anything else on the board
+                       from moving on its own
+  --commentary         Show a line of live commentary under the board
+  --sound              Ring the terminal bell on eating, twice when the game ends
+  --bind <ACTION=KEYS> Rebind up, down, left, right, p2-up, p2-down, p2-left,
+                       p2-right (WASD with --two-player), pause, undo, quit,
+                       theme, fps (F3 by default, shows the frame and tick rates),
+                       panel ('h' by default, hides the side panel) or
+                       boost (SPACE by default, double speed for a second or
+                       until let go, where the terminal reports that),
+                       e.g. --bind up=i or --bind quit=q,esc (repeatable)
+  --seed <N>           Seed the food placement for a reproducible game
+  --obstacles <LAYOUT> Walls inside the board: 'bar' or 'corners'
+  --random-level [D]   Scatter walls over a share D of the board (default 0.15,
+                       max 0.4), the same --seed gives the same level
+  --map <FILE>         Play on a level drawn as text: '#' wall, '.' empty,
+                       'S' snake start, 'F' food
+  --replay <FILE>      Play back a recorded game, the last one is kept in
+                       ~/.local/share/rust-snake/last.replay
+  --record <FILE>      Also save the game's replay to FILE when it ends
+  --measure-latency    Measure input-to-screen latency instead of playing
+  --headless <N>       Let the bot play N games with no screen, one seed after
+                       another from --seed, and print how they went
+  --json               Print the --headless results as JSON instead of a table
+  --debug-hud          Start with the frame rate, tick rate and draw time shown,
+                       as the fps key toggles
+  --cursor-fallback    Keep the cursor off the board on terminals that ignore hiding it
+  -h, --help           Print this help";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Args {
+    pub width: u16,
+    pub height: u16,
+    pub auto_fit: bool,
+    pub fullscreen: bool,
+    pub difficulty: Difficulty,
+    pub step_interval: Duration,
+    pub speed_up: bool,
+    pub name: Option<String>,
+    pub no_menu: bool,
+    pub relay: Option<Duration>,
+    pub walls: WallMode,
+    pub mode: GameMode,
+    // A clock of its own for the timed mode
+    pub time_limit: Option<Duration>,
+    pub rival: bool,
+    pub practice: bool,
+    pub demo: bool,
+    pub two_player: bool,
+    pub food_count: usize,
+    pub start_length: usize,
+    pub food_behavior: FoodBehavior,
+    pub fruit_weights: FruitWeights,
+    pub theme_file: Option<PathBuf>,
+    pub colorblind: bool,
+    pub no_color: bool,
+    pub color: ColorChoice,
+    pub ascii: bool,
+    pub reduce_motion: bool,
+    pub commentary: bool,
+    pub sound: bool,
+    pub bindings: KeyBindings,
+    pub seed: Option<u64>,
+    pub arena: Arena,
+    pub map: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+    pub record: Option<PathBuf>,
+    pub measure_latency: bool,
+    // Games for the bot to play without a terminal
+    pub headless: Option<u32>,
+    pub json: bool,
+    pub debug_hud: bool,
+    // Also turned on when the terminal has no room for the side panel
+    pub status_bar: bool,
+    // Only by --status-bar, so a bigger terminal never brings the panel back
+    pub status_bar_asked: bool,
+    // Widened once the player's name is known, where the terminal has room
+    pub panel_width: u16,
+    pub cursor_fallback: bool,
+    pub help: bool,
+    pub command: Option<Command>,
+}
+
+// Whether to color the game, for when detecting it guesses wrong
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ColorChoice {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl FromStr for ColorChoice {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => Err(format!("unknown color choice '{}'", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    ExportRun { output: PathBuf },
+    ImportRun { path: PathBuf },
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Self {
+            width: DEFAULT_WIDTH,
+            height: DEFAULT_HEIGHT,
+            auto_fit: false,
+            fullscreen: false,
+            difficulty: Difficulty::Normal,
+            step_interval: game::DEFAULT_STEP_INTERVAL,
+            speed_up: true,
+            name: None,
+            no_menu: false,
+            relay: None,
+            walls: WallMode::Solid,
+            mode: GameMode::Classic,
+            time_limit: None,
+            rival: false,
+            practice: false,
+            demo: false,
+            two_player: false,
+            food_count: 1,
+            start_length: 0,
+            food_behavior: FoodBehavior::Still,
+            fruit_weights: FruitWeights::default(),
+            theme_file: None,
+            colorblind: false,
+            no_color: false,
+            color: ColorChoice::Auto,
+            ascii: false,
+            reduce_motion: false,
+            commentary: false,
+            sound: false,
+            bindings: KeyBindings::default(),
+            seed: None,
+            arena: Arena::Open,
+            map: None,
+            replay: None,
+            record: None,
+            measure_latency: false,
+            headless: None,
+            json: false,
+            debug_hud: false,
+            status_bar: false,
+            status_bar_asked: false,
+            panel_width: PANEL_WIDTH,
+            cursor_fallback: false,
+            help: false,
+            command: None,
+        }
+    }
+}
+
+impl Args {
+    pub fn parse(args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut args = args.peekable();
+        if let Some(command) = args.next_if(|arg| !arg.starts_with('-')) {
+            return Ok(Args {
+                command: Some(parse_command(&command, args)?),
+                ..Args::default()
+            });
+        }
+
+        let mut parsed = Args::default();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--difficulty" => parsed.apply_difficulty(parse_value(&arg, args.next())?),
+                "--width" => parsed.width = parse_value(&arg, args.next())?,
+                "--height" => parsed.height = parse_value(&arg, args.next())?,
+                "--auto-fit" => parsed.auto_fit = true,
+                "--fullscreen" | "--fit" => parsed.fullscreen = true,
+                "--fps" | "--speed" => {
+                    let fps: u32 = parse_value(&arg, args.next())?;
+                    if fps == 0 {
+                        return Err(format!("{} must be at least 1", arg));
+                    }
+                    parsed.step_interval = Duration::from_secs(1) / fps;
+                }
+                "--no-speed-up" => parsed.speed_up = false,
+                "--name" => {
+                    let name: String = parse_value(&arg, args.next())?;
+                    // Left to the default when nothing in it can be shown
+                    parsed.name = Some(menu::sanitize_name(&name)).filter(|name| !name.is_empty());
+                }
+                "--no-menu" => parsed.no_menu = true,
+                "--walls" => parsed.walls = parse_value(&arg, args.next())?,
+                "--mode" => parsed.mode = parse_value(&arg, args.next())?,
+                "--time-attack" => {
+                    let seconds: u64 = parse_value(&arg, args.next())?;
+                    if seconds == 0 {
+                        return Err(format!("{} must be at least 1", arg));
+                    }
+                    parsed.mode = GameMode::Timed;
+                    parsed.time_limit = Some(Duration::from_secs(seconds));
+                }
+                "--rival" => parsed.rival = true,
+                "--practice" => parsed.practice = true,
+                "--demo" => parsed.demo = true,
+                "--two-player" => parsed.two_player = true,
+                "--food-count" => {
+                    parsed.food_count = parse_value(&arg, args.next())?;
+                    if parsed.food_count == 0 {
+                        return Err(format!("{} must be at least 1", arg));
+                    }
+                }
+                "--start-length" => parsed.start_length = parse_value(&arg, args.next())?,
+                "--moving-food" => parsed.food_behavior = FoodBehavior::Wandering,
+                "--fruit-weights" => {
+                    let weights: String = parse_value(&arg, args.next())?;
+                    parsed.fruit_weights = weights
+                        .parse()
+                        .map_err(|err| format!("invalid value for {}: {}", arg, err))?;
+                }
+                "--theme" => parsed.theme_file = Some(parse_value(&arg, args.next())?),
+                "--colorblind" => parsed.colorblind = true,
+                "--no-color" => parsed.no_color = true,
+                "--color" => parsed.color = parse_value(&arg, args.next())?,
+                "--ascii" => parsed.ascii = true,
+                "--reduce-motion" => parsed.reduce_motion = true,
+                "--commentary" => parsed.commentary = true,
+                "--sound" => parsed.sound = true,
+                "--relay" => {
+                    parsed.relay = parsed.relay.or(Some(relay::DEFAULT_TURN_LENGTH));
+                }
+                "--relay-seconds" => {
+                    let seconds: u64 = parse_value(&arg, args.next())?;
+                    if seconds == 0 {
+                        return Err(format!("{} must be at least 1", arg));
+                    }
+                    parsed.relay = Some(Duration::from_secs(seconds));
+                }
+                "--bind" => {
+                    let spec: String = parse_value(&arg, args.next())?;
+                    parsed
+                        .bindings
+                        .bind(&spec)
+                        .map_err(|err| format!("invalid value for {}: {}", arg, err))?;
+                }
+                "--seed" => parsed.seed = Some(parse_value(&arg, args.next())?),
+                "--obstacles" => parsed.arena = Arena::Obstacles(parse_value(&arg, args.next())?),
+                "--random-level" => {
+                    let density = match args.next_if(|value| !value.starts_with("--")) {
+                        Some(value) => parse_value(&arg, Some(value))?,
+                        None => level::DEFAULT_DENSITY,
+                    };
+                    if !(0.0..=level::MAX_DENSITY).contains(&density) {
+                        return Err(format!(
+                            "{} must be between 0 and {}",
+                            arg,
+                            level::MAX_DENSITY
+                        ));
+                    }
+                    parsed.arena = Arena::Random { density };
+                }
+                "--map" => parsed.map = Some(parse_value(&arg, args.next())?),
+                "--replay" => parsed.replay = Some(parse_value(&arg, args.next())?),
+                "--record" => parsed.record = Some(parse_value(&arg, args.next())?),
+                "--measure-latency" => parsed.measure_latency = true,
+                "--headless" => {
+                    let games = parse_value(&arg, args.next())?;
+                    if games == 0 {
+                        return Err("--headless needs at least one game".to_string());
+                    }
+                    parsed.headless = Some(games);
+                }
+                "--json" => parsed.json = true,
+                "--debug-hud" => parsed.debug_hud = true,
+                "--status-bar" => {
+                    parsed.status_bar = true;
+                    parsed.status_bar_asked = true;
+                }
+                "--cursor-fallback" => parsed.cursor_fallback = true,
+                "-h" | "--help" => parsed.help = true,
+                _ => return Err(format!("unknown argument '{}'", arg)),
+            }
+        }
+
+        if parsed.width < MIN_BOARD_SIZE || parsed.height < MIN_BOARD_SIZE {
+            return Err(format!(
+                "board must be at least {}x{}, got {}x{}",
+                MIN_BOARD_SIZE, MIN_BOARD_SIZE, parsed.width, parsed.height
+            ));
+        }
+        if parsed.two_player {
+            parsed.bindings.split_for_two_players();
+        }
+        parsed.bindings.validate()?;
+        if parsed.colorblind && parsed.theme_file.is_some() {
+            return Err("--colorblind cannot be combined with --theme".to_string());
+        }
+        if parsed.no_color && (parsed.colorblind || parsed.theme_file.is_some()) {
+            return Err("--no-color cannot be combined with --theme or --colorblind".to_string());
+        }
+        if parsed.no_color && parsed.color != ColorChoice::Auto {
+            return Err("--no-color cannot be combined with --color".to_string());
+        }
+        if parsed.fullscreen && (parsed.map.is_some() || parsed.replay.is_some()) {
+            return Err("--fullscreen cannot be combined with --map or --replay".to_string());
+        }
+        if parsed.arena != Arena::Open && parsed.map.is_some() {
+            return Err("--obstacles and --random-level cannot be combined with --map".to_string());
+        }
+        if parsed.time_limit.is_some() && parsed.mode != GameMode::Timed {
+            return Err("--time-attack cannot be combined with --mode".to_string());
+        }
+        if parsed.record.is_some() && (parsed.replay.is_some() || parsed.headless.is_some()) {
+            return Err("--record cannot be combined with --replay or --headless".to_string());
+        }
+        if parsed.two_player
+            && (parsed.rival || parsed.demo || parsed.relay.is_some() || parsed.headless.is_some())
+        {
+            return Err(
+                "--two-player cannot be combined with --rival, --demo, --relay or --headless"
+                    .to_string(),
+            );
+        }
+        if parsed.json && parsed.headless.is_none() {
+            return Err("--json only applies to --headless".to_string());
+        }
+        Ok(parsed)
+    }
+
+    // A game set up the way the options ask, on `level` when there is one
+    pub fn new_game(&self, level: Option<Level>, seed: u64) -> GameState {
+        let mut state = match level {
+            Some(level) => GameState::from_level(level, seed),
+            None => GameState::with_seed(self.width, self.height, seed),
+        }
+        .with_start_length(self.start_length)
+        .with_fruit_weights(self.fruit_weights)
+        .with_food_count(self.food_count)
+        .with_mode(self.mode);
+        // Only if the menu left the game timed
+        if let (GameMode::Timed, Some(limit)) = (self.mode, self.time_limit) {
+            state = state.with_time_limit(limit);
+        }
+        if self.rival {
+            state = state.with_rival(self.difficulty.preset().rival_replan);
+        }
+        if self.two_player {
+            state = state.with_second_player();
+        }
+        state.wall_mode = self.walls;
+        state.food_behavior = self.food_behavior;
+        state.practice = self.practice;
+        state.speed = self.speed();
+        state
+    }
+
+    pub fn apply_difficulty(&mut self, difficulty: Difficulty) {
+        let preset = difficulty.preset();
+        self.difficulty = difficulty;
+        self.width = preset.width;
+        self.height = preset.height;
+        self.step_interval = preset.speed.start;
+        self.walls = preset.walls;
+    }
+
+    // The difficulty's speed curve, starting at --fps if it was given
+    pub fn speed(&self) -> Speed {
+        if self.speed_up {
+            Speed {
+                start: self.step_interval,
+                ..self.difficulty.preset().speed
+            }
+        } else {
+            Speed::constant(self.step_interval)
+        }
+    }
+}
+
+fn parse_command(command: &str, mut args: impl Iterator<Item = String>) -> Result<Command, String> {
+    let parsed = match command {
+        "export-run" => {
+            if args.next().as_deref() != Some("--last") {
+                return Err("export-run only supports --last for now".to_string());
+            }
+            Command::ExportRun {
+                output: parse_value("--last", args.next())?,
+            }
+        }
+        "import-run" => Command::ImportRun {
+            path: parse_value(command, args.next())?,
+        },
+        _ => return Err(format!("unknown command '{}'", command)),
+    };
+    if let Some(arg) = args.next() {
+        return Err(format!("unexpected argument '{}' for {}", arg, command));
+    }
+    Ok(parsed)
+}
+
+fn parse_value<T: FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("{} expects a value", flag))?;
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{}' for {}", value, flag))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{ObstacleLayout, Position};
+    use crossterm::event::KeyCode;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        Args::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_defaults() {
+        assert_eq!(parse(&[]), Ok(Args::default()));
+
+        // The default board is the normal difficulty's
+        let normal = Difficulty::Normal.preset();
+        assert_eq!(
+            (normal.width, normal.height),
+            (DEFAULT_WIDTH, DEFAULT_HEIGHT)
+        );
+    }
+
+    #[test]
+    fn test_parse_all_options() {
+        let args = parse(&[
+            "--width",
+            "40",
+            "--height",
+            "20",
+            "--fps",
+            "10",
+            "--name",
+            "antoñito",
+            "--no-menu",
+            "--walls",
+            "wrap",
+            "--food-count",
+            "3",
+            "--obstacles",
+            "corners",
+            "--mode",
+            "lives",
+            "--rival",
+            "--practice",
+            "--demo",
+            "--debug-hud",
+            "--status-bar",
+            "--sound",
+            "--ascii",
+            "--reduce-motion",
+            "--color",
+            "never",
+        ])
+        .unwrap();
+        assert_eq!(args.width, 40);
+        assert_eq!(args.height, 20);
+        assert_eq!(args.step_interval, Duration::from_millis(100));
+        assert_eq!(args.name.as_deref(), Some("antoñito"));
+        assert!(args.no_menu);
+        assert_eq!(args.walls, WallMode::Wrap);
+        assert_eq!(args.food_count, 3);
+        assert_eq!(args.arena, Arena::Obstacles(ObstacleLayout::CornerBlocks));
+        assert_eq!(args.mode, GameMode::Lives);
+        assert!(args.rival);
+        assert!(args.practice);
+        assert!(args.demo);
+        assert!(args.debug_hud);
+        assert!(args.status_bar);
+        assert!(args.status_bar_asked);
+        assert!(args.sound);
+        assert!(args.ascii);
+        assert!(args.reduce_motion);
+        assert_eq!(args.color, ColorChoice::Never);
+        assert!(args.speed_up);
+        assert!(args.speed().max_level() > 0);
+    }
+
+    #[test]
+    fn test_difficulty_presets() {
+        let args = parse(&["--difficulty", "hard"]).unwrap();
+        let hard = Difficulty::Hard.preset();
+        assert_eq!(args.difficulty, Difficulty::Hard);
+        assert_eq!((args.width, args.height), (hard.width, hard.height));
+        assert_eq!(args.speed(), hard.speed);
+
+        // Options after the preset tweak it
+        let args = parse(&["--difficulty", "easy", "--walls", "solid", "--fps", "5"]).unwrap();
+        assert_eq!(args.walls, WallMode::Solid);
+        assert_eq!(args.speed().start, Duration::from_millis(200));
+        assert_eq!(
+            args.speed().points_per_step,
+            Difficulty::Easy.preset().speed.points_per_step
+        );
+
+        assert!(parse(&["--difficulty", "nightmare"]).is_err());
+        assert_eq!(
+            parse(&["--map", "level1.txt"]).unwrap().map,
+            Some(PathBuf::from("level1.txt"))
+        );
+    }
+
+    #[test]
+    fn test_random_level_density_is_optional() {
+        assert_eq!(
+            parse(&["--random-level", "--seed", "3"]).unwrap().arena,
+            Arena::Random {
+                density: level::DEFAULT_DENSITY
+            }
+        );
+        assert_eq!(
+            parse(&["--random-level", "0.3"]).unwrap().arena,
+            Arena::Random { density: 0.3 }
+        );
+        assert_eq!(
+            parse(&["--random-level"]).unwrap().arena,
+            Arena::Random {
+                density: level::DEFAULT_DENSITY
+            }
+        );
+    }
+
+    #[test]
+    fn test_key_bindings() {
+        // The old 's' to stop, with down back to the arrow only
+        let args = parse(&["--bind", "pause=s", "--bind", "down=down"]).unwrap();
+        assert_eq!(args.bindings.pause, vec![KeyCode::Char('s')]);
+        assert_eq!(args.bindings.down, vec![KeyCode::Down]);
+        assert!(parse(&["--bind", "pause=s"]).is_err());
+
+        // 'p' still pauses unless pause is moved too
+        assert!(parse(&["--bind", "up=p"]).is_err());
+        assert!(parse(&["--bind", "up"]).is_err());
+    }
+
+    #[test]
+    fn test_two_players() {
+        let args = parse(&["--two-player"]).unwrap();
+        assert_eq!(args.bindings.second_up, vec![KeyCode::Char('w')]);
+        assert_eq!(args.bindings.up, vec![KeyCode::Up]);
+        assert!(args.new_game(None, 1).is_two_player());
+        // The pause key can't also be the second player's down
+        assert!(parse(&["--two-player", "--bind", "pause=s"]).is_err());
+        assert!(parse(&["--two-player", "--rival"]).is_err());
+    }
+
+    #[test]
+    fn test_no_speed_up() {
+        let args = parse(&["--fps", "10", "--no-speed-up"]).unwrap();
+        assert!(!args.speed_up);
+        assert_eq!(args.speed(), Speed::constant(Duration::from_millis(100)));
+    }
+
+    #[test]
+    fn test_names_go_through_the_menu_filter() {
+        let args = parse(&["--name", "名前 antonio-the-great\n"]).unwrap();
+        assert_eq!(args.name.as_deref(), Some("antonio-the"));
+        assert_eq!(parse(&["--name", "🐍"]).unwrap().name, None);
+    }
+
+    #[test]
+    fn test_fruit_weights() {
+        let args = parse(&["--fruit-weights", "1,0,4,2"]).unwrap();
+        assert_eq!(args.fruit_weights, FruitWeights([1, 0, 4, 2]));
+        let args = parse(&["--fruit-weights", "12,6,2"]).unwrap();
+        assert_eq!(args.fruit_weights, FruitWeights([12, 6, 2, 0]));
+        assert!(parse(&["--fruit-weights", "1,2"]).is_err());
+        assert_eq!(
+            parse(&["--fruit-weights", "0,0,0,0"]),
+            Err(
+                "invalid value for --fruit-weights: at least one fruit weight must be above zero"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_invalid_combinations() {
+        assert!(parse(&["--width", "4"]).is_err());
+        assert!(parse(&["--height", "2"]).is_err());
+        assert!(parse(&["--fps", "0"]).is_err());
+        assert!(parse(&["--speed", "fast"]).is_err());
+        assert!(parse(&["--name"]).is_err());
+        assert!(parse(&["--bogus"]).is_err());
+        assert!(parse(&["--relay-seconds", "0"]).is_err());
+        assert!(parse(&["--walls", "lava"]).is_err());
+        assert!(parse(&["--mode", "zen"]).is_err());
+        assert!(parse(&["--food-count", "0"]).is_err());
+        assert!(parse(&["--obstacles", "spiral"]).is_err());
+        assert!(parse(&["--obstacles", "bar", "--map", "level1.txt"]).is_err());
+        assert!(parse(&["--random-level", "0.9"]).is_err());
+        assert!(parse(&["--random-level", "lots"]).is_err());
+        assert!(parse(&["--colorblind", "--theme", "mine.toml"]).is_err());
+        assert!(parse(&["--no-color", "--colorblind"]).is_err());
+        assert!(parse(&["--no-color", "--color", "always"]).is_err());
+        assert!(parse(&["--color", "sometimes"]).is_err());
+        assert!(parse(&["--fullscreen", "--map", "level1.txt"]).is_err());
+        assert!(parse(&["--fit", "--replay", "last.replay"]).is_err());
+        assert!(parse(&["--time-attack", "0"]).is_err());
+        assert!(parse(&["--record", "a.replay", "--replay", "b.replay"]).is_err());
+        assert!(parse(&["--record", "a.replay", "--headless", "10"]).is_err());
+        assert!(parse(&["--record"]).is_err());
+        assert!(parse(&["--time-attack", "60", "--mode", "lives"]).is_err());
+    }
+
+    #[test]
+    fn test_time_attack() {
+        let args = parse(&["--time-attack", "60"]).unwrap();
+        assert_eq!(args.mode, GameMode::Timed);
+        assert_eq!(args.time_limit, Some(Duration::from_secs(60)));
+        let state = args.new_game(None, 1);
+        assert_eq!(state.time_limit, Some(Duration::from_secs(60)));
+        assert_eq!(state.time_left, Some(Duration::from_secs(60)));
+
+        // Another mode picked in the menu plays without the clock
+        let args = Args {
+            mode: GameMode::Classic,
+            ..args
+        };
+        assert_eq!(args.new_game(None, 1).time_left, None);
+    }
+
+    #[test]
+    fn test_start_length() {
+        let args = parse(&["--width", "20", "--start-length", "3"]).unwrap();
+        let state = args.new_game(None, 1);
+        assert_eq!(state.snake.len(), 4);
+        assert_eq!(state.snake.tail.back(), Some(&Position::new(7, 7)));
+
+        // Cut short by the left wall
+        let args = parse(&["--width", "20", "--start-length", "50"]).unwrap();
+        assert_eq!(args.new_game(None, 1).snake.tail.len(), 9);
+        assert!(parse(&["--start-length", "-1"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_commands() {
+        assert_eq!(
+            parse(&["export-run", "--last", "out.runcard"])
+                .unwrap()
+                .command,
+            Some(Command::ExportRun {
+                output: PathBuf::from("out.runcard")
+            })
+        );
+        assert_eq!(
+            parse(&["import-run", "in.runcard"]).unwrap().command,
+            Some(Command::ImportRun {
+                path: PathBuf::from("in.runcard")
+            })
+        );
+        assert!(parse(&["export-run", "out.runcard"]).is_err());
+        assert!(parse(&["export-run", "--last"]).is_err());
+        assert!(parse(&["import-run"]).is_err());
+        assert!(parse(&["import-run", "a", "b"]).is_err());
+        assert!(parse(&["play"]).is_err());
+    }
+}
@@ -0,0 +1,367 @@
+use crate::{
+    game::{GameState, Position},
+    theme::Theme,
+};
+use crossterm::{
+    cursor, queue,
+    style::{self, Stylize},
+    terminal,
+};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use std::{io::Write, time::Duration};
+
+// A template is not reused until this much game time has passed
+pub const REPEAT_WINDOW: Duration = Duration::from_secs(60);
+// Foods eaten at most this many ticks apart keep the streak going
+const STREAK_TICKS: u64 = 40;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Moment {
+    FoodEaten,
+    // Food eaten right next to the wall
+    WallRide,
+    // The head passed right next to the snake's own body
+    NearMiss,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum Intensity {
+    Calm,
+    Warm,
+    Hot,
+}
+
+impl Intensity {
+    pub fn from_streak(streak: u32) -> Self {
+        match streak {
+            0..=2 => Intensity::Calm,
+            3..=4 => Intensity::Warm,
+            _ => Intensity::Hot,
+        }
+    }
+}
+
+pub struct Template {
+    pub moment: Moment,
+    pub intensity: Intensity,
+    pub text: &'static str,
+}
+
+// Placeholders: {name}, {score}, {streak}, {length}
+pub const TEMPLATES: &[Template] = &[
+    Template {
+        moment: Moment::FoodEaten,
+        intensity: Intensity::Calm,
+        text: "{name} grabs a snack, score {score}",
+    },
+    Template {
+        moment: Moment::FoodEaten,
+        intensity: Intensity::Calm,
+        text: "Another one down, {name} is at {score}",
+    },
+    Template {
+        moment: Moment::FoodEaten,
+        intensity: Intensity::Calm,
+        text: "The snake stretches to {length}",
+    },
+    Template {
+        moment: Moment::FoodEaten,
+        intensity: Intensity::Warm,
+        text: "That's {streak} in a row, the combo is heating up",
+    },
+    Template {
+        moment: Moment::FoodEaten,
+        intensity: Intensity::Warm,
+        text: "{name} is finding a rhythm: {streak} quick bites",
+    },
+    Template {
+        moment: Moment::FoodEaten,
+        intensity: Intensity::Hot,
+        text: "{streak} STRAIGHT! {name} is on fire!",
+    },
+    Template {
+        moment: Moment::FoodEaten,
+        intensity: Intensity::Hot,
+        text: "Unstoppable! {streak} in a row and counting!",
+    },
+    Template {
+        moment: Moment::WallRide,
+        intensity: Intensity::Calm,
+        text: "A risky wall ride pays off: score {score}",
+    },
+    Template {
+        moment: Moment::WallRide,
+        intensity: Intensity::Hot,
+        text: "Scraping the wall at full speed, {name} doesn't care!",
+    },
+    Template {
+        moment: Moment::NearMiss,
+        intensity: Intensity::Calm,
+        text: "{name} threads the needle past their own tail!",
+    },
+    Template {
+        moment: Moment::NearMiss,
+        intensity: Intensity::Calm,
+        text: "Close one! That was nearly a bite",
+    },
+    Template {
+        moment: Moment::NearMiss,
+        intensity: Intensity::Hot,
+        text: "Living dangerously, {name} brushes past {length} segments!",
+    },
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Context<'a> {
+    pub name: &'a str,
+    pub score: u32,
+    pub streak: u32,
+    pub length: usize,
+}
+
+// When each template was last used, indexed like TEMPLATES
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    last_used: Vec<Option<Duration>>,
+}
+
+impl History {
+    pub fn record(&mut self, template: usize, now: Duration) {
+        if self.last_used.len() <= template {
+            self.last_used.resize(template + 1, None);
+        }
+        self.last_used[template] = Some(now);
+    }
+
+    pub fn is_recent(&self, template: usize, now: Duration) -> bool {
+        self.last_used
+            .get(template)
+            .copied()
+            .flatten()
+            .is_some_and(|used| now.saturating_sub(used) < REPEAT_WINDOW)
+    }
+}
+
+pub fn fill(text: &str, context: &Context) -> String {
+    text.replace("{name}", context.name)
+        .replace("{score}", &context.score.to_string())
+        .replace("{streak}", &context.streak.to_string())
+        .replace("{length}", &context.length.to_string())
+}
+
+// Picks a template for the moment at the given intensity, preferring the most
+// excited tier available, and skipping anything said in the last minute.
+// Returns the template index and the filled-in line.
+pub fn pick(
+    moment: Moment,
+    context: &Context,
+    history: &History,
+    now: Duration,
+    rng: &mut impl Rng,
+) -> Option<(usize, String)> {
+    let intensity = Intensity::from_streak(context.streak);
+    let candidates: Vec<usize> = TEMPLATES
+        .iter()
+        .enumerate()
+        .filter(|(index, template)| {
+            template.moment == moment
+                && template.intensity <= intensity
+                && !history.is_recent(*index, now)
+        })
+        .map(|(index, _)| index)
+        .collect();
+
+    let top = candidates
+        .iter()
+        .map(|&index| TEMPLATES[index].intensity)
+        .fold(None, |top: Option<Intensity>, tier| match top {
+            Some(top) if top >= tier => Some(top),
+            _ => Some(tier),
+        })?;
+    let index = *candidates
+        .iter()
+        .filter(|&&index| TEMPLATES[index].intensity == top)
+        .collect::<Vec<_>>()
+        .choose(rng)?;
+    Some((*index, fill(TEMPLATES[*index].text, context)))
+}
+
+// Works out what was worth commenting on in the tick that just happened
+pub fn detect(state: &GameState, ate_food: bool) -> Option<Moment> {
+    let head = state.snake.head;
+    if ate_food {
+        let bounds = state.bounds;
+        let by_the_wall = head.x == bounds.left + 1
+            || head.y == bounds.top + 1
+            || head.x == bounds.right - 1
+            || head.y == bounds.bottom - 1;
+        return Some(if by_the_wall {
+            Moment::WallRide
+        } else {
+            Moment::FoodEaten
+        });
+    }
+
+    // The first tail segment is always next to the head
+    let near_miss = state
+        .snake
+        .tail
+        .iter()
+        .skip(2)
+        .any(|&segment| is_adjacent(head, segment));
+    near_miss.then_some(Moment::NearMiss)
+}
+
+fn is_adjacent(a: Position, b: Position) -> bool {
+    a.x.abs_diff(b.x) + a.y.abs_diff(b.y) == 1
+}
+
+// The ticker under the board
+pub struct Commentator {
+    pub line: String,
+    history: History,
+    streak: u32,
+    last_food_tick: Option<u64>,
+    rng: StdRng,
+}
+
+impl Commentator {
+    pub fn new() -> Self {
+        Self {
+            line: String::new(),
+            history: History::default(),
+            streak: 0,
+            last_food_tick: None,
+            // Cosmetic only, kept apart from the game RNG so replays stay exact
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    pub fn observe(&mut self, state: &GameState, ate_food: bool, name: &str, now: Duration) {
+        if ate_food {
+            let in_streak = self
+                .last_food_tick
+                .is_some_and(|tick| state.ticks.saturating_sub(tick) <= STREAK_TICKS);
+            self.streak = if in_streak { self.streak + 1 } else { 1 };
+            self.last_food_tick = Some(state.ticks);
+        }
+
+        let Some(moment) = detect(state, ate_food) else {
+            return;
+        };
+        let context = Context {
+            name,
+            score: state.score,
+            streak: self.streak,
+            length: state.snake.len(),
+        };
+        if let Some((template, line)) = pick(moment, &context, &self.history, now, &mut self.rng) {
+            self.history.record(template, now);
+            self.line = line;
+        }
+    }
+
+    pub fn queue(
+        &self,
+        stdout: &mut impl Write,
+        y: u16,
+        width: u16,
+        theme: &Theme,
+    ) -> std::io::Result<()> {
+        let line: String = self.line.chars().take(width as usize).collect();
+        queue!(
+            stdout,
+            cursor::MoveTo(0, y),
+            terminal::Clear(terminal::ClearType::CurrentLine),
+            style::PrintStyledContent(line.with(theme.inactive))
+        )
+    }
+}
+
+impl Default for Commentator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(streak: u32) -> Context<'static> {
+        Context {
+            name: "antoñito",
+            score: 7,
+            streak,
+            length: 8,
+        }
+    }
+
+    #[test]
+    fn test_placeholders_are_filled() {
+        assert_eq!(
+            fill(
+                "{name} has {score} after {streak}, length {length}",
+                &context(2)
+            ),
+            "antoñito has 7 after 2, length 8"
+        );
+    }
+
+    #[test]
+    fn test_repetition_avoidance() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut history = History::default();
+        let now = Duration::from_secs(10);
+
+        // Only two calm near-miss templates exist, then the commentator goes quiet
+        let (first, _) = pick(Moment::NearMiss, &context(0), &history, now, &mut rng).unwrap();
+        history.record(first, now);
+        let (second, _) = pick(Moment::NearMiss, &context(0), &history, now, &mut rng).unwrap();
+        assert_ne!(first, second);
+        history.record(second, now);
+        assert!(pick(Moment::NearMiss, &context(0), &history, now, &mut rng).is_none());
+
+        // A minute later the templates are available again
+        let later = now + REPEAT_WINDOW;
+        assert!(pick(Moment::NearMiss, &context(0), &history, later, &mut rng).is_some());
+    }
+
+    #[test]
+    fn test_intensity_tiers() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let history = History::default();
+        let now = Duration::ZERO;
+
+        for (streak, expected) in [
+            (1, Intensity::Calm),
+            (3, Intensity::Warm),
+            (6, Intensity::Hot),
+        ] {
+            assert_eq!(Intensity::from_streak(streak), expected);
+            for _ in 0..10 {
+                let (index, _) =
+                    pick(Moment::FoodEaten, &context(streak), &history, now, &mut rng).unwrap();
+                assert_eq!(TEMPLATES[index].intensity, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_detect_moments() {
+        let mut state = GameState::with_seed(30, 15, 1);
+        assert_eq!(detect(&state, true), Some(Moment::FoodEaten));
+        assert_eq!(detect(&state, false), None);
+
+        state.snake.head = Position::new(1, 5);
+        assert_eq!(detect(&state, true), Some(Moment::WallRide));
+
+        // Head at (5, 5) with the body curling back next to it
+        state.snake.head = Position::new(5, 5);
+        state.snake.tail = [(4, 5), (4, 6), (5, 6)]
+            .into_iter()
+            .map(|(x, y)| Position::new(x, y))
+            .collect();
+        assert_eq!(detect(&state, false), Some(Moment::NearMiss));
+    }
+}
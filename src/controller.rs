@@ -0,0 +1,742 @@
+use crate::{
+    game::{Bounds, Direction, GameState, InputBuffer, Position, Snake, WallMode, PLAYER},
+    input::InputCommand,
+};
+use std::collections::VecDeque;
+
+// Whatever decides where a snake goes next: the keyboard, a bot or a script.
+// Object safe, so a game can hold any mix of them as `Box<dyn Controller>`.
+pub trait Controller {
+    // The turn for the snake's next tick, None to keep going the same way
+    fn decide(&mut self, state: &GameState, snake_id: usize) -> Option<Direction>;
+
+    // Every command is offered here before the game handles it, returns true
+    // if it was taken
+    fn handle(&mut self, _command: InputCommand) -> bool {
+        false
+    }
+
+    // Forgets anything queued up, for when the snake starts over
+    fn reset(&mut self) {}
+}
+
+// Turns from the arrow keys, buffered so quick presses land on later ticks
+#[derive(Debug, Default)]
+pub struct KeyboardController {
+    input: InputBuffer,
+    // Takes the second player's turns rather than the first's
+    second_player: bool,
+}
+
+impl KeyboardController {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn second_player() -> Self {
+        Self {
+            second_player: true,
+            ..Self::default()
+        }
+    }
+}
+
+impl Controller for KeyboardController {
+    fn decide(&mut self, state: &GameState, snake_id: usize) -> Option<Direction> {
+        let snake = state.snake_by_id(snake_id)?;
+        self.input.pop_valid(snake.last_moved_direction)
+    }
+
+    fn handle(&mut self, command: InputCommand) -> bool {
+        let direction = match command {
+            InputCommand::Turn(direction) if !self.second_player => direction,
+            InputCommand::SecondTurn(direction) if self.second_player => direction,
+            _ => return false,
+        };
+        self.input.push(direction);
+        true
+    }
+
+    fn reset(&mut self) {
+        self.input = InputBuffer::new();
+    }
+}
+
+// Plays a fixed list of moves, one a tick, then keeps going straight
+#[derive(Debug, Default)]
+pub struct ScriptedController {
+    moves: VecDeque<Option<Direction>>,
+}
+
+impl ScriptedController {
+    pub fn new(moves: impl IntoIterator<Item = Option<Direction>>) -> Self {
+        Self {
+            moves: moves.into_iter().collect(),
+        }
+    }
+}
+
+impl Controller for ScriptedController {
+    fn decide(&mut self, _state: &GameState, _snake_id: usize) -> Option<Direction> {
+        self.moves.pop_front().flatten()
+    }
+}
+
+// A bot that heads for the nearest food along the shortest free path, and
+// when there is none takes any move that does not crash. The path is only
+// searched for again once it is used up, blocked or its food has gone, so
+// most ticks cost a few lookups rather than a search.
+#[derive(Debug, Clone)]
+pub struct AutoController {
+    // Ticks a path is still followed after its food has gone before looking
+    // for other food, the higher the easier it is to beat
+    pub replan_every: u32,
+    path: VecDeque<Direction>,
+    // The food the path leads to, and the cell its next step starts from
+    target: Option<Position>,
+    path_from: Option<usize>,
+    since_plan: u32,
+    board: Board,
+    // One for paths, which remembers food out of reach, one for the room
+    // each move leaves
+    search: Search,
+    flood: Search,
+}
+
+impl AutoController {
+    pub fn new(replan_every: u32) -> Self {
+        Self {
+            replan_every: replan_every.max(1),
+            path: VecDeque::new(),
+            target: None,
+            path_from: None,
+            since_plan: 0,
+            board: Board::default(),
+            search: Search::default(),
+            flood: Search::default(),
+        }
+    }
+}
+
+impl Controller for AutoController {
+    fn decide(&mut self, state: &GameState, snake_id: usize) -> Option<Direction> {
+        let snake = state.snake_by_id(snake_id)?;
+        let heading = snake.last_moved_direction;
+        let change = self.board.update(state);
+        let board = &self.board;
+        let head = board.index(snake.head);
+        let is_safe = |direction: Direction| {
+            // Reversing runs into the neck, or crashes a snake that is all head
+            direction != heading.reverse() && board.free[board.step(head, direction)]
+        };
+
+        let food_gone = self
+            .target
+            .is_none_or(|target| state.food_at(target).is_none());
+        let stale = food_gone && self.since_plan >= self.replan_every;
+        let next_step = self
+            .path
+            .front()
+            .copied()
+            .filter(|_| self.path_from == Some(head));
+        if stale || next_step.is_none_or(|direction| !is_safe(direction)) {
+            self.target =
+                self.search
+                    .shortest_path(state, board, change, head, heading, &mut self.path);
+            self.since_plan = 0;
+        }
+        self.since_plan += 1;
+
+        // A path into a pocket too small for the snake is a trap, better to
+        // take the move with the most room and look again next tick. Only
+        // where the cells round the head don't join up can the moves lead
+        // into pockets of different sizes.
+        let room_needed = snake.len() + 1;
+        let split = !board.joined_around(head);
+        let flood = &mut self.flood;
+        let mut rooms = [None; Direction::ALL.len()];
+        let mut room = |direction: Direction| {
+            if !split || !is_safe(direction) {
+                return 0;
+            }
+            *rooms[direction as usize]
+                .get_or_insert_with(|| flood.room(board, board.step(head, direction), room_needed))
+        };
+        let planned = self
+            .path
+            .pop_front()
+            .filter(|&direction| !split || room(direction) >= room_needed);
+        let direction = match planned {
+            Some(direction) => direction,
+            None => {
+                self.path.clear();
+                self.target = None;
+                Direction::ALL
+                    .into_iter()
+                    .filter(|&direction| is_safe(direction))
+                    .max_by_key(|&direction| room(direction))
+                    .unwrap_or(snake.direction)
+            }
+        };
+        self.path_from = Some(board.step(head, direction));
+        (direction != snake.direction).then_some(direction)
+    }
+
+    fn reset(&mut self) {
+        self.path.clear();
+        self.target = None;
+        self.path_from = None;
+        self.since_plan = 0;
+        self.board.synced = None;
+        self.search.shut_in = None;
+    }
+}
+
+// The way the bot at its sharpest would take the player's snake next, for
+// when only the one move is wanted
+pub fn autopilot_direction(state: &GameState) -> Direction {
+    AutoController::new(1)
+        .decide(state, PLAYER)
+        .unwrap_or(state.snake.direction)
+}
+
+// Where a move from `position` lands, around the board in wrap mode
+fn step(state: &GameState, position: Position, direction: Direction) -> Position {
+    let next = position.move_direction(direction);
+    match state.wall_mode {
+        WallMode::Wrap => state.wrap_around(next),
+        WallMode::Solid => next,
+    }
+}
+
+// The board as the bot sees it, cell by cell: which ones a snake can move
+// into and where each move from each one lands. Kept from one decision to
+// the next and, when all that changed is the player's snake moving a cell
+// on, brought up to date from the two cells that changed rather than worked
+// out again.
+#[derive(Debug, Default)]
+struct Board {
+    width: usize,
+    height: usize,
+    // Both have a last cell past the board's that is never free, and that
+    // any move off the board leads to
+    free: Vec<bool>,
+    moves: Vec<[usize; Direction::ALL.len()]>,
+    // The board the moves were worked out for
+    layout: Option<(u16, u16, Bounds, WallMode)>,
+    // The game the free cells were last brought up to date for, None when
+    // the next decision has to work them all out again
+    synced: Option<Synced>,
+}
+
+// A copy starts from nothing, the rival's history keeps one every tick
+impl Clone for Board {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+// What changed on the board since the decision before
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Change {
+    // Just the snake moving on, leaving the cell if it didn't grow
+    Moved(Option<usize>),
+    // Anything at all
+    Anything,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Synced {
+    ticks: u64,
+    head: Position,
+    // The snake's last cell, the one it leaves when it moves without growing
+    end: Position,
+    len: usize,
+    obstacles: usize,
+}
+
+impl Board {
+    fn update(&mut self, state: &GameState) -> Change {
+        let layout = (
+            state.game_width,
+            state.game_height,
+            state.bounds,
+            state.wall_mode,
+        );
+        if self.layout != Some(layout) {
+            self.lay_out(state);
+            self.layout = Some(layout);
+            self.synced = None;
+        }
+
+        let snake = &state.snake;
+        let now = Synced {
+            ticks: state.ticks(),
+            head: snake.head,
+            end: snake.tail.back().copied().unwrap_or(snake.head),
+            len: snake.len(),
+            obstacles: state.obstacles.len(),
+        };
+        let change = match self.synced {
+            Some(before) => self.follow(before, now, snake),
+            None => Change::Anything,
+        };
+        let overlapping = change == Change::Anything && !self.work_out(state);
+        // A rival is not followed, and neither is a snake that can pass
+        // through walls and itself
+        let followed = state.rival.is_none() && !state.practice && !state.is_phasing();
+        self.synced = (followed && !overlapping).then_some(now);
+        change
+    }
+
+    // Where every move from every cell lands
+    fn lay_out(&mut self, state: &GameState) {
+        self.width = state.game_width as usize;
+        self.height = state.game_height as usize;
+        let off_board = self.off_board();
+        self.moves.clear();
+        for y in 0..state.game_height {
+            for x in 0..state.game_width {
+                let moves = Direction::ALL
+                    .map(|direction| self.index(step(state, Position::new(x, y), direction)));
+                self.moves.push(moves);
+            }
+        }
+        self.moves.push([off_board; Direction::ALL.len()]);
+    }
+
+    // Frees the cell the snake left and takes the one its head moved into,
+    // when it moved a cell on since the decision before
+    fn follow(&mut self, before: Synced, now: Synced, snake: &Snake) -> Change {
+        if now.obstacles != before.obstacles {
+            return Change::Anything;
+        }
+        if now.ticks == before.ticks && now == before {
+            return Change::Moved(None);
+        }
+        let grew = now.len == before.len + 1;
+        let moved = now.ticks == before.ticks + 1
+            && (grew || now.len == before.len)
+            && (!grew || now.end == before.end)
+            && snake.tail.front().is_none_or(|&neck| neck == before.head);
+        if !moved {
+            return Change::Anything;
+        }
+        let left = (!grew)
+            .then(|| self.index(before.end))
+            .filter(|&cell| cell != self.off_board());
+        if let Some(left) = left {
+            self.free[left] = true;
+        }
+        // Into an occupied cell is a crash, or the snake is somewhere else
+        let head = self.index(now.head);
+        if !self.free[head] {
+            return Change::Anything;
+        }
+        self.free[head] = false;
+        Change::Moved(left)
+    }
+
+    // Works every cell out again, false when a snake lies on a cell
+    // something else already took
+    fn work_out(&mut self, state: &GameState) -> bool {
+        self.free.clear();
+        for y in 0..state.game_height {
+            for x in 0..state.game_width {
+                self.free.push(!state.bounds.is_wall(Position::new(x, y)));
+            }
+        }
+        self.free.push(false);
+        for &position in &state.obstacles {
+            let index = self.index(position);
+            self.free[index] = false;
+        }
+        let snakes = [
+            Some(&state.snake),
+            state.rival.as_ref().map(|rival| &rival.snake),
+        ];
+        let mut apart = true;
+        for snake in snakes.into_iter().flatten() {
+            for &position in std::iter::once(&snake.head).chain(&snake.tail) {
+                let index = self.index(position);
+                apart &= self.free[index];
+                self.free[index] = false;
+            }
+        }
+        apart
+    }
+
+    // The cell's index, the one past the board's for a position off it
+    fn index(&self, position: Position) -> usize {
+        let (x, y) = (position.x as usize, position.y as usize);
+        if x < self.width && y < self.height {
+            y * self.width + x
+        } else {
+            self.off_board()
+        }
+    }
+
+    fn off_board(&self) -> usize {
+        self.width * self.height
+    }
+
+    fn step(&self, cell: usize, direction: Direction) -> usize {
+        self.moves[cell][direction as usize]
+    }
+
+    // Whether the free cells next to `cell` join up round it through the
+    // corners between them, so they are all in the same pocket whichever the
+    // snake moves into
+    fn joined_around(&self, cell: usize) -> bool {
+        const CLOCKWISE: [Direction; 4] = [
+            Direction::Up,
+            Direction::Right,
+            Direction::Down,
+            Direction::Left,
+        ];
+        // The ring of cells round `cell`, each side followed by the corner
+        // after it
+        let mut ring = [(false, false); 8];
+        for (turn, direction) in CLOCKWISE.into_iter().enumerate() {
+            let side = self.step(cell, direction);
+            let corner = self.step(side, CLOCKWISE[(turn + 1) % CLOCKWISE.len()]);
+            ring[turn * 2] = (self.free[side], true);
+            ring[turn * 2 + 1] = (self.free[corner], false);
+        }
+        let Some(blocked) = ring.iter().position(|&(free, _)| !free) else {
+            return true;
+        };
+        // Going round from a blocked cell, count the runs of free cells with
+        // a side in them
+        let mut runs = 0;
+        let mut side_in_run = false;
+        for offset in 1..=ring.len() {
+            let (free, is_side) = ring[(blocked + offset) % ring.len()];
+            if free {
+                side_in_run |= is_side;
+            } else {
+                runs += usize::from(side_in_run);
+                side_in_run = false;
+            }
+        }
+        runs <= 1
+    }
+}
+
+// What a search keeps between decisions so it doesn't allocate: a stamp on
+// each cell reached, which saves clearing them all before the next search,
+// the move that reached it and the queues
+#[derive(Debug, Default)]
+struct Search {
+    stamp: u32,
+    reached: Vec<u32>,
+    came_by: Vec<Direction>,
+    queue: VecDeque<usize>,
+    food_queue: VecDeque<usize>,
+    // The cells with food, as of the last search
+    foods: Vec<usize>,
+    // After a search found all food out of reach, the stamp on the pocket it
+    // looked all through
+    shut_in: Option<u32>,
+}
+
+// A copy starts from nothing, like `Board`
+impl Clone for Search {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl Search {
+    // A stamp no cell has yet
+    fn next_stamp(&mut self, board: &Board) -> u32 {
+        let cells = board.free.len();
+        if self.reached.len() != cells {
+            self.reached = vec![0; cells];
+            self.came_by = vec![Direction::Up; cells];
+            self.stamp = 0;
+        }
+        self.stamp = self.stamp.wrapping_add(1);
+        if self.stamp == 0 {
+            self.reached.fill(0);
+            self.stamp = 1;
+        }
+        self.stamp
+    }
+
+    fn start(&mut self, board: &Board, from: usize) {
+        let stamp = self.next_stamp(board);
+        self.reached[from] = stamp;
+        self.queue.clear();
+        self.queue.push_back(from);
+    }
+
+    // Marks a free cell not reached before as reached by `direction`
+    fn reach(&mut self, board: &Board, cell: usize, direction: Direction) -> bool {
+        if !board.free[cell] || self.reached[cell] == self.stamp {
+            return false;
+        }
+        self.reached[cell] = self.stamp;
+        self.came_by[cell] = direction;
+        self.queue.push_back(cell);
+        true
+    }
+
+    // Breadth first from the head to the closest food, over free cells. Fills
+    // `path` with the moves there and returns where the food is.
+    fn shortest_path(
+        &mut self,
+        state: &GameState,
+        board: &Board,
+        change: Change,
+        head: usize,
+        heading: Direction,
+        path: &mut VecDeque<Direction>,
+    ) -> Option<Position> {
+        path.clear();
+        let food_moved = !self
+            .foods
+            .iter()
+            .copied()
+            .eq(state.foods.iter().map(|food| board.index(food.position)));
+        if food_moved {
+            self.foods.clear();
+            self.foods
+                .extend(state.foods.iter().map(|food| board.index(food.position)));
+            self.shut_in = None;
+        }
+        if self.still_shut_in(board, change) || !self.connected(board, head, heading) {
+            return None;
+        }
+
+        self.start(board, head);
+        while let Some(cell) = self.queue.pop_front() {
+            if cell != head && self.foods.contains(&cell) {
+                let food = state.foods[self.foods.iter().position(|&food| food == cell)?];
+                let mut cell = cell;
+                while cell != head {
+                    let direction = self.came_by[cell];
+                    path.push_front(direction);
+                    cell = board.step(cell, direction.reverse());
+                }
+                return Some(food.position);
+            }
+            for direction in Direction::ALL {
+                if cell != head || direction != heading.reverse() {
+                    self.reach(board, board.step(cell, direction), direction);
+                }
+            }
+        }
+        None
+    }
+
+    // Whether the food is still out of reach as it was last time: only the
+    // snake has moved since, and the cell it left does not open up the
+    // pocket that was shut in
+    fn still_shut_in(&mut self, board: &Board, change: Change) -> bool {
+        let Some(pocket) = self.shut_in else {
+            return false;
+        };
+        let opened = match change {
+            Change::Moved(left) => left.is_some_and(|cell| {
+                Direction::ALL
+                    .into_iter()
+                    .any(|direction| self.reached[board.step(cell, direction)] == pocket)
+            }),
+            Change::Anything => true,
+        };
+        if opened {
+            self.shut_in = None;
+        }
+        !opened
+    }
+
+    // Whether any food can be reached from the head. Searched from both ends
+    // a cell at a time, so food shut off from the head is found out as soon
+    // as the smaller of the two pockets has been looked through.
+    fn connected(&mut self, board: &Board, head: usize, heading: Direction) -> bool {
+        self.start(board, head);
+        let from_head = self.stamp;
+        self.queue.clear();
+        for direction in Direction::ALL {
+            if direction != heading.reverse() {
+                self.reach(board, board.step(head, direction), direction);
+            }
+        }
+        let from_food = self.next_stamp(board);
+        self.food_queue.clear();
+        for &food in &self.foods {
+            if self.reached[food] == from_head {
+                return true;
+            }
+            if board.free[food] {
+                self.reached[food] = from_food;
+                self.food_queue.push_back(food);
+            }
+        }
+
+        // Each side takes a cell in turn until it meets the other or has
+        // none left, which shuts it in
+        loop {
+            for (own, other) in [(from_head, from_food), (from_food, from_head)] {
+                let queue = if own == from_head {
+                    &mut self.queue
+                } else {
+                    &mut self.food_queue
+                };
+                let Some(cell) = queue.pop_front() else {
+                    self.shut_in = Some(own);
+                    return false;
+                };
+                for direction in Direction::ALL {
+                    let next = board.step(cell, direction);
+                    if !board.free[next] {
+                        continue;
+                    }
+                    if self.reached[next] == other {
+                        return true;
+                    }
+                    if self.reached[next] != own {
+                        self.reached[next] = own;
+                        queue.push_back(next);
+                    }
+                }
+            }
+        }
+    }
+
+    // Free cells reachable from `start`, counting up to `limit`
+    fn room(&mut self, board: &Board, start: usize, limit: usize) -> usize {
+        self.start(board, start);
+        let mut count = 1;
+        while let Some(cell) = self.queue.pop_front() {
+            for direction in Direction::ALL {
+                if count >= limit {
+                    return count;
+                }
+                if self.reach(board, board.step(cell, direction), direction) {
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{game::GameOutcome, replay::Replay};
+
+    // Runs the player's controller until the game ends, at most `max_ticks`
+    fn play_out(state: &mut GameState, controller: &mut dyn Controller, max_ticks: u64) {
+        while state.outcome().is_none() && state.ticks < max_ticks {
+            let change_direction = controller.decide(state, PLAYER);
+            state.next(state.player_action(change_direction));
+        }
+    }
+
+    fn scripted_game() -> GameState {
+        // A loop round the middle of the board, then on out through the
+        // right wall
+        let mut script = vec![None; 3];
+        script.extend([Some(Direction::Down), None, None]);
+        script.extend([Some(Direction::Left), None, None, None, None]);
+        script.extend([Some(Direction::Up), None, None, None, None]);
+        script.push(Some(Direction::Right));
+        let mut controller = ScriptedController::new(script);
+        let mut state = GameState::with_seed(14, 10, 7).with_food_count(4);
+        play_out(&mut state, &mut controller, 1_000);
+        state
+    }
+
+    #[test]
+    fn test_a_scripted_game_plays_out_the_same_every_time() {
+        let state = scripted_game();
+        assert_eq!(state.outcome(), Some(GameOutcome::WallCollision));
+        assert_eq!(state.ticks, 24);
+        assert_eq!(state.score, 2);
+        assert_eq!(state.snake.head, Position::new(13, 3));
+
+        let again = scripted_game();
+        assert_eq!(again.snake.tail, state.snake.tail);
+        assert_eq!(again.foods, state.foods);
+
+        // And what it recorded replays to the same end
+        let replay = Replay::from_state(&state);
+        let mut replayed = replay.initial_state();
+        for tick in &replay.ticks {
+            Replay::apply(&mut replayed, *tick);
+        }
+        assert_eq!(replayed.outcome(), state.outcome());
+        assert_eq!(replayed.score, state.score);
+        assert_eq!(replayed.foods, state.foods);
+    }
+
+    #[test]
+    fn test_autopilot_goes_round_an_obstacle_to_the_food() {
+        // Inside the walls:
+        // .....
+        // .>#F.
+        // .....
+        let mut state = GameState::with_seed(7, 5, 1).with_obstacles([Position::new(4, 2)]);
+        state.foods[0].position = Position::new(5, 2);
+        assert_eq!(state.snake.head, Position::new(3, 2));
+
+        let first = autopilot_direction(&state);
+        assert!(
+            matches!(first, Direction::Up | Direction::Down),
+            "{:?}",
+            first
+        );
+        for _ in 0..4 {
+            let direction = autopilot_direction(&state);
+            state.next(state.player_action(Some(direction)));
+            assert_eq!(state.outcome(), None);
+        }
+        assert_eq!(state.score, 1);
+
+        // Boxed in on three sides, the only way left is the way out
+        let mut state = GameState::with_seed(7, 5, 1)
+            .with_obstacles([Position::new(4, 2), Position::new(3, 1)]);
+        state.foods[0].position = Position::new(5, 2);
+        assert_eq!(autopilot_direction(&state), Direction::Down);
+    }
+
+    #[test]
+    fn test_keyboard_controller_takes_the_turns() {
+        let state = GameState::with_seed(14, 10, 7);
+        let mut controller = KeyboardController::new();
+        assert!(!controller.handle(InputCommand::Pause));
+        // Heading right, so left is ignored
+        assert!(controller.handle(InputCommand::Turn(Direction::Left)));
+        assert!(controller.handle(InputCommand::Turn(Direction::Up)));
+        assert_eq!(controller.decide(&state, PLAYER), Some(Direction::Up));
+        assert_eq!(controller.decide(&state, PLAYER), None);
+
+        controller.handle(InputCommand::Turn(Direction::Down));
+        controller.reset();
+        assert_eq!(controller.decide(&state, PLAYER), None);
+        assert_eq!(controller.decide(&state, 7), None);
+    }
+
+    #[test]
+    fn test_any_controller_can_steer_the_player() {
+        let mut controllers: Vec<Box<dyn Controller>> = vec![
+            Box::new(KeyboardController::new()),
+            Box::new(ScriptedController::new([Some(Direction::Up)])),
+            Box::new(AutoController::new(1)),
+        ];
+        for (seed, controller) in controllers.iter_mut().enumerate() {
+            let mut state = GameState::with_seed(16, 12, seed as u64);
+            play_out(&mut state, controller.as_mut(), 300);
+            assert!(state.ticks > 0);
+        }
+
+        // The bot on its own gets a fair way
+        let mut state = GameState::with_seed(16, 12, 3);
+        play_out(&mut state, &mut AutoController::new(1), 300);
+        assert_eq!(state.outcome(), None);
+        assert!(state.score >= 20, "{}", state.score);
+    }
+}
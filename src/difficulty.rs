@@ -0,0 +1,119 @@
+use crate::{
+    game::{self, WallMode},
+    speed::Speed,
+};
+use std::{fmt, str::FromStr, time::Duration};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+// Everything a difficulty changes about a game
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Preset {
+    pub speed: Speed,
+    pub width: u16,
+    pub height: u16,
+    pub walls: WallMode,
+    // Ticks between a rival's looks for the shortest way to the food
+    pub rival_replan: u32,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard];
+
+    // Normal matches the command line defaults
+    pub fn preset(self) -> Preset {
+        match self {
+            Difficulty::Easy => Preset {
+                speed: Speed {
+                    start: Duration::from_millis(100),
+                    step: Duration::from_millis(4),
+                    floor: Duration::from_millis(60),
+                    points_per_step: 8,
+                },
+                width: 30,
+                height: 15,
+                walls: WallMode::Wrap,
+                rival_replan: 4,
+            },
+            Difficulty::Normal => Preset {
+                speed: Speed::new(game::DEFAULT_STEP_INTERVAL),
+                width: 30,
+                height: 15,
+                walls: WallMode::Solid,
+                rival_replan: 2,
+            },
+            Difficulty::Hard => Preset {
+                speed: Speed {
+                    start: Duration::from_millis(60),
+                    step: Duration::from_millis(5),
+                    floor: Duration::from_millis(30),
+                    points_per_step: 3,
+                },
+                width: 24,
+                height: 12,
+                walls: WallMode::Solid,
+                rival_replan: 1,
+            },
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Normal => "normal",
+            Difficulty::Hard => "hard",
+        }
+    }
+}
+
+impl FromStr for Difficulty {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Difficulty::ALL
+            .into_iter()
+            .find(|difficulty| difficulty.name() == s)
+            .ok_or_else(|| format!("unknown difficulty '{}'", s))
+    }
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_difficulty_names_round_trip() {
+        for difficulty in Difficulty::ALL {
+            assert_eq!(difficulty.name().parse(), Ok(difficulty));
+        }
+        assert!("nightmare".parse::<Difficulty>().is_err());
+    }
+
+    #[test]
+    fn test_presets_get_harder() {
+        let [easy, normal, hard] = Difficulty::ALL.map(Difficulty::preset);
+        assert!(easy.speed.start > normal.speed.start);
+        assert!(normal.speed.start > hard.speed.start);
+        assert!(hard.speed.points_per_step < normal.speed.points_per_step);
+        assert!(hard.width * hard.height < normal.width * normal.height);
+        assert_eq!(easy.walls, WallMode::Wrap);
+        assert!(easy.rival_replan > normal.rival_replan);
+        assert!(normal.rival_replan > hard.rival_replan);
+    }
+}
@@ -0,0 +1,128 @@
+use crate::latency::{FrameTimer, Stage};
+use std::{collections::VecDeque, fmt::Write, time::Duration};
+
+// Frames the rates are averaged over, a second at 60 FPS
+pub const SAMPLES: usize = 60;
+
+// The frame and tick rates the game actually runs at, averaged over the last
+// `SAMPLES` frames so the readout does not flicker. Frames are timed by the
+// same checkpoints as the latency measurement.
+#[derive(Debug, Default)]
+pub struct FrameRate {
+    // Time from one frame's start to the next and the ticks it stepped,
+    // oldest first
+    samples: VecDeque<(Duration, u32)>,
+    // The last frame recorded, its draw time is shown
+    last_frame: Option<FrameTimer>,
+}
+
+impl FrameRate {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Once a frame has been flushed
+    pub fn record(&mut self, timer: &FrameTimer, ticks: u32) {
+        if let Some(last_frame) = &self.last_frame {
+            if self.samples.len() == SAMPLES {
+                self.samples.pop_front();
+            }
+            let frame_time = timer
+                .started()
+                .saturating_duration_since(last_frame.started());
+            self.samples.push_back((frame_time, ticks));
+        }
+        self.last_frame = Some(*timer);
+    }
+
+    pub fn fps(&self) -> Option<f64> {
+        self.per_second(self.samples.len() as u32)
+    }
+
+    pub fn tps(&self) -> Option<f64> {
+        self.per_second(self.samples.iter().map(|&(_, ticks)| ticks).sum())
+    }
+
+    // From the state being up to date to the frame being flushed
+    pub fn draw_time(&self) -> Option<Duration> {
+        let last_frame = self.last_frame.as_ref()?;
+        Some(last_frame.duration(Stage::Render)? + last_frame.duration(Stage::Flush)?)
+    }
+
+    // Into `label`, kept from frame to frame so the readout does not
+    // allocate, and left empty until two frames have been timed
+    pub fn write_label(&self, label: &mut String) {
+        label.clear();
+        let (Some(fps), Some(tps)) = (self.fps(), self.tps()) else {
+            return;
+        };
+        let _ = write!(label, "{:.0}fps {:.0}tps", fps, tps);
+        if let Some(draw_time) = self.draw_time() {
+            let _ = write!(label, " {:.1}ms", draw_time.as_secs_f64() * 1000.0);
+        }
+    }
+
+    fn per_second(&self, count: u32) -> Option<f64> {
+        let elapsed: Duration = self.samples.iter().map(|&(frame_time, _)| frame_time).sum();
+        (!elapsed.is_zero()).then(|| f64::from(count) / elapsed.as_secs_f64())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    // A frame starting at `start` that took `draw` from the state being
+    // stepped to the flush
+    fn frame(start: Instant, draw: Duration) -> FrameTimer {
+        let mut timer = FrameTimer::start(start);
+        timer.state_applied(start);
+        timer.frame_built(start + draw / 2);
+        timer.flushed(start + draw);
+        timer
+    }
+
+    #[test]
+    fn test_rates_average_over_the_last_frames() {
+        let mut rate = FrameRate::new();
+        let mut label = String::new();
+        rate.write_label(&mut label);
+        assert_eq!(label, "");
+
+        // 60 FPS with a tick every fourth frame
+        let mut start = Instant::now();
+        let mut next = |rate: &mut FrameRate, after: Duration, draw, ticks| {
+            start += after;
+            rate.record(&frame(start, draw), ticks);
+        };
+        let draw = Duration::from_micros(2_340);
+        for frame in 0..=SAMPLES * 2 {
+            next(
+                &mut rate,
+                Duration::from_micros(16_667),
+                draw,
+                u32::from(frame % 4 == 0),
+            );
+        }
+        rate.write_label(&mut label);
+        assert_eq!(label, "60fps 15tps 2.3ms");
+        let capacity = label.capacity();
+        rate.write_label(&mut label);
+        assert_eq!(label.capacity(), capacity);
+
+        // A stall only counts until it drops out of the window
+        next(&mut rate, Duration::from_millis(500), draw, 0);
+        assert!(rate.fps().unwrap() < 45.0);
+        for _ in 0..SAMPLES {
+            next(
+                &mut rate,
+                Duration::from_millis(20),
+                Duration::from_millis(4),
+                1,
+            );
+        }
+        rate.write_label(&mut label);
+        assert_eq!(label, "50fps 50tps 4.0ms");
+    }
+}
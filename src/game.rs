@@ -1,380 +1,3437 @@
-use crossterm::{
-    cursor,
-    event::KeyCode,
-    queue,
-    style::{self, Stylize},
-};
-use rand::Rng;
-use std::{
-    collections::VecDeque,
-    io::{self},
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use std::{collections::VecDeque, fmt, ops::Deref, str::FromStr, time::Duration};
+
+use crate::{
+    controller::Controller, input::InputCommand, level::Level, rival::Rival, speed::Speed,
 };
 
-use crate::theme;
+pub const DEFAULT_STEP_INTERVAL: Duration = Duration::from_millis(75); // ~13 steps/s
+
+// Smallest board, border included, that leaves room to play
+pub const MIN_BOARD_SIZE: u16 = 5;
+
+// Which snake a controller steers
+pub const PLAYER: usize = 0;
+pub const RIVAL: usize = 1;
+
+// Every so many foods a bonus worth more drops, and only stays for a while
+pub const BONUS_EVERY: u32 = 10;
+pub const BONUS_LIFETIME: u32 = 60; // ticks, ~4.5s at the default speed
+pub const BONUS_VALUE: u32 = 5;
+// After eating, a phasing pellet shows up one time in this many
+pub const PHASE_CHANCE: u32 = 15;
+// Ticks the snake can pass through the border and its own tail
+pub const PHASE_TICKS: u32 = 20;
+// Ticks an hourglass slows the snake down for, more add up
+pub const SLOW_TICKS: u32 = 30;
+// Wandering food takes a step this often
+pub const WANDER_EVERY: u64 = 3; // ticks
+
+// How many ticks back undo can go from the furthest one played
+pub const UNDO_LIMIT: u64 = 64;
+// Lives in the lives mode, and how much tail a respawned snake keeps
+pub const LIVES: u8 = 3;
+pub const RESPAWN_TAIL: usize = 3;
+// How long a timed game lasts
+pub const TIME_LIMIT: Duration = Duration::from_secs(120);
+// How often a shrinking arena closes in, until it is this many cells across
+// inside its walls
+pub const SHRINK_EVERY: Duration = Duration::from_secs(30);
+pub const MIN_ARENA: u16 = 6;
+// Food eaten within this much game time of the last is worth one more times
+// its points, up to `COMBO_CAP` times
+pub const COMBO_WINDOW: Duration = Duration::from_secs(3);
+pub const COMBO_CAP: u32 = 5;
 
 pub struct GameState {
     pub snake: Snake,
-    pub food: Food,
+    // Tail cells the snake was asked to start with, fewer may have fit
+    pub start_length: usize,
+    // Never empty, eating one respawns just that one
+    pub foods: Vec<Food>,
+    pub bonus: Option<BonusFood>,
+    // A rare pellet that starts a phasing effect when eaten
+    pub phase_pellet: Option<Position>,
+    pub effects: Vec<Effect>,
     pub score: u32,
+    pub(crate) ticks: u64,
+    // Game time the ticks so far stood for, a step interval each, so replays
+    // and pauses do not change it
+    pub game_time: Duration,
+    pub(crate) combo: Combo,
+    // Game time between two snake steps, independent of how often it is drawn
+    pub speed: Speed,
     pub game_width: u16,
     pub game_height: u16,
-    pub actions: Vec<Action>,
+    // The walls the snake can crash into, the board's border unless the
+    // arena has closed in
+    pub bounds: Bounds,
+    pub(crate) actions: Vec<Action>,
+    // Ticks up to here are out of undo's reach
+    undo_floor: u64,
+    // Set by the first undo, a rewound run doesn't count for the high score
+    pub(crate) rewound: bool,
+    // Nothing the snake runs into ends the game, it goes through walls, its
+    // tail and the rest, so the run doesn't count for the high score either
+    pub practice: bool,
+    pub seed: u64,
+    pub wall_mode: WallMode,
+    // Interior cells that are as deadly as the border
+    pub obstacles: Vec<Position>,
+    // The map the game started from, kept so replays can rebuild it
+    pub level: Option<Level>,
+    pub fruit_weights: FruitWeights,
+    // How many of each fruit were eaten, by `FruitKind` index
+    pub(crate) fruits_eaten: [u32; FruitKind::ALL.len()],
+    pub food_behavior: FoodBehavior,
+    pub mode: GameMode,
+    // Lives left, counting the one being played. A collision with more than
+    // one left respawns the snake instead of ending the game.
+    pub lives: u8,
+    // How long a timed game lasts, and the unpaused play time left of it,
+    // counted down by the caller
+    pub time_limit: Option<Duration>,
+    pub time_left: Option<Duration>,
+    // Time until the arena next closes in, while it still can
+    pub next_shrink: Option<Duration>,
+    // The tick count each time the arena closed in
+    pub(crate) shrinks: Vec<u64>,
+    // Shrinks still to come in a replayed game
+    scheduled_shrinks: VecDeque<u64>,
+    // A computer-controlled snake after the same food
+    pub rival: Option<Rival>,
+    rng: StdRng,
+    // RNG state before each tick that used it, so undoing a grow or a food
+    // step rewinds it too
+    rng_history: Vec<StdRng>,
+    // Where the food was before each tick it wandered
+    wander_history: Vec<Vec<Food>>,
+    // The active effects before each tick, in a fixed array as there is at
+    // most one of each kind, so a tick does not allocate for it
+    effects_history: Vec<[Option<Effect>; EffectKind::COUNT]>,
+    // The snake and the food as they were before each respawn
+    respawn_history: Vec<(Snake, Vec<Food>)>,
+    // The rival, the food and the RNG before each move of the rival
+    rival_history: Vec<(Rival, Vec<Food>, StdRng)>,
 }
 
 impl GameState {
     pub fn new(game_width: u16, game_height: u16) -> Self {
+        Self::with_seed(game_width, game_height, rand::random())
+    }
+
+    pub fn with_seed(game_width: u16, game_height: u16, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
         let snake = Snake::new(game_width / 2, game_height / 2);
-        let food = Food::new(game_width, game_height);
+        let bounds = Bounds::new(game_width, game_height);
+        let food = Food::new_avoiding(bounds, &snake, &[], &mut rng);
         let score = 0;
 
         Self {
             snake,
-            food,
+            start_length: 0,
+            foods: vec![food],
+            bonus: None,
+            phase_pellet: None,
+            effects: Vec::new(),
             score,
+            ticks: 0,
+            game_time: Duration::ZERO,
+            combo: Combo::default(),
+            speed: Speed::new(DEFAULT_STEP_INTERVAL),
             game_width,
             game_height,
+            bounds,
             actions: Vec::new(),
+            undo_floor: 0,
+            rewound: false,
+            practice: false,
+            seed,
+            wall_mode: WallMode::Solid,
+            obstacles: Vec::new(),
+            level: None,
+            fruit_weights: FruitWeights::default(),
+            fruits_eaten: [0; FruitKind::ALL.len()],
+            food_behavior: FoodBehavior::Still,
+            mode: GameMode::Classic,
+            lives: 1,
+            time_limit: None,
+            time_left: None,
+            next_shrink: None,
+            shrinks: Vec::new(),
+            scheduled_shrinks: VecDeque::new(),
+            rival: None,
+            rng,
+            rng_history: Vec::new(),
+            wander_history: Vec::new(),
+            effects_history: Vec::new(),
+            respawn_history: Vec::new(),
+            rival_history: Vec::new(),
         }
     }
 
-    pub fn queue(&self, stdout: &mut io::Stdout) -> io::Result<()> {
-        self.food.queue(stdout)?;
-        self.snake.queue(stdout)?;
-        Ok(())
+    // The snake starts where the map says, with the map's food or a random one
+    pub fn from_level(level: Level, seed: u64) -> Self {
+        let mut state = Self::with_seed(level.width, level.height, seed);
+        state.snake = Snake::new(level.snake_start.x, level.snake_start.y);
+        state.foods = level
+            .foods
+            .iter()
+            .map(|&position| Food::at(position))
+            .collect();
+        if state.foods.is_empty() {
+            // The one placed by `with_seed` did not know about the snake start
+            let food = state.spawn_food(None);
+            state.foods.push(food);
+        }
+        let mut state = state.with_obstacles(level.obstacles.iter().copied());
+        state.level = Some(level);
+        state
     }
 
-    pub fn next(&mut self, action: Action) {
-        self.actions.push(action);
+    // Starts the snake with a tail of `tail_length` cells trailing back from
+    // its head, cut short by a wall or an obstacle. Food under the tail is
+    // moved.
+    pub fn with_start_length(mut self, tail_length: usize) -> Self {
+        self.start_length = tail_length;
+        let head = self.snake.head;
+        let direction = self.snake.direction;
+        let tail_length = self.room_behind(head, direction, tail_length);
+        self.snake = Snake::with_length(head.x, head.y, direction, tail_length);
+        for index in 0..self.foods.len() {
+            if self.snake.contains(self.foods[index].position) {
+                self.foods[index] = self.spawn_food(Some(index));
+            }
+        }
+        self
+    }
 
-        if let Some(new_direction) = action.change_direction {
-            self.snake.direction = new_direction;
+    // How many of `tail_length` cells behind a head facing `direction` are
+    // clear of walls and obstacles
+    fn room_behind(&self, head: Position, direction: Direction, tail_length: usize) -> usize {
+        let mut position = head;
+        (0..tail_length)
+            .take_while(|_| {
+                position = position.move_direction(direction.reverse());
+                !self.bounds.is_wall(position) && !self.obstacles.contains(&position)
+            })
+            .count()
+    }
+
+    // Cells on the border, under the snake or repeated are left out. Food that
+    // ends up under an obstacle is moved.
+    pub fn with_obstacles(mut self, positions: impl IntoIterator<Item = Position>) -> Self {
+        for position in positions {
+            if !self.bounds.is_wall(position)
+                && !self.snake.contains(position)
+                && !self.obstacles.contains(&position)
+            {
+                self.obstacles.push(position);
+            }
         }
+        for index in 0..self.foods.len() {
+            if self.obstacles.contains(&self.foods[index].position) {
+                self.foods[index] = self.spawn_food(Some(index));
+            }
+        }
+        self
+    }
 
-        if action.must_grow {
-            self.snake.move_and_grow();
-            self.food = Food::new(self.game_width, self.game_height);
-            self.score += 1;
+    // The first food is always an apple, later ones follow the weights
+    pub fn with_fruit_weights(mut self, weights: FruitWeights) -> Self {
+        self.fruit_weights = weights;
+        self
+    }
+
+    pub fn with_mode(mut self, mode: GameMode) -> Self {
+        self.mode = mode;
+        self.lives = mode.lives();
+        self.time_limit = mode.time_limit();
+        self.time_left = self.time_limit;
+        self.next_shrink = (mode == GameMode::Shrinking).then_some(SHRINK_EVERY);
+        self
+    }
+
+    // A timed game against a clock other than the mode's
+    pub fn with_time_limit(mut self, limit: Duration) -> Self {
+        self.time_limit = Some(limit);
+        self.time_left = Some(limit);
+        self
+    }
+
+    // Closes the arena in after the given ticks rather than on the clock, to
+    // replay a game
+    pub fn with_shrinks(mut self, ticks: &[u64]) -> Self {
+        self.next_shrink = None;
+        self.scheduled_shrinks = ticks.iter().copied().collect();
+        self.run_scheduled_shrinks();
+        self
+    }
+
+    // Adds a rival that plans its way to the food every `replan_every` ticks,
+    // starting near the top left corner
+    pub fn with_rival(mut self, replan_every: u32) -> Self {
+        let corner = Position::new(self.bounds.left + 2, self.bounds.top + 2);
+        self.rival = self
+            .nearest_free_cell(corner)
+            .map(|start| Rival::new(start, replan_every));
+        self
+    }
+
+    // Adds a snake for a second player, starting where a rival would
+    pub fn with_second_player(mut self) -> Self {
+        let corner = Position::new(self.bounds.left + 2, self.bounds.top + 2);
+        self.rival = self.nearest_free_cell(corner).map(Rival::second_player);
+        self
+    }
+
+    // Adds food until there are `count` items on the board, at least one
+    pub fn with_food_count(mut self, count: usize) -> Self {
+        while self.foods.len() < count {
+            let food = self.spawn_food(None);
+            self.foods.push(food);
         }
+        self
+    }
 
-        self.snake.move_direction();
+    // A new fruit away from the snake, the obstacles and every other food but
+    // the one at `replacing`
+    fn spawn_food(&mut self, replacing: Option<usize>) -> Food {
+        let position = self.spawn_position(replacing);
+        Food {
+            position,
+            kind: self.fruit_weights.pick(&mut self.rng),
+        }
     }
 
-    pub fn is_game_over(&self) -> bool {
-        self.snake
-            .head
-            .is_on_border(self.game_width, self.game_height)
-            || self.snake.self_collision()
+    fn spawn_position(&mut self, replacing: Option<usize>) -> Position {
+        let (foods, bonus, pellet, rival) =
+            (&self.foods, self.bonus, self.phase_pellet, &self.rival);
+        let is_free = |position: Position| {
+            !self.snake.contains(position)
+                && !self.obstacles.contains(&position)
+                && !foods
+                    .iter()
+                    .enumerate()
+                    .any(|(index, food)| Some(index) != replacing && food.position == position)
+                && bonus.is_none_or(|bonus| bonus.position != position)
+                && pellet != Some(position)
+                && rival
+                    .as_ref()
+                    .is_none_or(|rival| !rival.snake.contains(position))
+        };
+        Food::new_where(self.bounds, self.snake.head, is_free, &mut self.rng).position
     }
 
-    pub fn get_action(&self, user_input: Option<KeyCode>) -> Action {
-        let direction = user_input.and_then(|code| match code {
-            KeyCode::Up => Some(Direction::Up),
-            KeyCode::Down => Some(Direction::Down),
-            KeyCode::Left => Some(Direction::Left),
-            KeyCode::Right => Some(Direction::Right),
-            _ => None,
-        });
+    pub(crate) fn food_at(&self, position: Position) -> Option<usize> {
+        self.foods.iter().position(|food| food.position == position)
+    }
+
+    // Plays a tick, returning what happened on it in the order it did
+    pub fn next(&mut self, mut action: Action) -> TickEvents {
+        let speed_level = self.speed.level(self.score);
+        // Remember what this tick overwrites so it can be undone
+        action.food = self.foods[0];
+        action.previous_direction = Some(self.snake.direction);
+        action.previous_bonus = self.bonus;
+        action.previous_pellet = self.phase_pellet;
+        action.phased = self.is_phasing();
+        action.previous_combo = self.combo;
+        action.previous_game_time = self.game_time;
+        self.game_time += self.step_interval();
+        let mut effects_before = [None; EffectKind::COUNT];
+        for (slot, &effect) in effects_before.iter_mut().zip(&self.effects) {
+            *slot = Some(effect);
+        }
+        self.effects_history.push(effects_before);
+        self.ticks = self.ticks.saturating_add(1);
+        self.undo_floor = self.undo_floor.max(self.ticks.saturating_sub(UNDO_LIMIT));
+
+        if let Some(new_direction) = action.change_direction {
+            self.snake.direction = new_direction;
+        }
 
-        let must_grow = self.snake.head == self.food.position;
+        let wanders = self.food_behavior == FoodBehavior::Wandering
+            && self.ticks.is_multiple_of(WANDER_EVERY);
+        let foods_before = wanders.then(|| self.foods.clone());
+        if self.food_behavior == FoodBehavior::Wandering {
+            // Recorded grows can come from food that walked into the head, so
+            // only food already in the way makes the snake grow as it moves
+            let next_head = self.wrap(self.snake.head.move_direction(self.snake.direction));
+            action.must_grow = self.food_at(next_head).is_some();
+        }
+        if action.must_grow || wanders {
+            self.rng_history.push(self.rng.clone());
+        }
+
+        if action.must_grow {
+            self.snake.move_and_grow();
+            self.snake.head = self.wrap(self.snake.head);
+            // A forced grow (as in tests) with no food under the head replaces
+            // the first one
+            let eaten = self.food_at(self.snake.head).unwrap_or(0);
+            self.eat(&mut action, self.foods[eaten].kind);
+            action.food_index = eaten;
+            action.food = self.foods[eaten];
+            // A full board has no free cell left to place food on
+            if !self.is_board_full() {
+                self.foods[eaten] = self.spawn_food(Some(eaten));
+            }
+        } else {
+            action.dropped_tail = self.snake.move_direction();
+            self.snake.head = self.wrap(self.snake.head);
+        }
+
+        if let Some(foods_before) = foods_before {
+            self.wander(&mut action, &foods_before);
+            self.wander_history.push(foods_before);
+            action.wandered = true;
+        }
 
-        if direction.is_none() {
-            return Action::new(self.snake.head, None, must_grow);
+        let tick = self.ticks;
+        let event = |kind| GameEvent { tick, kind };
+        let mut events = TickEvents::new(event(GameEventKind::Moved {
+            from: action.snake_head,
+            to: self.snake.head,
+        }));
+        if action.must_grow {
+            // Where the head is, food that wandered into it included
+            events.push(event(GameEventKind::FoodEaten {
+                position: self.snake.head,
+                kind: action.food.kind,
+                points: action.food_points,
+            }));
+            events.push(event(GameEventKind::Grew {
+                length: self.snake.len(),
+            }));
         }
 
-        let new_direction = direction.unwrap();
-        if new_direction != self.snake.direction && new_direction != self.snake.direction.reverse()
+        // Eating it on its last tick still counts, it is gone after that
+        if let Some(bonus) = self.bonus.filter(|bonus| bonus.position == self.snake.head) {
+            self.score = self.score.saturating_add(bonus.value);
+            action.bonus_points = bonus.value;
+            self.bonus = None;
+            events.push(event(GameEventKind::BonusEaten {
+                position: bonus.position,
+                points: bonus.value,
+            }));
+        }
+        if let Some(bonus) = &mut self.bonus {
+            bonus.ttl = bonus.ttl.saturating_sub(1);
+            if bonus.ttl == 0 {
+                self.bonus = None;
+            }
+        }
+        if action.must_grow
+            && self.foods_eaten().is_multiple_of(BONUS_EVERY)
+            && self.bonus.is_none()
+            && !self.is_board_full()
         {
-            return Action::new(self.snake.head, Some(new_direction), must_grow);
+            self.bonus = Some(BonusFood {
+                position: self.spawn_position(None),
+                ttl: BONUS_LIFETIME,
+                value: BONUS_VALUE,
+            });
         }
 
-        Action::new(self.snake.head, None, must_grow)
-    }
-}
+        // Effects wear off one tick at a time, one picked up now lasts for the
+        // whole of its next ticks
+        for effect in &mut self.effects {
+            effect.remaining -= 1;
+        }
+        self.effects.retain(|effect| effect.remaining > 0);
+        if action.must_grow && action.food.kind == FruitKind::Hourglass {
+            match self
+                .effects
+                .iter_mut()
+                .find(|effect| effect.kind == EffectKind::SlowMotion)
+            {
+                Some(effect) => effect.remaining += SLOW_TICKS,
+                None => self.effects.push(Effect {
+                    kind: EffectKind::SlowMotion,
+                    remaining: SLOW_TICKS,
+                }),
+            }
+            events.push(event(GameEventKind::EffectGained {
+                kind: EffectKind::SlowMotion,
+            }));
+        }
+        if self.phase_pellet == Some(self.snake.head) {
+            self.phase_pellet = None;
+            self.effects
+                .retain(|effect| effect.kind != EffectKind::Phasing);
+            self.effects.push(Effect {
+                kind: EffectKind::Phasing,
+                remaining: PHASE_TICKS,
+            });
+            events.push(event(GameEventKind::EffectGained {
+                kind: EffectKind::Phasing,
+            }));
+        }
+        if action.must_grow
+            && self.phase_pellet.is_none()
+            && !self.is_board_full()
+            && self.rng.gen_ratio(1, PHASE_CHANCE)
+        {
+            self.phase_pellet = Some(self.spawn_position(None));
+        }
 
-pub struct GameGrid {
-    pub width: u16,
-    pub height: u16,
-}
+        if self.speed.level(self.score) != speed_level {
+            events.push(event(GameEventKind::SpeedChanged {
+                level: self.speed.level(self.score),
+            }));
+        }
 
-impl GameGrid {
-    pub fn new(width: u16, height: u16) -> Self {
-        Self { width, height }
+        self.move_rival(action.second_turn);
+        self.actions.push(action);
+        match self.outcome() {
+            Some(cause) if cause.is_collision() => {
+                events.push(event(GameEventKind::Died { cause }));
+                if self.lives > 1 {
+                    self.respawn();
+                    events.push(event(GameEventKind::Respawned { lives: self.lives }));
+                }
+            }
+            Some(GameOutcome::Win) => events.push(event(GameEventKind::Won)),
+            _ => {}
+        }
+        self.run_scheduled_shrinks();
+        events
     }
 
-    pub fn queue(&self, stdout: &mut io::Stdout) -> io::Result<()> {
-        for y in 0..self.height {
-            for x in 0..self.width {
-                queue!(stdout, cursor::MoveTo(x, y))?;
-                if Position::new(x, y).is_on_border(self.width, self.height) {
-                    queue!(stdout, style::PrintStyledContent("█".with(theme::SURFACE)))?;
-                    continue;
+    // The board cells the last tick repainted: the head it left and the one
+    // it took, the tail tip it dropped and whatever food came or went. None
+    // when it moved more than that, for a respawn, wandering food or walls
+    // closing in.
+    pub fn changed_cells(&self) -> Option<Vec<Position>> {
+        let action = self.actions.last()?;
+        if action.respawned || action.wandered || self.shrinks.last() == Some(&self.ticks) {
+            return None;
+        }
+        let mut cells = vec![action.snake_head, self.snake.head];
+        cells.extend(action.dropped_tail);
+        if action.must_grow {
+            cells.push(action.food.position);
+            cells.push(self.foods[action.food_index].position);
+        }
+        cells.extend(action.previous_bonus.map(|bonus| bonus.position));
+        cells.extend(self.bonus.map(|bonus| bonus.position));
+        cells.extend(action.previous_pellet);
+        cells.extend(self.phase_pellet);
+        if let (Some(rival), Some((before, foods, _))) = (&self.rival, self.rival_history.last()) {
+            if rival.crashes != before.crashes {
+                return None;
+            }
+            cells.extend([before.snake.head, rival.snake.head]);
+            cells.extend(before.snake.tail.back());
+            // Food the rival ate, and what took its place
+            for (before, after) in foods.iter().zip(&self.foods) {
+                if before != after {
+                    cells.extend([before.position, after.position]);
                 }
-                queue!(
-                    stdout,
-                    style::PrintStyledContent("█".with(theme::BACKGROUND))
-                )?;
             }
         }
-        Ok(())
+
+        let mut changed = Vec::with_capacity(cells.len());
+        for position in cells {
+            if !changed.contains(&position) {
+                changed.push(position);
+            }
+        }
+        Some(changed)
     }
-}
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub enum Direction {
-    Up,
-    Down,
-    Left,
-    Right,
-}
+    // The rival moves after the player, so it is the one that crashes when
+    // both go for the same cell. A crashed rival starts over in its corner, a
+    // second player who crashed is left where they are to end the game.
+    fn move_rival(&mut self, second_turn: Option<Direction>) {
+        let Some(rival) = &self.rival else {
+            return;
+        };
+        self.rival_history
+            .push((rival.clone(), self.foods.clone(), self.rng.clone()));
 
-impl Direction {
-    pub fn reverse(&self) -> Self {
-        match self {
-            Direction::Up => Direction::Down,
-            Direction::Down => Direction::Up,
-            Direction::Left => Direction::Right,
-            Direction::Right => Direction::Left,
+        // Decided with the rival still on the board, then taken off it to move
+        let mut controller = rival.controller.clone();
+        let change_direction = if rival.second_player {
+            second_turn
+        } else {
+            controller.decide(self, RIVAL)
+        };
+        let Some(mut rival) = self.rival.take() else {
+            return;
+        };
+        rival.controller = controller;
+
+        if let Some(direction) = change_direction {
+            rival.snake.direction = direction;
         }
-    }
-}
+        let next_head = rival.snake.head.move_direction(rival.snake.direction);
+        let must_grow = self.food_at(self.wrap_around_if(next_head)).is_some();
+        if must_grow {
+            rival.snake.move_and_grow();
+        } else {
+            rival.snake.move_direction();
+        }
+        rival.snake.head = self.wrap_around_if(rival.snake.head);
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub struct Position {
-    pub x: u16,
-    pub y: u16,
-}
+        let head = rival.snake.head;
+        let crashed = self.bounds.is_wall(head)
+            || self.obstacles.contains(&head)
+            || self.snake.contains(head)
+            || rival.snake.self_collision();
+        let eaten = self.food_at(head).filter(|_| !crashed);
+        if let Some(index) = eaten {
+            rival.score = rival.score.saturating_add(self.foods[index].kind.points());
+        }
+        let restarts = crashed && !rival.second_player;
+        self.rival = Some(rival);
 
-impl Position {
-    pub fn new(x: u16, y: u16) -> Self {
-        Self { x, y }
+        if let Some(index) = eaten {
+            self.foods[index] = self.spawn_food(Some(index));
+        }
+        if restarts {
+            self.restart_rival();
+        }
     }
 
-    pub fn is_on_border(&self, width: u16, height: u16) -> bool {
-        self.x == 0 || self.y == height - 1 || self.x == width - 1 || self.y == 0
+    fn restart_rival(&mut self) {
+        let corner = Position::new(self.bounds.left + 2, self.bounds.top + 2);
+        let Some(mut rival) = self.rival.take() else {
+            return;
+        };
+        if let Some(start) = self.nearest_free_cell(corner) {
+            rival.restart(start);
+        }
+        self.rival = Some(rival);
     }
 
-    pub fn move_direction(&self, direction: Direction) -> Position {
-        let mut next = *self;
-        match direction {
-            Direction::Up => next.y -= 1,
-            Direction::Down => next.y += 1,
-            Direction::Left => next.x -= 1,
-            Direction::Right => next.x += 1,
+    pub(crate) fn snake_by_id(&self, snake_id: usize) -> Option<&Snake> {
+        match snake_id {
+            PLAYER => Some(&self.snake),
+            RIVAL => self.rival.as_ref().map(|rival| &rival.snake),
+            _ => None,
         }
-        next
     }
-}
 
-#[derive(Debug)]
-pub struct Snake {
-    pub head: Position,
-    pub tail: VecDeque<Position>,
-    pub direction: Direction,
-    pub grow: bool,
-}
+    // Whether any snake is on the cell
+    pub fn is_occupied(&self, position: Position) -> bool {
+        self.snake.contains(position) || self.rival_contains(position)
+    }
 
-impl Snake {
-    pub fn new(initial_x: u16, initial_y: u16) -> Self {
-        Self {
-            head: Position::new(initial_x, initial_y),
-            tail: VecDeque::new(),
-            direction: Direction::Right,
-            grow: false,
+    pub fn is_two_player(&self) -> bool {
+        self.rival.as_ref().is_some_and(|rival| rival.second_player)
+    }
+
+    // The second player ran into anything at all, walls closing in included
+    fn second_player_crashed(&self) -> bool {
+        let Some(rival) = self.rival.as_ref().filter(|rival| rival.second_player) else {
+            return false;
+        };
+        let head = rival.snake.head;
+        !self.practice
+            && (self.bounds.is_wall(head)
+                || rival
+                    .snake
+                    .tail
+                    .iter()
+                    .any(|&cell| self.bounds.is_wall(cell))
+                || self.obstacles.contains(&head)
+                || self.snake.contains(head)
+                || rival.snake.self_collision())
+    }
+
+    pub(crate) fn rival_contains(&self, position: Position) -> bool {
+        self.rival
+            .as_ref()
+            .is_some_and(|rival| rival.snake.contains(position))
+    }
+
+    fn run_scheduled_shrinks(&mut self) {
+        while self.scheduled_shrinks.front() == Some(&self.ticks) {
+            self.scheduled_shrinks.pop_front();
+            self.shrink();
         }
     }
 
-    pub fn queue(&self, stdout: &mut io::Stdout) -> io::Result<()> {
-        for pos in &self.tail {
-            queue!(
-                stdout,
-                cursor::MoveTo(pos.x, pos.y),
-                style::PrintStyledContent("█".with(theme::SECONDARY))
-            )?;
+    // Closes the walls in by a cell on every side, down to `MIN_ARENA`. Food
+    // caught in the new walls moves to the nearest free cell, a snake caught in
+    // them is crushed.
+    pub(crate) fn shrink(&mut self) {
+        if !self.bounds.can_shrink() {
+            return;
+        }
+        self.bounds = self.bounds.shrunk();
+        self.shrinks.push(self.ticks);
+        if self.next_shrink.is_some() {
+            self.next_shrink = self.bounds.can_shrink().then_some(SHRINK_EVERY);
         }
 
-        queue!(
-            stdout,
-            cursor::MoveTo(self.head.x, self.head.y),
-            style::PrintStyledContent("█".with(theme::PRIMARY))
-        )?;
+        for index in 0..self.foods.len() {
+            let position = self.foods[index].position;
+            if !self.bounds.is_wall(position) {
+                continue;
+            }
+            if let Some(free) = self.nearest_free_cell(position) {
+                self.foods[index].position = free;
+            }
+        }
+        if self
+            .bonus
+            .is_some_and(|bonus| self.bounds.is_wall(bonus.position))
+        {
+            self.bonus = None;
+        }
+        if self
+            .phase_pellet
+            .is_some_and(|pellet| self.bounds.is_wall(pellet))
+        {
+            self.phase_pellet = None;
+        }
+        // A second player crushed is out, not put back
+        let rival_crushed = self.rival.as_ref().is_some_and(|rival| {
+            !rival.second_player
+                && (self.bounds.is_wall(rival.snake.head)
+                    || rival
+                        .snake
+                        .tail
+                        .iter()
+                        .any(|&cell| self.bounds.is_wall(cell)))
+        });
+        if rival_crushed {
+            self.restart_rival();
+        }
+    }
 
-        Ok(())
+    fn nearest_free_cell(&self, position: Position) -> Option<Position> {
+        self.bounds
+            .interior()
+            .filter(|&cell| {
+                !self.snake.contains(cell)
+                    && !self.rival_contains(cell)
+                    && !self.obstacles.contains(&cell)
+                    && self.food_at(cell).is_none()
+                    && self.bonus.is_none_or(|bonus| bonus.position != cell)
+                    && self.phase_pellet != Some(cell)
+            })
+            .min_by_key(|cell| cell.x.abs_diff(position.x) + cell.y.abs_diff(position.y))
     }
 
-    pub fn move_direction(&mut self) {
-        let old_head = self.head;
-        self.head = self.head.move_direction(self.direction);
+    // Takes a life and puts the snake back where it started, heading right
+    // with at most `RESPAWN_TAIL` of its tail, to carry on from the tick that
+    // killed it. The score, the food and the board stay as they were, food the
+    // new snake lands on is moved out of its way.
+    pub(crate) fn respawn(&mut self) {
+        let Some(action) = self.actions.last_mut() else {
+            return;
+        };
+        action.respawned = true;
+        self.lives = self.lives.saturating_sub(1);
+        self.rng_history.push(self.rng.clone());
 
-        if !self.tail.is_empty() {
-            self.tail.push_front(old_head);
-            self.tail.pop_back();
+        let start = self.level.as_ref().map_or(
+            Position::new(self.game_width / 2, self.game_height / 2),
+            |level| level.snake_start,
+        );
+        let tail_length = self.snake.tail.len().min(RESPAWN_TAIL);
+        let tail_length = self.room_behind(start, Direction::Right, tail_length);
+        let snake = Snake::with_length(start.x, start.y, Direction::Right, tail_length);
+        let dead = std::mem::replace(&mut self.snake, snake);
+        self.respawn_history.push((dead, self.foods.clone()));
+        // Undoing the tick puts the rival back from its own history
+        let rival_in_the_way = self.rival.as_ref().is_some_and(|rival| {
+            self.snake.contains(rival.snake.head)
+                || rival
+                    .snake
+                    .tail
+                    .iter()
+                    .any(|&cell| self.snake.contains(cell))
+        });
+        if rival_in_the_way {
+            self.restart_rival();
+        }
+
+        for index in 0..self.foods.len() {
+            if self.snake.contains(self.foods[index].position) {
+                self.foods[index] = self.spawn_food(Some(index));
+            }
+        }
+        // Undoing the tick puts these back from the action
+        if self
+            .bonus
+            .is_some_and(|bonus| self.snake.contains(bonus.position))
+        {
+            self.bonus = None;
+        }
+        if self
+            .phase_pellet
+            .is_some_and(|pellet| self.snake.contains(pellet))
+        {
+            self.phase_pellet = None;
         }
     }
 
-    pub fn move_and_grow(&mut self) {
-        let old_head = self.head;
-        self.head = self.head.move_direction(self.direction);
+    // Steps the game back one tick, returning the action that was undone
+    pub fn undo(&mut self) -> Option<Action> {
+        // The walls do not move back out, so undoing stops at the last shrink,
+        // and it never goes more than `UNDO_LIMIT` ticks back
+        if self.shrinks.last() >= Some(&self.ticks) || self.ticks <= self.undo_floor {
+            return None;
+        }
+        let action = self.actions.pop()?;
+        self.rewound = true;
+        let reverse = Action::reverse(action);
 
-        self.tail.push_front(old_head);
-    }
+        // Back to the snake that died, then on to undoing the move itself
+        if reverse.respawned {
+            if let Some((snake, foods)) = self.respawn_history.pop() {
+                self.snake = snake;
+                self.foods = foods;
+            }
+            if let Some(rng) = self.rng_history.pop() {
+                self.rng = rng;
+            }
+            self.lives += 1;
+        }
+        if self.rival.is_some() {
+            if let Some((rival, foods, rng)) = self.rival_history.pop() {
+                self.rival = Some(rival);
+                self.foods = foods;
+                self.rng = rng;
+            }
+        }
 
-    pub fn self_collision(&self) -> bool {
-        self.tail
-            .iter()
-            .any(|pos| pos.x == self.head.x && pos.y == self.head.y)
+        // Reversing a grow shrinks the snake back
+        let shrink = !reverse.must_grow;
+        if shrink || reverse.dropped_tail.is_some() {
+            self.snake.tail.pop_front();
+        }
+        if let Some(position) = reverse.dropped_tail {
+            self.snake.tail.push_back(position);
+        }
+        self.score = self.score.saturating_sub(reverse.bonus_points);
+        self.bonus = reverse.previous_bonus;
+        self.phase_pellet = reverse.previous_pellet;
+        if let Some(effects) = self.effects_history.pop() {
+            self.effects = effects.into_iter().flatten().collect();
+        }
+        if shrink {
+            let kind = reverse.food.kind;
+            self.score = self.score.saturating_sub(reverse.food_points);
+            self.fruits_eaten[kind as usize] = self.fruits_eaten[kind as usize].saturating_sub(1);
+        }
+        self.combo = reverse.previous_combo;
+        self.game_time = reverse.previous_game_time;
+        if shrink || reverse.wandered {
+            if let Some(rng) = self.rng_history.pop() {
+                self.rng = rng;
+            }
+        }
+
+        self.snake.head = reverse.snake_head;
+        if let Some(direction) = reverse.change_direction {
+            self.snake.direction = direction;
+        }
+        // The tick before the undone one moved in the direction held going into it
+        self.snake.last_moved_direction = self.snake.direction;
+        self.foods[reverse.food_index] = reverse.food;
+        if reverse.wandered {
+            if let Some(foods) = self.wander_history.pop() {
+                self.foods = foods;
+            }
+        }
+        self.ticks = self.ticks.saturating_sub(1);
+
+        Some(action)
     }
-}
 
-#[derive(Debug)]
-pub struct Food {
-    pub position: Position,
-}
+    // Every food takes one step to a free neighbouring cell. The only part of
+    // the snake it may step onto is the head, which eats it as if the snake had
+    // moved there, unless the snake already ate this tick.
+    fn wander(&mut self, action: &mut Action, foods_before: &[Food]) {
+        for (index, before) in foods_before.iter().enumerate() {
+            let position = self.foods[index].position;
+            let steps: Vec<Position> = Direction::ALL
+                .iter()
+                .map(|&direction| position.move_direction(direction))
+                .filter(|&step| {
+                    let free = !self.bounds.is_wall(step)
+                        && !self.snake.tail.contains(&step)
+                        && !self.rival_contains(step)
+                        && !self.obstacles.contains(&step)
+                        && self.food_at(step).is_none()
+                        && self.bonus.is_none_or(|bonus| bonus.position != step)
+                        && self.phase_pellet != Some(step);
+                    free && (step != self.snake.head || !action.must_grow)
+                })
+                .collect();
+            let Some(&step) = steps.choose(&mut self.rng) else {
+                continue;
+            };
+            self.foods[index].position = step;
+            if step != self.snake.head {
+                continue;
+            }
 
-impl Food {
-    pub fn new(max_width: u16, max_height: u16) -> Self {
-        let mut rng = rand::thread_rng();
-        let position = Position::new(
-            rng.gen_range(1..max_width - 1),
-            rng.gen_range(1..max_height - 1),
-        );
-        Self { position }
+            // The same as growing on the move that just happened
+            match action.dropped_tail.take() {
+                Some(dropped) => self.snake.tail.push_back(dropped),
+                None => self.snake.tail.push_front(action.snake_head),
+            }
+            action.must_grow = true;
+            action.food_index = index;
+            action.food = *before;
+            self.eat(action, self.foods[index].kind);
+            if !self.is_board_full() {
+                self.foods[index] = self.spawn_food(Some(index));
+            }
+        }
     }
 
-    pub fn queue(&self, stdout: &mut io::Stdout) -> io::Result<()> {
-        queue!(
-            stdout,
-            cursor::MoveTo(self.position.x, self.position.y),
-            style::PrintStyledContent("●".with(theme::ACCENT).on(theme::BACKGROUND))
-        )?;
-        Ok(())
+    // Scores a fruit times the combo it keeps going
+    fn eat(&mut self, action: &mut Action, kind: FruitKind) {
+        self.combo = self.combo.after_eating(self.game_time);
+        action.food_points = kind.points() * self.combo.multiplier;
+        self.score = self.score.saturating_add(action.food_points);
+        self.fruits_eaten[kind as usize] = self.fruits_eaten[kind as usize].saturating_add(1);
     }
-}
 
-#[derive(Debug, Clone, Copy)]
-pub struct Action {
-    pub snake_head: Position,
-    pub change_direction: Option<Direction>,
-    pub must_grow: bool,
-    pub food_position: Position,
-    pub is_reverse: bool,
-}
+    // Counted rather than read off the length, which a start length or a
+    // respawn throws off, and brought back by undo with the fruit counts
+    pub fn foods_eaten(&self) -> u32 {
+        self.fruits_eaten
+            .iter()
+            .fold(0, |total, &count| total.saturating_add(count))
+    }
 
-impl Action {
-    pub fn new(snake_head: Position, change_direction: Option<Direction>, must_grow: bool) -> Self {
-        Self {
-            snake_head,
-            change_direction,
-            must_grow,
-            food_position: Position::new(0, 0),
-            is_reverse: false,
+    // The combo multiplier while the next food would still add to it
+    pub fn combo_multiplier(&self) -> Option<u32> {
+        self.combo.active(self.game_time)
+    }
+
+    // Shrinks with the score following the speed curve, and doubles while
+    // slowed down
+    pub fn step_interval(&self) -> Duration {
+        let interval = self.speed.interval(self.score);
+        if self.effect_ticks(EffectKind::SlowMotion).is_some() {
+            interval * 2
+        } else {
+            interval
         }
     }
 
-    pub fn reverse(action: Action) -> Self {
-        let reverse_direction = action.change_direction.map(|d| d.reverse());
-        Self {
-            snake_head: action.snake_head,
-            change_direction: reverse_direction,
-            must_grow: !action.must_grow,
-            food_position: action.food_position,
-            is_reverse: true,
+    // Runs the clocks of a timed game or a shrinking arena down by the time
+    // played since last time
+    pub fn advance_clock(&mut self, elapsed: Duration) {
+        if let Some(time_left) = &mut self.time_left {
+            *time_left = time_left.saturating_sub(elapsed);
+        }
+        if let Some(next_shrink) = self.next_shrink {
+            let next_shrink = next_shrink.saturating_sub(elapsed);
+            self.next_shrink = Some(next_shrink);
+            if next_shrink.is_zero() {
+                self.shrink();
+            }
         }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    pub(crate) fn playable_cells(&self) -> usize {
+        let inside = self
+            .obstacles
+            .iter()
+            .filter(|&&position| !self.bounds.is_wall(position))
+            .count();
+        self.bounds.area() - inside
+    }
 
-    #[test]
-    fn test_empty_snake_movement() {
-        let mut snake = Snake::new(5, 5);
-        assert_eq!(snake.head, Position::new(5, 5));
-        assert!(snake.tail.is_empty());
+    pub fn is_board_full(&self) -> bool {
+        self.snake.len() >= self.playable_cells()
+    }
 
-        snake.direction = Direction::Right;
-        snake.move_direction();
-        assert_eq!(snake.head, Position::new(6, 5));
-        assert!(snake.tail.is_empty());
+    // The board as plain text, a line per row: `#` border and obstacles, `O`
+    // head and `o` tail, `X` and `x` for the rival or second player, `*` food,
+    // `$` the bonus and `+` the phase pellet. Run cards keep the final board
+    // this way.
+    pub fn to_ascii(&self) -> String {
+        let rival = self.rival.as_ref().map(|rival| &rival.snake);
+        let mut board = String::new();
+        for y in 0..self.game_height {
+            for x in 0..self.game_width {
+                let position = Position::new(x, y);
+                let cell = if self.snake.head == position {
+                    'O'
+                } else if self.snake.tail.contains(&position) {
+                    'o'
+                } else if rival.is_some_and(|rival| rival.head == position) {
+                    'X'
+                } else if rival.is_some_and(|rival| rival.contains(position)) {
+                    'x'
+                } else if self.bounds.is_wall(position) || self.obstacles.contains(&position) {
+                    '#'
+                } else if self.food_at(position).is_some() {
+                    '*'
+                } else if self.bonus.is_some_and(|bonus| bonus.position == position) {
+                    '$'
+                } else if self.phase_pellet == Some(position) {
+                    '+'
+                } else {
+                    ' '
+                };
+                board.push(cell);
+            }
+            board.push('\n');
+        }
+        board
+    }
 
-        snake.direction = Direction::Down;
-        snake.move_direction();
-        assert_eq!(snake.head, Position::new(6, 6));
-        assert!(snake.tail.is_empty());
+    // Read-only, undo keeps these in step with the actions it takes back
+    pub fn ticks(&self) -> u64 {
+        self.ticks
     }
 
-    #[test]
-    fn test_snake_with_tail_size_one() {
-        let mut snake = Snake::new(5, 5);
-        snake.tail.push_back(Position::new(4, 5));
+    pub fn is_rewound(&self) -> bool {
+        self.rewound
+    }
+
+    pub fn fruits_eaten(&self) -> [u32; FruitKind::ALL.len()] {
+        self.fruits_eaten
+    }
+
+    // Whether the run can set a high score or make the top 10
+    pub fn is_ranked(&self) -> bool {
+        !self.rewound && !self.practice && !self.is_two_player()
+    }
+
+    pub fn is_phasing(&self) -> bool {
+        self.phasing_ticks().is_some()
+    }
+
+    pub fn phasing_ticks(&self) -> Option<u32> {
+        self.effect_ticks(EffectKind::Phasing)
+    }
+
+    pub fn effect_ticks(&self, kind: EffectKind) -> Option<u32> {
+        self.effects
+            .iter()
+            .find(|effect| effect.kind == kind)
+            .map(|effect| effect.remaining)
+    }
+
+    // In wrap mode, while phasing or in practice, a position that stepped onto the border
+    // comes back in on the opposite side of the playable interior
+    pub fn wrap(&self, position: Position) -> Position {
+        if self.wall_mode == WallMode::Solid && !self.is_phasing() && !self.practice {
+            return position;
+        }
+        self.wrap_around(position)
+    }
+
+    // Wrap mode only, phasing is the player's own
+    fn wrap_around_if(&self, position: Position) -> Position {
+        match self.wall_mode {
+            WallMode::Wrap => self.wrap_around(position),
+            WallMode::Solid => position,
+        }
+    }
+
+    pub(crate) fn wrap_around(&self, position: Position) -> Position {
+        Position::new(
+            wrap_axis(position.x, self.bounds.left, self.bounds.right),
+            wrap_axis(position.y, self.bounds.top, self.bounds.bottom),
+        )
+    }
+
+    // In a two-player game either crashing ends it, and both crashing on the
+    // same tick is a draw. That covers the heads meeting: the first player
+    // moves first, so when both go for the same food they eat it, and then
+    // the second player's head runs into theirs.
+    pub fn outcome(&self) -> Option<GameOutcome> {
+        let outcome = self.player_outcome();
+        if !self.second_player_crashed() {
+            return outcome;
+        }
+        match outcome {
+            Some(outcome) if outcome.is_collision() => Some(GameOutcome::BothCrashed),
+            _ => Some(GameOutcome::SecondPlayerCrashed),
+        }
+    }
+
+    // How the game ended for the first player's snake
+    fn player_outcome(&self) -> Option<GameOutcome> {
+        // The last move went through walls and tail unharmed. When phasing
+        // wears off inside the tail, it is the next move that decides. A
+        // practice run never stops phasing.
+        let phased = self.practice || self.actions.last().is_some_and(|action| action.phased);
+        let hits_wall =
+            self.wall_mode == WallMode::Solid && !phased && self.bounds.is_wall(self.snake.head);
+        // Walls closing in crush any part of the snake they land on, whatever
+        // the wall mode
+        let crushed = !self.practice
+            && !self.shrinks.is_empty()
+            && (self.bounds.is_wall(self.snake.head)
+                || self
+                    .snake
+                    .tail
+                    .iter()
+                    .any(|&position| self.bounds.is_wall(position)));
+        if hits_wall || crushed {
+            return Some(GameOutcome::WallCollision);
+        }
+        if self.obstacles.contains(&self.snake.head) && !self.practice {
+            return Some(GameOutcome::ObstacleCollision);
+        }
+        if self.snake.self_collision() && !phased {
+            return Some(GameOutcome::SelfCollision);
+        }
+        if self.rival_contains(self.snake.head) && !phased {
+            return Some(GameOutcome::RivalCollision);
+        }
+        if self.is_board_full() {
+            return Some(GameOutcome::Win);
+        }
+        if self.time_left == Some(Duration::ZERO) {
+            return Some(GameOutcome::TimesUp);
+        }
+        None
+    }
+
+    // Takes at most one turn from the buffer, the rest waits for later ticks
+    pub fn get_action(&self, input: &mut InputBuffer) -> Action {
+        // Checked against the last step taken, not the last turn requested,
+        // otherwise two quick turns can fold the head back onto the neck
+        let change_direction = input.pop_valid(self.snake.last_moved_direction);
+        self.player_action(change_direction)
+    }
+
+    // The player's move for a turn from any controller
+    pub fn player_action(&self, change_direction: Option<Direction>) -> Action {
+        // The snake grows on the tick its head lands on the food
+        let next_head = self.wrap(
+            self.snake
+                .head
+                .move_direction(change_direction.unwrap_or(self.snake.direction)),
+        );
+        let must_grow = self.food_at(next_head).is_some();
+
+        Action::new(self.snake.head, change_direction, must_grow)
+    }
+}
+
+// Maps 0..size onto the interior 1..size-1, so 0 lands on size-2 and size-1 on 1
+fn wrap_axis(value: u16, low: u16, high: u16) -> u16 {
+    let interior = high - low - 1;
+    (value - low + interior - 1) % interior + low + 1
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum WallMode {
+    #[default]
+    Solid,
+    // The border is still drawn but the snake passes through it
+    Wrap,
+}
+
+impl WallMode {
+    pub const ALL: [WallMode; 2] = [WallMode::Solid, WallMode::Wrap];
+}
+
+impl FromStr for WallMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "solid" => Ok(WallMode::Solid),
+            "wrap" => Ok(WallMode::Wrap),
+            _ => Err(format!("unknown wall mode '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for WallMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WallMode::Solid => write!(f, "solid"),
+            WallMode::Wrap => write!(f, "wrap"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GameMode {
+    #[default]
+    Classic,
+    // Three lives, the snake respawns after each of the first two crashes
+    Lives,
+    // As many points as possible before the clock runs out
+    Timed,
+    // The walls close in every so often
+    Shrinking,
+}
+
+impl GameMode {
+    pub const ALL: [GameMode; 4] = [
+        GameMode::Classic,
+        GameMode::Lives,
+        GameMode::Timed,
+        GameMode::Shrinking,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GameMode::Classic => "classic",
+            GameMode::Lives => "lives",
+            GameMode::Timed => "timed",
+            GameMode::Shrinking => "shrinking",
+        }
+    }
+
+    pub fn lives(self) -> u8 {
+        match self {
+            GameMode::Lives => LIVES,
+            GameMode::Classic | GameMode::Timed | GameMode::Shrinking => 1,
+        }
+    }
+
+    pub fn time_limit(self) -> Option<Duration> {
+        match self {
+            GameMode::Timed => Some(TIME_LIMIT),
+            GameMode::Classic | GameMode::Lives | GameMode::Shrinking => None,
+        }
+    }
+}
+
+impl FromStr for GameMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        GameMode::ALL
+            .into_iter()
+            .find(|mode| mode.name() == s)
+            .ok_or_else(|| format!("unknown game mode '{}'", s))
+    }
+}
+
+impl fmt::Display for GameMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            GameMode::Classic => "Classic",
+            GameMode::Lives => "Lives",
+            GameMode::Timed => "Timed",
+            GameMode::Shrinking => "Shrinking",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum FoodBehavior {
+    #[default]
+    Still,
+    // Every few ticks each food steps to a random free neighbouring cell
+    Wandering,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GameOutcome {
+    WallCollision,
+    ObstacleCollision,
+    SelfCollision,
+    // Ran into the rival snake, or the second player's
+    RivalCollision,
+    // Only in two-player games, the first player is still going
+    SecondPlayerCrashed,
+    BothCrashed,
+    Win,
+    // The clock of a timed game ran out
+    TimesUp,
+}
+
+impl GameOutcome {
+    pub fn name(self) -> &'static str {
+        match self {
+            GameOutcome::WallCollision => "wall",
+            GameOutcome::ObstacleCollision => "obstacle",
+            GameOutcome::SelfCollision => "self",
+            GameOutcome::RivalCollision => "rival",
+            GameOutcome::SecondPlayerCrashed => "player2",
+            GameOutcome::BothCrashed => "draw",
+            GameOutcome::Win => "win",
+            GameOutcome::TimesUp => "time",
+        }
+    }
+
+    pub fn is_collision(self) -> bool {
+        matches!(
+            self,
+            GameOutcome::WallCollision
+                | GameOutcome::ObstacleCollision
+                | GameOutcome::SelfCollision
+                | GameOutcome::RivalCollision
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ObstacleLayout {
+    // A horizontal bar across the middle half of the board, two rows above
+    // where the snake starts
+    CentralBar,
+    // A 2x2 block near each corner
+    CornerBlocks,
+}
+
+impl FromStr for ObstacleLayout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bar" => Ok(ObstacleLayout::CentralBar),
+            "corners" => Ok(ObstacleLayout::CornerBlocks),
+            _ => Err(format!("unknown obstacle layout '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for ObstacleLayout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObstacleLayout::CentralBar => write!(f, "bar"),
+            ObstacleLayout::CornerBlocks => write!(f, "corners"),
+        }
+    }
+}
+
+impl ObstacleLayout {
+    pub fn positions(&self, width: u16, height: u16) -> Vec<Position> {
+        match self {
+            ObstacleLayout::CentralBar => {
+                let y = (height / 2).saturating_sub(2);
+                (width / 4..width - width / 4)
+                    .map(|x| Position::new(x, y))
+                    .collect()
+            }
+            ObstacleLayout::CornerBlocks => {
+                let xs = [2, 3, width.saturating_sub(4), width.saturating_sub(3)];
+                let ys = [2, 3, height.saturating_sub(4), height.saturating_sub(3)];
+                ys.iter()
+                    .flat_map(|&y| xs.iter().map(move |&x| Position::new(x, y)))
+                    .collect()
+            }
+        }
+    }
+}
+
+// The walls around the playable cells: the board's border at first, closer in
+// once a shrinking arena has closed in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bounds {
+    pub left: u16,
+    pub top: u16,
+    pub right: u16,
+    pub bottom: u16,
+}
+
+impl Bounds {
+    pub fn new(width: u16, height: u16) -> Self {
+        Self {
+            left: 0,
+            top: 0,
+            right: width - 1,
+            bottom: height - 1,
+        }
+    }
+
+    // On one of the walls or beyond it
+    pub fn is_wall(&self, position: Position) -> bool {
+        position.x <= self.left
+            || position.x >= self.right
+            || position.y <= self.top
+            || position.y >= self.bottom
+    }
+
+    // Cells inside the walls
+    pub fn area(&self) -> usize {
+        let width = self.right.saturating_sub(self.left + 1);
+        let height = self.bottom.saturating_sub(self.top + 1);
+        usize::from(width) * usize::from(height)
+    }
+
+    pub fn interior(&self) -> impl Iterator<Item = Position> {
+        let Bounds {
+            left,
+            top,
+            right,
+            bottom,
+        } = *self;
+        (top + 1..bottom).flat_map(move |y| (left + 1..right).map(move |x| Position::new(x, y)))
+    }
+
+    fn can_shrink(&self) -> bool {
+        self.right - self.left - 3 >= MIN_ARENA && self.bottom - self.top - 3 >= MIN_ARENA
+    }
+
+    fn shrunk(self) -> Self {
+        Self {
+            left: self.left + 1,
+            top: self.top + 1,
+            right: self.right - 1,
+            bottom: self.bottom - 1,
+        }
+    }
+}
+
+pub struct GameGrid {
+    pub width: u16,
+    pub height: u16,
+}
+
+impl GameGrid {
+    pub fn new(width: u16, height: u16) -> Self {
+        Self { width, height }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 4] = [
+        Direction::Up,
+        Direction::Down,
+        Direction::Left,
+        Direction::Right,
+    ];
+
+    pub fn reverse(&self) -> Self {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+        }
+    }
+}
+
+// Direction keys pressed since the last tick, oldest first
+#[derive(Debug, Default)]
+pub struct InputBuffer {
+    directions: VecDeque<Direction>,
+}
+
+impl InputBuffer {
+    // Enough for a quick double turn without building up a long backlog
+    const CAPACITY: usize = 3;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Returns false for commands that are not turns, so the caller can handle them
+    pub fn push_command(&mut self, command: InputCommand) -> bool {
+        match command {
+            InputCommand::Turn(direction) => {
+                self.push(direction);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn push(&mut self, direction: Direction) {
+        if self.directions.len() < Self::CAPACITY {
+            self.directions.push_back(direction);
+        }
+    }
+
+    // Pops the first turn that is valid for a snake moving `current`, dropping
+    // the ones that would keep the same direction or reverse it
+    pub fn pop_valid(&mut self, current: Direction) -> Option<Direction> {
+        while let Some(direction) = self.directions.pop_front() {
+            if direction != current && direction != current.reverse() {
+                return Some(direction);
+            }
+        }
+        None
+    }
+
+    pub fn len(&self) -> usize {
+        self.directions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.directions.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Position {
+    pub x: u16,
+    pub y: u16,
+}
+
+impl Position {
+    pub fn new(x: u16, y: u16) -> Self {
+        Self { x, y }
+    }
+
+    pub fn is_on_border(&self, width: u16, height: u16) -> bool {
+        self.x == 0 || self.y == height - 1 || self.x == width - 1 || self.y == 0
+    }
+
+    // Saturates at the edges of the coordinate space, the border check is what
+    // decides whether leaving the board is fatal
+    pub fn move_direction(&self, direction: Direction) -> Position {
+        let mut next = *self;
+        match direction {
+            Direction::Up => next.y = next.y.saturating_sub(1),
+            Direction::Down => next.y = next.y.saturating_add(1),
+            Direction::Left => next.x = next.x.saturating_sub(1),
+            Direction::Right => next.x = next.x.saturating_add(1),
+        }
+        next
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Snake {
+    pub head: Position,
+    pub tail: VecDeque<Position>,
+    pub direction: Direction,
+    // The direction of the last step actually taken, which can lag behind
+    // `direction` until the next tick. Turns are validated against this one.
+    pub last_moved_direction: Direction,
+    pub grow: bool,
+}
+
+impl Snake {
+    pub fn new(initial_x: u16, initial_y: u16) -> Self {
+        Self {
+            head: Position::new(initial_x, initial_y),
+            tail: VecDeque::new(),
+            direction: Direction::Right,
+            last_moved_direction: Direction::Right,
+            grow: false,
+        }
+    }
+
+    // A snake heading `direction` with `tail_length` tail cells in a straight
+    // line behind its head
+    pub fn with_length(x: u16, y: u16, direction: Direction, tail_length: usize) -> Self {
+        let mut snake = Self::new(x, y);
+        snake.direction = direction;
+        snake.last_moved_direction = direction;
+        let mut position = snake.head;
+        for _ in 0..tail_length {
+            position = position.move_direction(direction.reverse());
+            snake.tail.push_back(position);
+        }
+        snake
+    }
+
+    // Returns the tail cell the snake left behind, if any. A snake with no
+    // tail is a single cell: only its head moves and nothing is dropped, the
+    // cell it left is the action's `snake_head`, which undo puts it back on.
+    pub fn move_direction(&mut self) -> Option<Position> {
+        let old_head = self.head;
+        self.head = self.head.move_direction(self.direction);
+        self.last_moved_direction = self.direction;
+
+        if self.tail.is_empty() {
+            return None;
+        }
+        self.tail.push_front(old_head);
+        self.tail.pop_back()
+    }
+
+    pub(crate) fn move_and_grow(&mut self) {
+        let old_head = self.head;
+        self.head = self.head.move_direction(self.direction);
+        self.last_moved_direction = self.direction;
+
+        self.tail.push_front(old_head);
+    }
+
+    // A snake always has a head, so there is no meaningful `is_empty`
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        self.tail.len() + 1
+    }
+
+    pub fn contains(&self, position: Position) -> bool {
+        self.head == position || self.tail.contains(&position)
+    }
+
+    pub(crate) fn self_collision(&self) -> bool {
+        let key = |cell: &Position| u32::from(cell.x) << 16 | u32::from(cell.y);
+        let head = key(&self.head);
+        let (front, back) = self.tail.as_slices();
+        // A cell as one number, and no early return, so the comparisons can
+        // run side by side
+        let hits = |cells: &[Position]| {
+            cells
+                .iter()
+                .fold(false, |hit, cell| hit | (key(cell) == head))
+        };
+        hits(front) | hits(back)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Food {
+    pub position: Position,
+    pub kind: FruitKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FruitKind {
+    Apple,
+    Cherry,
+    Banana,
+    // Worth a point and slows the snake down for a while
+    Hourglass,
+}
+
+impl FruitKind {
+    pub const ALL: [FruitKind; 4] = [
+        FruitKind::Apple,
+        FruitKind::Cherry,
+        FruitKind::Banana,
+        FruitKind::Hourglass,
+    ];
+
+    pub fn points(self) -> u32 {
+        match self {
+            FruitKind::Apple | FruitKind::Hourglass => 1,
+            FruitKind::Cherry => 2,
+            FruitKind::Banana => 3,
+        }
+    }
+}
+
+// How likely each fruit is to spawn, relative to the others, in
+// `FruitKind::ALL` order
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FruitWeights(pub [u32; FruitKind::ALL.len()]);
+
+impl Default for FruitWeights {
+    fn default() -> Self {
+        Self([12, 6, 2, 1])
+    }
+}
+
+impl FruitWeights {
+    pub fn pick(&self, rng: &mut impl Rng) -> FruitKind {
+        let total: u32 = self.0.iter().sum();
+        if total == 0 {
+            return FruitKind::Apple;
+        }
+        let mut roll = rng.gen_range(0..total);
+        for (kind, &weight) in FruitKind::ALL.iter().zip(&self.0) {
+            if roll < weight {
+                return *kind;
+            }
+            roll -= weight;
+        }
+        unreachable!("the roll is below the total weight")
+    }
+}
+
+impl FromStr for FruitWeights {
+    type Err = String;
+
+    // `apple,cherry,banana,hourglass`, e.g. "12,6,2,1". Three weights, as
+    // from before the hourglass, never spawn one.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let weights: Vec<u32> = s
+            .split(',')
+            .map(|weight| weight.trim().parse())
+            .collect::<Result<_, _>>()
+            .map_err(|_| format!("invalid fruit weights '{}'", s))?;
+        let weights = match weights[..] {
+            [apple, cherry, banana] => [apple, cherry, banana, 0],
+            _ => weights
+                .try_into()
+                .map_err(|_| format!("expected three or four fruit weights, got '{}'", s))?,
+        };
+        if weights.iter().all(|&weight| weight == 0) {
+            return Err("at least one fruit weight must be above zero".to_string());
+        }
+        // `pick` rolls below the total, which has to fit in a u32
+        if weights
+            .iter()
+            .try_fold(0u32, |total, &weight| total.checked_add(weight))
+            .is_none()
+        {
+            return Err(format!("fruit weights '{}' add up to too much", s));
+        }
+        Ok(Self(weights))
+    }
+}
+
+impl fmt::Display for FruitWeights {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let [apple, cherry, banana, hourglass] = self.0;
+        write!(f, "{},{},{},{}", apple, cherry, banana, hourglass)
+    }
+}
+
+impl Food {
+    pub fn new(bounds: Bounds, rng: &mut impl Rng) -> Self {
+        let position = Position::new(
+            rng.gen_range(bounds.left + 1..bounds.right),
+            rng.gen_range(bounds.top + 1..bounds.bottom),
+        );
+        Self::at(position)
+    }
+
+    // An apple, the plain one point fruit
+    pub fn at(position: Position) -> Self {
+        Self {
+            position,
+            kind: FruitKind::Apple,
+        }
+    }
+
+    pub(crate) fn new_avoiding(
+        bounds: Bounds,
+        snake: &Snake,
+        obstacles: &[Position],
+        rng: &mut impl Rng,
+    ) -> Self {
+        let is_free = |position| !snake.contains(position) && !obstacles.contains(&position);
+        Self::new_where(bounds, snake.head, is_free, rng)
+    }
+
+    // On a cell `is_free` accepts, or at `fallback` when there is none
+    pub(crate) fn new_where(
+        bounds: Bounds,
+        fallback: Position,
+        is_free: impl Fn(Position) -> bool,
+        rng: &mut impl Rng,
+    ) -> Self {
+        const MAX_RANDOM_ATTEMPTS: usize = 32;
+        for _ in 0..MAX_RANDOM_ATTEMPTS {
+            let food = Food::new(bounds, rng);
+            if is_free(food.position) {
+                return food;
+            }
+        }
+
+        // On a nearly full board random rolls keep hitting the snake, so pick
+        // among the cells that are actually free. Counted then walked rather
+        // than collected, drawing from the RNG the way `choose` would.
+        let free_cells = || bounds.interior().filter(|&position| is_free(position));
+        let count = free_cells().count();
+        if count == 0 {
+            return Self::at(fallback);
+        }
+        let pick = rng.gen_range(0..count as u32) as usize;
+        Self::at(free_cells().nth(pick).unwrap_or(fallback))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BonusFood {
+    pub position: Position,
+    // Ticks left before it disappears, counted in ticks rather than time so
+    // replays and pauses see the same thing
+    pub ttl: u32,
+    pub value: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EffectKind {
+    // Passes through the border and the snake's own tail
+    Phasing,
+    // Steps take twice as long
+    SlowMotion,
+}
+
+impl EffectKind {
+    pub const COUNT: usize = 2;
+}
+
+// Food eaten in quick succession, each within `COMBO_WINDOW` of the last
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Combo {
+    // What the last food eaten was multiplied by, 0 before any
+    pub multiplier: u32,
+    // Game time the last food was eaten at
+    pub last_eat: Duration,
+}
+
+impl Combo {
+    // The combo after eating at `now`, one up if the last food was recent
+    // enough, otherwise back to one
+    pub(crate) fn after_eating(self, now: Duration) -> Self {
+        let multiplier = match self.active(now) {
+            Some(multiplier) => (multiplier + 1).min(COMBO_CAP),
+            None => 1,
+        };
+        Self {
+            multiplier,
+            last_eat: now,
+        }
+    }
+
+    // The multiplier food eaten by `now` would build on, None once it lapsed
+    pub fn active(self, now: Duration) -> Option<u32> {
+        let in_time = now.saturating_sub(self.last_eat) <= COMBO_WINDOW;
+        (self.multiplier > 0 && in_time).then_some(self.multiplier)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Effect {
+    pub kind: EffectKind,
+    // Ticks left, counted down at the end of every tick
+    pub remaining: u32,
+}
+
+// Something that happened on a tick, for whatever reacts to the game: the
+// screen, sounds, statistics. Undoing a tick doesn't take its events back.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GameEvent {
+    pub tick: u64,
+    pub kind: GameEventKind,
+}
+
+// The most one tick can bring: the move, eating and growing, the bonus, both
+// effects, a speed change, then dying and respawning
+const MAX_TICK_EVENTS: usize = 9;
+
+// A tick's events, kept inline so playing a tick doesn't allocate. Reads as
+// a slice of them.
+#[derive(Debug, Clone, Copy)]
+pub struct TickEvents {
+    events: [GameEvent; MAX_TICK_EVENTS],
+    len: usize,
+}
+
+impl TickEvents {
+    // Every tick starts with the move
+    fn new(moved: GameEvent) -> Self {
+        Self {
+            events: [moved; MAX_TICK_EVENTS],
+            len: 1,
+        }
+    }
+
+    fn push(&mut self, event: GameEvent) {
+        self.events[self.len] = event;
+        self.len += 1;
+    }
+}
+
+impl Deref for TickEvents {
+    type Target = [GameEvent];
+
+    fn deref(&self) -> &[GameEvent] {
+        &self.events[..self.len]
+    }
+}
+
+impl PartialEq for TickEvents {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GameEventKind {
+    Moved {
+        from: Position,
+        to: Position,
+    },
+    FoodEaten {
+        position: Position,
+        kind: FruitKind,
+        points: u32,
+    },
+    // The snake's length after growing
+    Grew {
+        length: usize,
+    },
+    BonusEaten {
+        position: Position,
+        points: u32,
+    },
+    // Picked up, or made to last longer
+    EffectGained {
+        kind: EffectKind,
+    },
+    // The speed level the score has reached
+    SpeedChanged {
+        level: u32,
+    },
+    Died {
+        cause: GameOutcome,
+    },
+    // Back at the start after dying, with this many lives left
+    Respawned {
+        lives: u8,
+    },
+    Won,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Action {
+    pub snake_head: Position,
+    pub change_direction: Option<Direction>,
+    pub must_grow: bool,
+    // Filled in by `GameState::next` with what the tick replaced
+    pub food_index: usize,
+    pub food: Food,
+    pub previous_direction: Option<Direction>,
+    pub dropped_tail: Option<Position>,
+    pub previous_bonus: Option<BonusFood>,
+    pub bonus_points: u32,
+    // What the food eaten was worth with the combo
+    pub food_points: u32,
+    pub previous_combo: Combo,
+    pub previous_game_time: Duration,
+    pub previous_pellet: Option<Position>,
+    // The move was made while phasing
+    pub phased: bool,
+    pub wandered: bool,
+    // The move crashed and the snake was put back at the start
+    pub respawned: bool,
+    // The second player's turn in a two-player game
+    pub second_turn: Option<Direction>,
+    pub is_reverse: bool,
+}
+
+impl Action {
+    pub fn new(snake_head: Position, change_direction: Option<Direction>, must_grow: bool) -> Self {
+        Self {
+            snake_head,
+            change_direction,
+            must_grow,
+            food_index: 0,
+            food: Food::at(Position::new(0, 0)),
+            previous_direction: None,
+            dropped_tail: None,
+            previous_bonus: None,
+            bonus_points: 0,
+            food_points: 0,
+            previous_combo: Combo::default(),
+            previous_game_time: Duration::ZERO,
+            previous_pellet: None,
+            phased: false,
+            wandered: false,
+            respawned: false,
+            second_turn: None,
+            is_reverse: false,
+        }
+    }
+
+    // The action that takes the game back to where it was before `action`:
+    // the head returns to `snake_head`, the direction and food are restored and
+    // a grow becomes a shrink
+    pub(crate) fn reverse(action: Action) -> Self {
+        Self {
+            snake_head: action.snake_head,
+            change_direction: action.previous_direction,
+            must_grow: !action.must_grow,
+            food_index: action.food_index,
+            food: action.food,
+            previous_direction: action.change_direction,
+            dropped_tail: action.dropped_tail,
+            previous_bonus: action.previous_bonus,
+            bonus_points: action.bonus_points,
+            food_points: action.food_points,
+            previous_combo: action.previous_combo,
+            previous_game_time: action.previous_game_time,
+            previous_pellet: action.previous_pellet,
+            phased: action.phased,
+            wandered: action.wandered,
+            respawned: action.respawned,
+            second_turn: action.second_turn,
+            is_reverse: true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_snake_movement() {
+        let mut snake = Snake::new(5, 5);
+        assert_eq!(snake.head, Position::new(5, 5));
+        assert!(snake.tail.is_empty());
+
+        snake.direction = Direction::Right;
+        snake.move_direction();
+        assert_eq!(snake.head, Position::new(6, 5));
+        assert!(snake.tail.is_empty());
+
+        snake.direction = Direction::Down;
+        snake.move_direction();
+        assert_eq!(snake.head, Position::new(6, 6));
+        assert!(snake.tail.is_empty());
+    }
+
+    #[test]
+    fn test_a_long_snake_moves_from_the_first_tick() {
+        let mut snake = Snake::with_length(5, 5, Direction::Up, 3);
+        assert_eq!(
+            snake.tail,
+            [
+                Position::new(5, 6),
+                Position::new(5, 7),
+                Position::new(5, 8)
+            ]
+        );
+        assert_eq!(snake.move_direction(), Some(Position::new(5, 8)));
+        assert_eq!(snake.head, Position::new(5, 4));
+        assert_eq!(
+            snake.tail,
+            [
+                Position::new(5, 5),
+                Position::new(5, 6),
+                Position::new(5, 7)
+            ]
+        );
+
+        // Shortened to fit in front of an obstacle, the food under it moved
+        let map = "#########\n#.......#\n#.F.#.S.#\n#.......#\n#########\n";
+        let mut state =
+            GameState::from_level(crate::level::parse_map(map).unwrap(), 1).with_start_length(4);
+        assert_eq!(state.start_length, 4);
+        assert_eq!(state.snake.tail, [Position::new(5, 2)]);
+        state.next(state.player_action(None));
+        assert_eq!(state.snake.head, Position::new(7, 2));
+        assert_eq!(state.snake.tail, [Position::new(6, 2)]);
+
+        let map = "#########\n#.......#\n#...FS..#\n#.......#\n#########\n";
+        let state =
+            GameState::from_level(crate::level::parse_map(map).unwrap(), 1).with_start_length(2);
+        assert_eq!(state.snake.len(), 3);
+        assert!(!state.snake.contains(state.foods[0].position));
+    }
+
+    #[test]
+    fn test_a_fresh_snake_is_a_single_cell() {
+        let mut state = GameState::with_seed(20, 12, 1);
+        let start = state.snake.head;
+        state.foods[0] = Food::at(Position::new(start.x + 2, start.y));
+
+        state.next(state.player_action(None));
+        assert!(state.snake.tail.is_empty());
+        assert_eq!(state.actions[0].dropped_tail, None);
+        // The cell it left is repainted all the same
+        let changed = state.changed_cells().unwrap();
+        assert!(changed.contains(&start));
+        assert!(!state.snake.contains(start));
+
+        // Eating grows the tail onto the cell just left, which then follows
+        state.next(state.player_action(None));
+        assert_eq!(state.snake.tail, [Position::new(start.x + 1, start.y)]);
+        state.next(state.player_action(Some(Direction::Down)));
+        assert_eq!(state.snake.head, Position::new(start.x + 2, start.y + 1));
+        assert_eq!(state.snake.tail, [Position::new(start.x + 2, start.y)]);
+
+        for _ in 0..3 {
+            state.undo();
+        }
+        assert_eq!(state.snake.head, start);
+        assert!(state.snake.tail.is_empty());
+        assert_eq!(state.score, 0);
+    }
+
+    #[test]
+    fn test_snake_with_tail_size_one() {
+        let mut snake = Snake::new(5, 5);
+        snake.tail.push_back(Position::new(4, 5));
+
+        snake.direction = Direction::Right;
+        snake.move_direction();
+
+        assert_eq!(snake.head, Position::new(6, 5));
+        assert_eq!(snake.tail.len(), 1);
+        assert_eq!(snake.tail.front().unwrap(), &Position::new(5, 5));
+
+        snake.direction = Direction::Right;
+        snake.move_direction();
+
+        assert_eq!(snake.head, Position::new(7, 5));
+        assert_eq!(snake.tail.len(), 1);
+        assert_eq!(snake.tail.front().unwrap(), &Position::new(6, 5));
+
+        snake.direction = Direction::Up;
+        snake.move_direction();
+
+        assert_eq!(snake.head, Position::new(7, 4));
+        assert_eq!(snake.tail.len(), 1);
+        assert_eq!(snake.tail.front().unwrap(), &Position::new(7, 5));
+
+        snake.direction = Direction::Up;
+        snake.move_direction();
+
+        assert_eq!(snake.head, Position::new(7, 3));
+        assert_eq!(snake.tail.len(), 1);
+        assert_eq!(snake.tail.front().unwrap(), &Position::new(7, 4));
+    }
+
+    #[test]
+    fn test_snake_with_tail_size_two() {
+        let mut snake = Snake::new(5, 5);
+        snake.tail.push_back(Position::new(4, 5));
+        snake.tail.push_back(Position::new(3, 5));
+
+        snake.direction = Direction::Right;
+        snake.move_direction();
+
+        assert_eq!(snake.head, Position::new(6, 5));
+        assert_eq!(snake.tail.len(), 2);
+        assert_eq!(snake.tail.front().unwrap(), &Position::new(5, 5));
+        assert_eq!(snake.tail.back().unwrap(), &Position::new(4, 5));
 
         snake.direction = Direction::Right;
         snake.move_direction();
 
-        assert_eq!(snake.head, Position::new(6, 5));
-        assert_eq!(snake.tail.len(), 1);
-        assert_eq!(snake.tail.front().unwrap(), &Position::new(5, 5));
+        assert_eq!(snake.head, Position::new(7, 5));
+        assert_eq!(snake.tail.len(), 2);
+        assert_eq!(snake.tail.front().unwrap(), &Position::new(6, 5));
+        assert_eq!(snake.tail.back().unwrap(), &Position::new(5, 5));
+
+        snake.direction = Direction::Up;
+        snake.move_direction();
+
+        assert_eq!(snake.head, Position::new(7, 4));
+        assert_eq!(snake.tail.len(), 2);
+        assert_eq!(snake.tail.front().unwrap(), &Position::new(7, 5));
+        assert_eq!(snake.tail.back().unwrap(), &Position::new(6, 5));
+    }
+
+    #[test]
+    fn test_snake_self_collision() {
+        let mut snake = Snake::new(5, 5);
+        snake.tail.push_back(Position::new(4, 5));
+        assert!(!snake.self_collision());
+
+        snake.tail.push_back(Position::new(5, 5));
+        assert!(snake.self_collision());
+    }
+
+    #[test]
+    fn test_board_as_plain_text() {
+        let mut state = GameState::with_seed(6, 5, 1);
+        state.snake.head = Position::new(3, 2);
+        state.snake.tail = [Position::new(2, 2), Position::new(1, 2)].into();
+        state.foods[0] = Food::at(Position::new(4, 1));
+        assert_eq!(
+            state.to_ascii(),
+            "######\n\
+             #   *#\n\
+             #ooO #\n\
+             #    #\n\
+             ######\n"
+        );
+
+        state = state.with_rival(1);
+        let rival = &mut state.rival.as_mut().unwrap().snake;
+        rival.head = Position::new(2, 3);
+        rival.tail = [Position::new(1, 3)].into();
+        state.bonus = Some(BonusFood {
+            position: Position::new(1, 1),
+            ttl: BONUS_LIFETIME,
+            value: BONUS_VALUE,
+        });
+        state.phase_pellet = Some(Position::new(4, 3));
+        assert_eq!(
+            state.to_ascii(),
+            "######\n\
+             #$  *#\n\
+             #ooO #\n\
+             #xX +#\n\
+             ######\n"
+        );
+    }
+
+    #[test]
+    fn test_filling_the_board_is_a_win() {
+        // 4x4 board leaves a 2x2 playable interior, the snake starts at (2, 2)
+        let mut state = GameState::new(4, 4);
+        let moves = [
+            (Direction::Up, Position::new(2, 1)),
+            (Direction::Left, Position::new(1, 1)),
+            (Direction::Down, Position::new(1, 2)),
+        ];
+
+        for (direction, food) in moves {
+            assert_eq!(state.outcome(), None);
+            // Apples only, bonuses and other fruits are worth more than one point
+            state.foods[0] = Food::at(food);
+            state.bonus = None;
+            let mut input = InputBuffer::new();
+            input.push_command(InputCommand::Turn(direction));
+            let action = state.get_action(&mut input);
+            assert!(action.must_grow);
+            state.next(action);
+        }
+
+        assert_eq!(state.snake.len(), 4);
+        // Three in a row build a combo
+        assert_eq!(state.score, 1 + 2 + 3);
+        assert_eq!(state.outcome(), Some(GameOutcome::Win));
+    }
+
+    // A snake of length 6 heading into the right wall, two cells away
+    fn crash_course(mode: GameMode) -> GameState {
+        let mut state = GameState::with_seed(20, 10, 7).with_mode(mode);
+        state.snake.head = Position::new(17, 5);
+        state.snake.tail = (12..17).rev().map(|x| Position::new(x, 5)).collect();
+        state.foods[0] = Food::at(Position::new(3, 2));
+        state.score = 12;
+        state
+    }
+
+    #[test]
+    fn test_dying_with_lives_left_respawns() {
+        let mut state = crash_course(GameMode::Lives);
+        state.lives = 2;
+        for _ in 0..2 {
+            state.next(state.get_action(&mut InputBuffer::new()));
+        }
+
+        assert_eq!(state.outcome(), None);
+        assert_eq!(state.lives, 1);
+        assert_eq!(state.score, 12);
+        assert_eq!(state.snake.head, Position::new(10, 5));
+        assert_eq!(state.snake.len(), 1 + RESPAWN_TAIL);
+        assert_eq!(state.snake.direction, Direction::Right);
+        assert_eq!(state.foods[0].position, Position::new(3, 2));
+
+        // The game carries on from the start
+        state.next(state.get_action(&mut InputBuffer::new()));
+        assert_eq!(state.snake.head, Position::new(11, 5));
+        assert_eq!(state.outcome(), None);
+    }
+
+    #[test]
+    fn test_dying_on_the_last_life_ends_the_game() {
+        let mut state = crash_course(GameMode::Lives);
+        state.lives = 1;
+        for _ in 0..2 {
+            state.next(state.get_action(&mut InputBuffer::new()));
+        }
+        assert_eq!(state.outcome(), Some(GameOutcome::WallCollision));
+        assert_eq!(state.score, 12);
+
+        // A classic game has only the one
+        let mut state = crash_course(GameMode::Classic);
+        for _ in 0..2 {
+            state.next(state.get_action(&mut InputBuffer::new()));
+        }
+        assert_eq!(state.outcome(), Some(GameOutcome::WallCollision));
+    }
+
+    #[test]
+    fn test_respawn_moves_food_out_of_the_way_and_undoes() {
+        let mut state = crash_course(GameMode::Lives);
+        state.foods[0] = Food::at(Position::new(9, 5));
+        for _ in 0..2 {
+            state.next(state.get_action(&mut InputBuffer::new()));
+        }
+        assert_eq!(state.lives, LIVES - 1);
+        assert!(!state.snake.contains(state.foods[0].position));
+
+        state.undo();
+        assert_eq!(state.lives, LIVES);
+        assert_eq!(state.snake.head, Position::new(18, 5));
+        assert_eq!(state.snake.len(), 6);
+        assert_eq!(state.foods[0].position, Position::new(9, 5));
+    }
+
+    #[test]
+    fn test_timed_game_ends_when_the_clock_runs_out() {
+        let mut state = GameState::with_seed(30, 15, 1).with_mode(GameMode::Timed);
+        assert_eq!(state.time_left, Some(TIME_LIMIT));
+
+        state.advance_clock(Duration::from_secs(100));
+        state.next(state.get_action(&mut InputBuffer::new()));
+        state.advance_clock(Duration::from_millis(19_999));
+        assert_eq!(state.outcome(), None);
+        assert_eq!(state.time_left, Some(Duration::from_millis(1)));
+
+        state.advance_clock(Duration::from_secs(5));
+        assert_eq!(state.time_left, Some(Duration::ZERO));
+        assert_eq!(state.outcome(), Some(GameOutcome::TimesUp));
+        assert!(!GameOutcome::TimesUp.is_collision());
+
+        // Other modes have no clock to run out
+        let mut state = GameState::with_seed(30, 15, 1);
+        state.advance_clock(TIME_LIMIT * 2);
+        assert_eq!(state.outcome(), None);
+    }
+
+    #[test]
+    fn test_a_tick_reports_the_cells_it_changed() {
+        let mut state = GameState::with_seed(20, 12, 3);
+        state.foods[0] = Food::at(Position::new(15, 3));
+        assert_eq!(state.changed_cells(), None);
+
+        // Just a head: it moves on and leaves its old cell empty
+        state.next(Action::new(state.snake.head, None, false));
+        assert_eq!(
+            state.changed_cells(),
+            Some(vec![Position::new(10, 6), Position::new(11, 6)])
+        );
+
+        // Eating takes the food's cell and puts food down somewhere else
+        state.foods[0] = Food::at(Position::new(12, 6));
+        state.next(Action::new(state.snake.head, None, true));
+        let food = state.foods[0].position;
+        assert_eq!(
+            state.changed_cells(),
+            Some(vec![Position::new(11, 6), Position::new(12, 6), food])
+        );
+
+        // With a tail, the old head is recoloured and the tip is dropped
+        state.next(Action::new(state.snake.head, None, false));
+        assert_eq!(
+            state.changed_cells(),
+            Some(vec![
+                Position::new(12, 6),
+                Position::new(13, 6),
+                Position::new(11, 6)
+            ])
+        );
+
+        state.shrink();
+        assert_eq!(state.changed_cells(), None);
+    }
+
+    #[test]
+    fn test_arena_shrinks_on_the_clock() {
+        let mut state = GameState::with_seed(20, 12, 3).with_mode(GameMode::Shrinking);
+        state.foods[0] = Food::at(Position::new(1, 6));
+        state.advance_clock(SHRINK_EVERY - Duration::from_millis(1));
+        assert_eq!(state.bounds, Bounds::new(20, 12));
+
+        state.advance_clock(Duration::from_millis(1));
+        assert_eq!(state.shrinks, [0]);
+        assert_eq!(state.next_shrink, Some(SHRINK_EVERY));
+        // The food that was by the old wall moved to the nearest free cell
+        assert_eq!(state.foods[0].position, Position::new(2, 6));
+        assert_eq!(state.playable_cells(), 16 * 8);
+        assert_eq!(state.outcome(), None);
+
+        state.next(Action::new(state.snake.head, None, false));
+        state.advance_clock(SHRINK_EVERY);
+        assert_eq!(
+            state.bounds,
+            Bounds {
+                left: 2,
+                top: 2,
+                right: 17,
+                bottom: 9
+            }
+        );
+        assert_eq!(state.foods[0].position, Position::new(3, 6));
+        // An arena 6 cells high inside its walls is as small as it gets
+        assert_eq!(state.next_shrink, None);
+        state.shrink();
+        assert_eq!(state.shrinks, [0, 1]);
+
+        // Food only ever spawns inside
+        for _ in 0..50 {
+            let food = state.spawn_food(Some(0));
+            assert!(!state.bounds.is_wall(food.position), "{:?}", food);
+        }
+        // And undoing stops at the last shrink
+        assert!(state.undo().is_none());
+    }
+
+    #[test]
+    fn test_closing_walls_crush_the_snake() {
+        // The head is well inside, but the end of the tail is by the wall
+        let mut state = GameState::with_seed(20, 12, 3).with_mode(GameMode::Shrinking);
+        state.snake.head = Position::new(5, 6);
+        state.snake.tail = (1..5).rev().map(|x| Position::new(x, 6)).collect();
+        state.shrink();
+        assert_eq!(state.outcome(), Some(GameOutcome::WallCollision));
+
+        // In wrap mode the snake goes around the new walls, until they close
+        // in on it
+        let mut state = GameState::with_seed(20, 12, 3).with_mode(GameMode::Shrinking);
+        state.wall_mode = WallMode::Wrap;
+        state.snake.head = Position::new(17, 6);
+        state.shrink();
+        state.next(Action::new(state.snake.head, None, false));
+        assert_eq!(state.snake.head, Position::new(2, 6));
+        assert_eq!(state.outcome(), None);
+        state.shrink();
+        assert_eq!(state.outcome(), Some(GameOutcome::WallCollision));
+    }
+
+    #[test]
+    fn test_starting_from_a_level() {
+        let level = crate::level::parse_map(
+            "\
+########
+#..#...#
+#.S#.F.#
+#......#
+########",
+        )
+        .unwrap();
+        let state = GameState::from_level(level.clone(), 3);
+        assert_eq!(state.snake.head, Position::new(2, 2));
+        assert_eq!(state.obstacles, level.obstacles);
+        assert_eq!(state.foods[0].position, Position::new(5, 2));
+
+        // Without food in the map it lands somewhere free
+        let mut level = level;
+        level.foods.clear();
+        for seed in 0..50 {
+            let state = GameState::from_level(level.clone(), seed);
+            let food = state.foods[0].position;
+            assert!(!state.obstacles.contains(&food));
+            assert!(!state.snake.contains(food));
+        }
+    }
+
+    #[test]
+    fn test_outcome_distinguishes_collisions() {
+        let mut state = GameState::new(10, 10);
+        state.snake.head = Position::new(9, 5);
+        assert_eq!(state.outcome(), Some(GameOutcome::WallCollision));
+
+        state.snake.head = Position::new(5, 5);
+        state.snake.tail.push_back(Position::new(5, 5));
+        assert_eq!(state.outcome(), Some(GameOutcome::SelfCollision));
+    }
+
+    #[test]
+    fn test_wrap_mode_passes_through_walls() {
+        let mut state = GameState::with_seed(10, 8, 1);
+        state.wall_mode = WallMode::Wrap;
+        state.foods[0].position = Position::new(5, 5);
+
+        // Right edge comes back in on the left
+        state.snake.head = Position::new(8, 3);
+        state.next(Action::new(state.snake.head, None, false));
+        assert_eq!(state.snake.head, Position::new(1, 3));
+        assert_eq!(state.outcome(), None);
+
+        for (direction, from, to) in [
+            (Direction::Left, (1, 3), (8, 3)),
+            (Direction::Up, (4, 1), (4, 6)),
+            (Direction::Down, (4, 6), (4, 1)),
+        ] {
+            state.snake.head = Position::new(from.0, from.1);
+            state.next(Action::new(state.snake.head, Some(direction), false));
+            assert_eq!(state.snake.head, Position::new(to.0, to.1));
+        }
+
+        // The same board still kills on the border with solid walls
+        state.wall_mode = WallMode::Solid;
+        state.snake.head = Position::new(9, 3);
+        assert_eq!(state.outcome(), Some(GameOutcome::WallCollision));
+    }
+
+    #[test]
+    fn test_wrap_mode_eats_food_across_the_edge() {
+        let mut state = GameState::with_seed(10, 8, 1);
+        state.wall_mode = WallMode::Wrap;
+        state.snake.head = Position::new(8, 3);
+        state.foods[0].position = Position::new(1, 3);
+
+        let action = state.get_action(&mut InputBuffer::new());
+        assert!(action.must_grow);
+        state.next(action);
+        assert_eq!(state.score, 1);
+        assert_eq!(state.snake.tail.front(), Some(&Position::new(8, 3)));
+    }
+
+    #[test]
+    fn test_food_never_spawns_on_snake() {
+        // Only (2, 2) is free in the 2x2 playable interior of a 4x4 board
+        let mut snake = Snake::new(1, 1);
+        snake.tail.push_back(Position::new(2, 1));
+        snake.tail.push_back(Position::new(1, 2));
+
+        let mut rng = rand::thread_rng();
+        for _ in 0..50 {
+            let food = Food::new_avoiding(Bounds::new(4, 4), &snake, &[], &mut rng);
+            assert_eq!(food.position, Position::new(2, 2));
+        }
+    }
+
+    #[test]
+    fn test_obstacle_collision() {
+        let mut state = GameState::with_seed(10, 10, 1).with_obstacles([Position::new(7, 5)]);
+        state.foods[0].position = Position::new(1, 1);
+        assert_eq!(state.snake.head, Position::new(5, 5));
+
+        state.next(state.get_action(&mut InputBuffer::new()));
+        assert_eq!(state.outcome(), None);
+        state.next(state.get_action(&mut InputBuffer::new()));
+        assert_eq!(state.outcome(), Some(GameOutcome::ObstacleCollision));
+    }
+
+    #[test]
+    fn test_food_never_spawns_on_obstacles() {
+        // Everything but (2, 2) is snake or obstacle in the 2x2 interior
+        let snake = Snake::new(1, 1);
+        let obstacles = [Position::new(2, 1), Position::new(1, 2)];
+
+        let mut rng = rand::thread_rng();
+        for _ in 0..50 {
+            let food = Food::new_avoiding(Bounds::new(4, 4), &snake, &obstacles, &mut rng);
+            assert_eq!(food.position, Position::new(2, 2));
+        }
+
+        for layout in [ObstacleLayout::CentralBar, ObstacleLayout::CornerBlocks] {
+            for seed in 0..20 {
+                let state =
+                    GameState::with_seed(30, 15, seed).with_obstacles(layout.positions(30, 15));
+                assert!(!state.obstacles.is_empty());
+                assert!(!state.obstacles.contains(&state.foods[0].position));
+                assert!(!state.obstacles.contains(&state.snake.head));
+            }
+        }
+    }
+
+    #[test]
+    fn test_eating_one_of_several_foods() {
+        let mut state = GameState::with_seed(30, 15, 4).with_food_count(3);
+        assert_eq!(state.foods.len(), 3);
+        state.snake.head = Position::new(5, 5);
+        state.snake.tail = VecDeque::from([Position::new(4, 5)]);
+        state.foods[1] = Food::at(Position::new(6, 5));
+        let others = [state.foods[0], state.foods[2]];
 
-        snake.direction = Direction::Right;
-        snake.move_direction();
+        let action = state.get_action(&mut InputBuffer::new());
+        assert!(action.must_grow);
+        state.next(action);
+        assert_eq!(state.score, 1);
+        assert_eq!(state.snake.len(), 3);
+        assert_eq!([state.foods[0], state.foods[2]], others);
+        assert_ne!(state.foods[1].position, Position::new(6, 5));
 
-        assert_eq!(snake.head, Position::new(7, 5));
-        assert_eq!(snake.tail.len(), 1);
-        assert_eq!(snake.tail.front().unwrap(), &Position::new(6, 5));
+        state.undo();
+        assert_eq!(state.foods[1].position, Position::new(6, 5));
+        assert_eq!([state.foods[0], state.foods[2]], others);
+    }
 
-        snake.direction = Direction::Up;
-        snake.move_direction();
+    #[test]
+    fn test_food_never_spawns_on_other_food() {
+        let distinct = |state: &GameState| {
+            let mut positions: Vec<_> = state.foods.iter().map(|food| food.position).collect();
+            positions.sort_by_key(|position| (position.x, position.y));
+            positions.dedup();
+            positions.len() == state.foods.len()
+                && positions
+                    .iter()
+                    .all(|&position| !state.snake.contains(position))
+        };
 
-        assert_eq!(snake.head, Position::new(7, 4));
-        assert_eq!(snake.tail.len(), 1);
-        assert_eq!(snake.tail.front().unwrap(), &Position::new(7, 5));
+        // 3x3 interior: the snake and six foods leave two free cells
+        for seed in 0..20 {
+            let mut state = GameState::with_seed(5, 5, seed).with_food_count(6);
+            assert_eq!(state.foods.len(), 6);
+            assert!(distinct(&state));
 
-        snake.direction = Direction::Up;
-        snake.move_direction();
+            // Make sure there is food right in front of the snake and eat it
+            let ahead = Position::new(3, 2);
+            if state.food_at(ahead).is_none() {
+                state.foods[0].position = ahead;
+            }
+            let action = state.get_action(&mut InputBuffer::new());
+            assert!(action.must_grow);
+            state.next(action);
+            assert!(distinct(&state));
+        }
+    }
 
-        assert_eq!(snake.head, Position::new(7, 3));
-        assert_eq!(snake.tail.len(), 1);
-        assert_eq!(snake.tail.front().unwrap(), &Position::new(7, 4));
+    // A bonus `distance` cells ahead of the snake that lasts `ttl` more ticks
+    fn bonus_ahead(state: &mut GameState, distance: u16, ttl: u32) {
+        state.foods[0].position = Position::new(1, 1);
+        let head = state.snake.head;
+        state.bonus = Some(BonusFood {
+            position: Position::new(head.x + distance, head.y),
+            ttl,
+            value: BONUS_VALUE,
+        });
     }
 
     #[test]
-    fn test_snake_with_tail_size_two() {
-        let mut snake = Snake::new(5, 5);
-        snake.tail.push_back(Position::new(4, 5));
-        snake.tail.push_back(Position::new(3, 5));
+    fn test_bonus_expires_on_schedule() {
+        let mut state = GameState::with_seed(30, 15, 1);
+        bonus_ahead(&mut state, 10, 3);
+        for ttl in [2, 1] {
+            state.next(state.get_action(&mut InputBuffer::new()));
+            assert_eq!(state.bonus.map(|bonus| bonus.ttl), Some(ttl));
+        }
+        state.next(state.get_action(&mut InputBuffer::new()));
+        assert_eq!(state.bonus, None);
 
-        snake.direction = Direction::Right;
-        snake.move_direction();
+        // Undoing the tick it expired on brings it back
+        state.undo();
+        assert_eq!(state.bonus.map(|bonus| bonus.ttl), Some(1));
+    }
 
-        assert_eq!(snake.head, Position::new(6, 5));
-        assert_eq!(snake.tail.len(), 2);
-        assert_eq!(snake.tail.front().unwrap(), &Position::new(5, 5));
-        assert_eq!(snake.tail.back().unwrap(), &Position::new(4, 5));
+    #[test]
+    fn test_bonus_eaten_on_its_last_tick_counts() {
+        let mut state = GameState::with_seed(30, 15, 1);
+        bonus_ahead(&mut state, 3, 3);
+        for _ in 0..3 {
+            state.next(state.get_action(&mut InputBuffer::new()));
+        }
+        assert_eq!(state.score, BONUS_VALUE);
+        assert_eq!(state.bonus, None);
+    }
 
-        snake.direction = Direction::Right;
-        snake.move_direction();
+    #[test]
+    fn test_bonus_spawns_every_few_foods_and_can_be_undone() {
+        let mut state = GameState::with_seed(30, 15, 2);
+        for _ in 1..BONUS_EVERY {
+            state.next(Action::new(state.snake.head, None, true));
+            assert_eq!(state.bonus, None);
+        }
+        state.next(Action::new(state.snake.head, None, true));
+        let bonus = state.bonus.unwrap();
+        assert_eq!(bonus.ttl, BONUS_LIFETIME);
+        assert!(!state.snake.contains(bonus.position));
+        assert!(state.food_at(bonus.position).is_none());
 
-        assert_eq!(snake.head, Position::new(7, 5));
-        assert_eq!(snake.tail.len(), 2);
-        assert_eq!(snake.tail.front().unwrap(), &Position::new(6, 5));
-        assert_eq!(snake.tail.back().unwrap(), &Position::new(5, 5));
+        // Undoing the grow that dropped it takes it away again
+        state.undo();
+        assert_eq!(state.bonus, None);
+    }
+
+    #[test]
+    fn test_bonus_counts_foods_not_the_start_length() {
+        let mut state = GameState::with_seed(30, 15, 2).with_start_length(5);
+        for _ in 1..BONUS_EVERY {
+            state.next(Action::new(state.snake.head, None, true));
+            assert_eq!(state.bonus, None);
+        }
+        state.next(Action::new(state.snake.head, None, true));
+        assert_eq!(state.foods_eaten(), BONUS_EVERY);
+        assert!(state.bonus.is_some());
+    }
+
+    #[test]
+    fn test_eating_the_bonus_adds_its_value() {
+        let mut state = GameState::with_seed(30, 15, 1);
+        bonus_ahead(&mut state, 1, BONUS_LIFETIME);
+
+        let action = state.get_action(&mut InputBuffer::new());
+        assert!(!action.must_grow);
+        state.next(action);
+        assert_eq!(state.score, BONUS_VALUE);
+        assert_eq!(state.bonus, None);
+        assert_eq!(state.snake.len(), 1);
+
+        state.undo();
+        assert_eq!(state.score, 0);
+        assert!(state.bonus.is_some());
+    }
+
+    #[test]
+    fn test_phasing_counts_down_and_passes_the_border() {
+        let mut state = GameState::with_seed(30, 15, 1);
+        state.phase_pellet = Some(Position::new(16, 7));
+        state.next(state.get_action(&mut InputBuffer::new()));
+        assert_eq!(state.phase_pellet, None);
+        assert_eq!(state.phasing_ticks(), Some(PHASE_TICKS));
+
+        for remaining in (1..PHASE_TICKS).rev() {
+            state.next(state.get_action(&mut InputBuffer::new()));
+            assert_eq!(state.phasing_ticks(), Some(remaining));
+            assert_eq!(state.outcome(), None);
+        }
+        // Straight through the right border and back in on the left
+        assert!(state.snake.head.x < 16);
+
+        state.next(state.get_action(&mut InputBuffer::new()));
+        assert_eq!(state.phasing_ticks(), None);
+
+        // Undoing brings the effect back, and the pellet before it was eaten
+        state.undo();
+        assert_eq!(state.phasing_ticks(), Some(1));
+        while state.undo().is_some() {}
+        assert_eq!(state.phasing_ticks(), None);
+        assert_eq!(state.phase_pellet, Some(Position::new(16, 7)));
+    }
+
+    #[test]
+    fn test_phasing_through_the_tail_kills_on_the_next_normal_move() {
+        let mut state = GameState::with_seed(30, 15, 1);
+        state.snake.head = Position::new(10, 5);
+        state.snake.tail = (11..=20).map(|x| Position::new(x, 5)).collect();
+        state.effects.push(Effect {
+            kind: EffectKind::Phasing,
+            remaining: 2,
+        });
+
+        state.next(Action::new(state.snake.head, None, false));
+        assert!(state.snake.self_collision());
+        assert_eq!(state.outcome(), None);
+
+        // Wears off with the head still inside the tail
+        state.next(Action::new(state.snake.head, None, false));
+        assert_eq!(state.phasing_ticks(), None);
+        assert!(state.snake.self_collision());
+        assert_eq!(state.outcome(), None);
+
+        state.next(Action::new(state.snake.head, None, false));
+        assert_eq!(state.outcome(), Some(GameOutcome::SelfCollision));
+    }
+
+    #[test]
+    fn test_practice_passes_through_everything_and_still_eats() {
+        let mut state = GameState::with_seed(10, 8, 1).with_obstacles([Position::new(3, 3)]);
+        state.practice = true;
+        state.foods[0].position = Position::new(1, 3);
+
+        // Through the right wall onto the food on the left
+        state.snake.head = Position::new(8, 3);
+        let action = state.get_action(&mut InputBuffer::new());
+        assert!(action.must_grow);
+        state.next(action);
+        assert_eq!(state.snake.head, Position::new(1, 3));
+        assert_eq!(state.score, 1);
+
+        // Over the obstacle and back into its own tail
+        state.next(Action::new(state.snake.head, None, false));
+        state.next(Action::new(state.snake.head, None, false));
+        assert!(state.obstacles.contains(&state.snake.head));
+        assert_eq!(state.outcome(), None);
+        state.snake.tail.push_back(state.snake.head);
+        assert!(state.snake.self_collision());
+        assert_eq!(state.outcome(), None);
+        assert!(!state.is_ranked());
+
+        state.practice = false;
+        assert_eq!(state.outcome(), Some(GameOutcome::ObstacleCollision));
+    }
+
+    #[test]
+    fn test_slow_motion_doubles_the_interval_and_restores_it() {
+        let mut state = GameState::with_seed(30, 15, 1);
+        state.speed = Speed {
+            start: Duration::from_millis(100),
+            step: Duration::from_millis(10),
+            floor: Duration::from_millis(50),
+            points_per_step: 2,
+        };
+        state.foods[0] = Food {
+            position: Position::new(16, 7),
+            kind: FruitKind::Hourglass,
+        };
+        state.next(state.get_action(&mut InputBuffer::new()));
+        assert_eq!(state.effect_ticks(EffectKind::SlowMotion), Some(SLOW_TICKS));
+        assert_eq!(state.step_interval(), Duration::from_millis(200));
+
+        // Points earned while slowed still speed the snake up underneath
+        state.score = 4;
+        assert_eq!(state.step_interval(), Duration::from_millis(160));
+        for _ in 1..SLOW_TICKS {
+            state.bonus = None;
+            state.next(Action::new(state.snake.head, None, false));
+        }
+        assert_eq!(state.effect_ticks(EffectKind::SlowMotion), Some(1));
+        state.next(Action::new(state.snake.head, None, false));
+        assert_eq!(state.effect_ticks(EffectKind::SlowMotion), None);
+        assert_eq!(state.step_interval(), Duration::from_millis(80));
+    }
+
+    #[test]
+    fn test_slow_motion_stacks() {
+        let mut state = GameState::with_seed(30, 15, 1);
+        for x in [16, 17] {
+            state.foods[0] = Food {
+                position: Position::new(x, 7),
+                kind: FruitKind::Hourglass,
+            };
+            state.next(state.get_action(&mut InputBuffer::new()));
+        }
+        assert_eq!(
+            state.effect_ticks(EffectKind::SlowMotion),
+            Some(2 * SLOW_TICKS - 1)
+        );
+        state.undo();
+        assert_eq!(state.effect_ticks(EffectKind::SlowMotion), Some(SLOW_TICKS));
+    }
+
+    #[test]
+    fn test_same_seed_same_game() {
+        let mut first = GameState::with_seed(30, 15, 7);
+        let mut second = GameState::with_seed(30, 15, 7);
+        assert_eq!(first.foods[0].position, second.foods[0].position);
+
+        for _ in 0..5 {
+            // Force an eat every tick so food keeps respawning
+            first.next(Action::new(first.snake.head, None, true));
+            second.next(Action::new(second.snake.head, None, true));
+            assert_eq!(first.foods[0].position, second.foods[0].position);
+            assert_eq!(first.score, second.score);
+        }
+    }
+
+    #[test]
+    fn test_moving_past_zero_does_not_underflow() {
+        let mut snake = Snake::new(1, 1);
+        snake.direction = Direction::Left;
+        snake.move_direction();
+        assert_eq!(snake.head, Position::new(0, 1));
+        snake.move_direction();
+        assert_eq!(snake.head, Position::new(0, 1));
 
         snake.direction = Direction::Up;
         snake.move_direction();
+        snake.move_direction();
+        assert_eq!(snake.head, Position::new(0, 0));
 
-        assert_eq!(snake.head, Position::new(7, 4));
-        assert_eq!(snake.tail.len(), 2);
-        assert_eq!(snake.tail.front().unwrap(), &Position::new(7, 5));
-        assert_eq!(snake.tail.back().unwrap(), &Position::new(6, 5));
+        let corner = Position::new(u16::MAX, u16::MAX);
+        assert_eq!(corner.move_direction(Direction::Right), corner);
+        assert_eq!(corner.move_direction(Direction::Down), corner);
     }
 
     #[test]
-    fn test_snake_self_collision() {
-        let mut snake = Snake::new(5, 5);
-        snake.tail.push_back(Position::new(4, 5));
-        assert!(!snake.self_collision());
+    fn test_counters_saturate_instead_of_wrapping() {
+        let mut state = GameState::with_seed(30, 15, 1);
+        // Fast-forward the counters instead of playing for hours
+        state.score = u32::MAX - 1;
+        state.ticks = u64::MAX - 1;
+        state.fruits_eaten = [u32::MAX - 1; FruitKind::ALL.len()];
 
-        snake.tail.push_back(Position::new(5, 5));
-        assert!(snake.self_collision());
+        for _ in 0..3 {
+            state.next(Action::new(state.snake.head, None, true));
+        }
+
+        assert_eq!(state.score, u32::MAX);
+        assert_eq!(state.ticks, u64::MAX);
+        assert_eq!(state.fruits_eaten[FruitKind::Apple as usize], u32::MAX);
+        assert_eq!(state.foods_eaten(), u32::MAX);
+    }
+
+    fn assert_same_state(a: &GameState, b: &GameState) {
+        assert_eq!(a.snake.head, b.snake.head);
+        assert_eq!(a.snake.tail, b.snake.tail);
+        assert_eq!(a.snake.direction, b.snake.direction);
+        assert_eq!(a.snake.last_moved_direction, b.snake.last_moved_direction);
+        assert_eq!(a.foods, b.foods);
+        assert_eq!(a.score, b.score);
+        assert_eq!(a.ticks, b.ticks);
+        assert_eq!(a.actions.len(), b.actions.len());
+    }
+
+    fn snapshot(state: &GameState) -> GameState {
+        let mut copy = GameState::with_seed(state.game_width, state.game_height, state.seed);
+        copy.snake.head = state.snake.head;
+        copy.snake.tail = state.snake.tail.clone();
+        copy.snake.direction = state.snake.direction;
+        copy.snake.last_moved_direction = state.snake.last_moved_direction;
+        copy.foods = state.foods.clone();
+        copy.score = state.score;
+        copy.ticks = state.ticks;
+        copy.actions = state.actions.clone();
+        copy
+    }
+
+    #[test]
+    fn test_move_then_undo_restores_state() {
+        let mut state = GameState::with_seed(30, 15, 3);
+        state.snake.tail.push_back(Position::new(14, 7));
+        state.snake.tail.push_back(Position::new(13, 7));
+        let before = snapshot(&state);
+
+        let mut input = InputBuffer::new();
+        input.push(Direction::Up);
+        state.next(state.get_action(&mut input));
+        assert_ne!(state.snake.head, before.snake.head);
+
+        assert!(state.undo().is_some());
+        assert_same_state(&state, &before);
+    }
+
+    #[test]
+    fn test_grow_then_undo_shrinks_and_restores_food() {
+        let mut state = GameState::with_seed(30, 15, 3);
+        state.foods[0].position = Position::new(16, 7);
+        let before = snapshot(&state);
+
+        let action = state.get_action(&mut InputBuffer::new());
+        assert!(action.must_grow);
+        state.next(action);
+        let respawned_food = state.foods[0].position;
+        assert_eq!(state.snake.len(), 2);
+        assert_eq!(state.score, 1);
+
+        state.undo();
+        assert_same_state(&state, &before);
+
+        // The RNG is rewound too, so eating again respawns the same food
+        state.next(state.get_action(&mut InputBuffer::new()));
+        assert_eq!(state.foods[0].position, respawned_food);
+    }
+
+    #[test]
+    fn test_rewinding_every_move_gets_back_to_the_start() {
+        let mut state = GameState::with_seed(30, 15, 3);
+        state.foods[0].position = Position::new(17, 7);
+        let before = snapshot(&state);
+        let turns = [None, None, Some(Direction::Up), None, Some(Direction::Left)];
+        for turn in turns {
+            state.next(state.player_action(turn));
+        }
+        assert_eq!(state.snake.len(), 2);
+        assert!(!state.rewound);
+
+        for _ in turns {
+            assert!(state.undo().is_some());
+        }
+        assert!(state.undo().is_none());
+        assert_same_state(&state, &before);
+        assert_eq!(state.fruits_eaten, before.fruits_eaten);
+        assert_eq!(state.combo, before.combo);
+        assert_eq!(state.game_time, Duration::ZERO);
+        assert!(state.rewound);
+    }
+
+    #[test]
+    fn test_undo_goes_back_at_most_the_limit() {
+        let mut state = GameState::with_seed(30, 15, 3);
+        state.wall_mode = WallMode::Wrap;
+        for _ in 0..UNDO_LIMIT + 10 {
+            state.next(state.player_action(None));
+        }
+        for _ in 0..UNDO_LIMIT {
+            assert!(state.undo().is_some());
+        }
+        assert!(state.undo().is_none());
+        assert_eq!(state.ticks, 10);
+
+        // Playing on only brings back what was played since
+        for _ in 0..5 {
+            state.next(state.player_action(None));
+        }
+        for _ in 0..5 {
+            assert!(state.undo().is_some());
+        }
+        assert!(state.undo().is_none());
+    }
+
+    #[test]
+    fn test_undo_without_history() {
+        let mut state = GameState::with_seed(30, 15, 3);
+        let before = snapshot(&state);
+        assert!(state.undo().is_none());
+        assert_same_state(&state, &before);
+    }
+
+    #[test]
+    fn test_two_turns_in_one_frame_apply_over_two_ticks() {
+        let mut state = GameState::with_seed(30, 15, 3);
+        state.foods[0].position = Position::new(1, 1);
+        assert_eq!(state.snake.direction, Direction::Right);
+
+        // Up then Left pressed within the same frame
+        let mut input = InputBuffer::new();
+        input.push(Direction::Up);
+        input.push(Direction::Left);
+
+        state.next(state.get_action(&mut input));
+        assert_eq!(state.snake.direction, Direction::Up);
+        assert_eq!(state.snake.head, Position::new(15, 6));
+        assert_eq!(input.len(), 1);
+
+        state.next(state.get_action(&mut input));
+        assert_eq!(state.snake.direction, Direction::Left);
+        assert_eq!(state.snake.head, Position::new(14, 6));
+        assert!(input.is_empty());
+    }
+
+    #[test]
+    fn test_reverse_key_is_ignored_while_moving() {
+        let mut state = GameState::with_seed(30, 15, 3);
+        state.foods[0].position = Position::new(1, 1);
+        state.snake.head = Position::new(5, 5);
+        state.snake.tail = VecDeque::from([Position::new(4, 5), Position::new(3, 5)]);
+
+        // What the live loop does with a Left key while the snake moves Right
+        let mut input = InputBuffer::new();
+        assert!(input.push_command(InputCommand::Turn(Direction::Left)));
+        state.next(state.get_action(&mut input));
+
+        assert_eq!(state.snake.direction, Direction::Right);
+        assert_eq!(state.snake.head, Position::new(6, 5));
+        assert_eq!(state.outcome(), None);
+    }
+
+    #[test]
+    fn test_quick_double_turn_does_not_reverse_into_neck() {
+        let mut state = GameState::with_seed(30, 15, 3);
+        state.foods[0].position = Position::new(1, 1);
+        state.snake.head = Position::new(5, 5);
+        state.snake.tail = VecDeque::from([Position::new(4, 5), Position::new(3, 5)]);
+
+        // Up is requested but the snake has not stepped Up yet when Left arrives
+        state.snake.direction = Direction::Up;
+        let mut input = InputBuffer::new();
+        input.push(Direction::Left);
+        let action = state.get_action(&mut input);
+        assert_eq!(action.change_direction, None);
+
+        state.next(action);
+        assert_eq!(state.snake.head, Position::new(5, 4));
+        assert_eq!(state.snake.last_moved_direction, Direction::Up);
+        assert_eq!(state.outcome(), None);
+
+        // The same pair fed through the buffer in one tick
+        let mut state = GameState::with_seed(30, 15, 3);
+        state.foods[0].position = Position::new(1, 1);
+        state.snake.head = Position::new(5, 5);
+        state.snake.tail = VecDeque::from([Position::new(4, 5), Position::new(3, 5)]);
+        input.push(Direction::Up);
+        input.push(Direction::Left);
+        for _ in 0..2 {
+            state.next(state.get_action(&mut input));
+            assert!(!state.snake.self_collision());
+        }
+        assert_eq!(state.snake.head, Position::new(4, 4));
+    }
+
+    #[test]
+    fn test_input_buffer_drops_invalid_turns() {
+        let mut input = InputBuffer::new();
+        input.push(Direction::Left);
+        input.push(Direction::Right);
+        input.push(Direction::Down);
+        input.push(Direction::Up);
+        assert_eq!(input.len(), 3);
+
+        // Left reverses and Right repeats the current direction
+        assert_eq!(input.pop_valid(Direction::Right), Some(Direction::Down));
+        assert!(input.is_empty());
+    }
+
+    #[test]
+    fn test_same_seed_same_food_sequence() {
+        let food_sequence = |seed| {
+            let mut state = GameState::with_seed(30, 15, seed);
+            let mut positions = vec![state.foods[0].position];
+            for _ in 0..10 {
+                state.next(Action::new(state.snake.head, None, true));
+                positions.push(state.foods[0].position);
+            }
+            positions
+        };
+
+        assert_eq!(food_sequence(42), food_sequence(42));
+        assert_ne!(food_sequence(42), food_sequence(43));
+    }
+
+    #[test]
+    fn test_fruit_weights_use_the_game_rng() {
+        let picks = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let weights = FruitWeights([1, 0, 3, 0]);
+            (0..50).map(|_| weights.pick(&mut rng)).collect::<Vec<_>>()
+        };
+
+        let first = picks(7);
+        assert_eq!(first, picks(7));
+        assert_ne!(first, picks(8));
+        assert!(!first.contains(&FruitKind::Cherry));
+        assert!(first.contains(&FruitKind::Apple));
+        assert!(first.contains(&FruitKind::Banana));
+
+        // Spawned food draws its kind from the seeded game RNG as well
+        let kinds = |seed| {
+            let mut state = GameState::with_seed(30, 15, seed);
+            (0..20)
+                .map(|_| {
+                    state.next(Action::new(state.snake.head, None, true));
+                    state.foods[0].kind
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(kinds(42), kinds(42));
+    }
+
+    #[test]
+    fn test_fruit_weights_parse() {
+        assert_eq!("12,6,2,1".parse(), Ok(FruitWeights::default()));
+        assert_eq!(FruitWeights([0, 2, 5, 1]).to_string(), "0,2,5,1");
+        assert_eq!("12,6,2".parse(), Ok(FruitWeights([12, 6, 2, 0])));
+        for invalid in [
+            "",
+            "1,2",
+            "0,0,0",
+            "1,2,3,4,5",
+            "a,b,c,d",
+            "0,0,0,0",
+            "-1,2,3,4",
+        ] {
+            assert!(invalid.parse::<FruitWeights>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_fruit_weights_total_fits_a_u32() {
+        let max = u32::MAX;
+        let fits = format!("{},0,0", max);
+        assert_eq!(fits.parse(), Ok(FruitWeights([max, 0, 0, 0])));
+        let fits = format!("{},1,0,0", max - 1);
+        let weights: FruitWeights = fits.parse().unwrap();
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..100 {
+            weights.pick(&mut rng);
+        }
+
+        for too_much in [
+            format!("{},1,0", max),
+            format!("{},0,0,1", max),
+            format!("{},{},0,0", max / 2 + 1, max / 2 + 1),
+        ] {
+            assert!(too_much.parse::<FruitWeights>().is_err(), "{}", too_much);
+        }
+    }
+
+    #[test]
+    fn test_scoring_sums_fruit_points() {
+        let mut state = GameState::with_seed(30, 15, 5);
+        state.fruit_weights = FruitWeights([1, 1, 1, 1]);
+        let mut expected = 0;
+        let mut eaten = [0; 4];
+        for eaten_before in 0..20 {
+            // Bonuses are scored separately
+            state.bonus = None;
+            let kind = state.foods[0].kind;
+            state.next(Action::new(state.snake.head, None, true));
+            // One a tick keeps the combo going
+            expected += kind.points() * (eaten_before + 1).min(COMBO_CAP);
+            eaten[kind as usize] += 1;
+            assert_eq!(state.score, expected);
+        }
+        assert_eq!(state.fruits_eaten, eaten);
+        assert!(eaten.iter().all(|&count| count > 0));
+
+        // Undoing takes back exactly what the last fruit was worth
+        let last = state.actions.last().unwrap().food.kind;
+        state.undo();
+        assert_eq!(state.score, expected - last.points() * COMBO_CAP);
+        eaten[last as usize] -= 1;
+        assert_eq!(state.fruits_eaten, eaten);
+    }
+
+    #[test]
+    fn test_combo_builds_up_and_lapses() {
+        let second = Duration::from_secs(1);
+        let combo = Combo::default();
+        assert_eq!(combo.active(Duration::ZERO), None);
+        let combo = combo.after_eating(second);
+        assert_eq!(combo.multiplier, 1);
+        let combo = combo.after_eating(second + COMBO_WINDOW);
+        assert_eq!(combo.multiplier, 2);
+        assert_eq!(combo.active(second * 5), Some(2));
+        assert_eq!(combo.active(second * 5 + COMBO_WINDOW), None);
+
+        let mut combo = combo;
+        for _ in 0..10 {
+            combo = combo.after_eating(combo.last_eat + second);
+        }
+        assert_eq!(combo.multiplier, COMBO_CAP);
+        assert_eq!(combo.after_eating(second * 60).multiplier, 1);
+    }
+
+    #[test]
+    fn test_game_time_only_runs_while_stepping() {
+        let mut state = GameState::with_seed(30, 15, 5);
+        state.next(state.player_action(None));
+        // The wall clock of a pause or a countdown never reaches the steps
+        state.advance_clock(Duration::from_secs(90));
+        assert_eq!(state.game_time, DEFAULT_STEP_INTERVAL);
+        state.next(state.player_action(None));
+        assert_eq!(state.game_time, DEFAULT_STEP_INTERVAL * 2);
+        state.undo();
+        assert_eq!(state.game_time, DEFAULT_STEP_INTERVAL);
+    }
+
+    #[test]
+    fn test_combo_runs_on_game_time_and_undoes() {
+        let mut state = GameState::with_seed(30, 15, 5);
+        state.next(Action::new(state.snake.head, None, true));
+        state.next(Action::new(state.snake.head, None, true));
+        assert_eq!(state.combo_multiplier(), Some(2));
+        assert_eq!(state.game_time, DEFAULT_STEP_INTERVAL * 2);
+
+        // Going on without eating lets it lapse after a few seconds of steps
+        state.wall_mode = WallMode::Wrap;
+        state.foods[0] = Food::at(Position::new(1, 1));
+        let steps = COMBO_WINDOW.as_millis() / state.step_interval().as_millis();
+        for _ in 0..steps {
+            state.next(Action::new(state.snake.head, None, false));
+        }
+        assert_eq!(state.combo_multiplier(), Some(2));
+        state.next(Action::new(state.snake.head, None, false));
+        assert_eq!(state.combo_multiplier(), None);
+
+        let score = state.score;
+        state.next(Action::new(state.snake.head, None, true));
+        assert_eq!(state.combo.multiplier, 1);
+        state.undo();
+        assert_eq!(state.score, score);
+        assert_eq!(state.combo.multiplier, 2);
+        assert_eq!(state.combo_multiplier(), None);
+    }
+
+    #[test]
+    fn test_wandering_food_stays_in_the_playable_area() {
+        let mut state = GameState::with_seed(30, 15, 11)
+            .with_obstacles([Position::new(3, 3), Position::new(8, 5)])
+            .with_food_count(3);
+        state.wall_mode = WallMode::Wrap;
+        state.food_behavior = FoodBehavior::Wandering;
+        let mut turns = StdRng::seed_from_u64(5);
+        let start = state.foods.clone();
+
+        for _ in 0..2000 {
+            // Any turn that does not crash, so the run lasts
+            let safe: Vec<Direction> = Direction::ALL
+                .into_iter()
+                .filter(|&direction| {
+                    let next = state.wrap(state.snake.head.move_direction(direction));
+                    direction != state.snake.direction.reverse()
+                        && !state.snake.tail.contains(&next)
+                        && !state.obstacles.contains(&next)
+                })
+                .collect();
+            let Some(&turn) = safe.choose(&mut turns) else {
+                break;
+            };
+            let mut input = InputBuffer::new();
+            input.push(turn);
+            state.next(state.get_action(&mut input));
+            for food in &state.foods {
+                let position = food.position;
+                assert!(!position.is_on_border(30, 15), "{:?}", position);
+                assert!(position.x < 30 && position.y < 15, "{:?}", position);
+                assert!(!state.obstacles.contains(&position), "{:?}", position);
+                assert!(!state.snake.contains(position), "{:?}", position);
+            }
+        }
+        assert_ne!(state.foods, start);
+    }
+
+    #[test]
+    fn test_food_walking_into_the_head_is_eaten() {
+        // Walled in on three sides, the food's only way out is where the head
+        // is about to be
+        let mut state = GameState::with_seed(10, 8, 1).with_obstacles([
+            Position::new(8, 4),
+            Position::new(7, 3),
+            Position::new(7, 5),
+        ]);
+        state.food_behavior = FoodBehavior::Wandering;
+        state.foods[0] = Food::at(Position::new(7, 4));
+        state.ticks = WANDER_EVERY - 1;
+        let before = snapshot(&state);
+
+        let action = state.get_action(&mut InputBuffer::new());
+        assert!(!action.must_grow);
+        state.next(action);
+        assert_eq!(state.snake.head, Position::new(6, 4));
+        assert_eq!(state.snake.len(), 2);
+        assert_eq!(state.score, 1);
+        assert_ne!(state.foods[0].position, Position::new(6, 4));
+        assert!(state.actions[0].must_grow);
+
+        state.undo();
+        assert_same_state(&state, &before);
+    }
+
+    #[test]
+    fn test_wandering_food_replays_and_undoes() {
+        let play = |state: &mut GameState| {
+            for turn in [None, Some(Direction::Up), None, Some(Direction::Left)] {
+                for _ in 0..3 {
+                    state.next(Action::new(state.snake.head, turn, false));
+                }
+            }
+        };
+        let mut state = GameState::with_seed(30, 15, 8);
+        state.food_behavior = FoodBehavior::Wandering;
+        let before = snapshot(&state);
+        play(&mut state);
+        let after = state.foods.clone();
+
+        while state.undo().is_some() {}
+        assert_same_state(&state, &before);
+        // The RNG was rewound as well, so playing again wanders the same way
+        play(&mut state);
+        assert_eq!(state.foods, after);
+    }
+
+    #[test]
+    fn test_game_logic_does_not_depend_on_the_terminal() {
+        // Split so this test does not trip over its own source
+        let backend = ["cross", "term"].concat();
+        assert!(!include_str!("game.rs").contains(&backend));
+    }
+
+    fn kinds(events: &[GameEvent]) -> Vec<GameEventKind> {
+        events.iter().map(|event| event.kind).collect()
+    }
+
+    #[test]
+    fn test_events_of_moving_and_eating() {
+        let mut state = GameState::with_seed(30, 15, 3);
+        let start = state.snake.head;
+        let ahead = Position::new(start.x + 1, start.y);
+        let events = state.next(state.player_action(Some(Direction::Up)));
+        assert_eq!(
+            *events,
+            [GameEvent {
+                tick: 1,
+                kind: GameEventKind::Moved {
+                    from: start,
+                    to: Position::new(start.x, start.y - 1),
+                },
+            }]
+        );
+
+        let mut state = GameState::with_seed(30, 15, 3);
+        state.foods[0] = Food::at(ahead);
+        state.bonus = Some(BonusFood {
+            position: ahead,
+            ttl: 5,
+            value: 7,
+        });
+        let events = state.next(state.player_action(None));
+        assert!(events.iter().all(|event| event.tick == 1));
+        assert_eq!(
+            kinds(&events),
+            [
+                GameEventKind::Moved {
+                    from: start,
+                    to: ahead
+                },
+                GameEventKind::FoodEaten {
+                    position: ahead,
+                    kind: FruitKind::Apple,
+                    points: 1
+                },
+                GameEventKind::Grew { length: 2 },
+                GameEventKind::BonusEaten {
+                    position: ahead,
+                    points: 7
+                },
+                GameEventKind::SpeedChanged { level: 1 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_events_tell_a_whole_game_without_a_screen() {
+        let mut state = GameState::with_seed(30, 15, 3);
+        let mut events = Vec::new();
+        while state.outcome().is_none() && state.ticks < 100 {
+            let head = state.snake.head;
+            state.foods[0] = Food::at(if state.ticks < 3 {
+                Position::new(head.x + 1, head.y)
+            } else {
+                Position::new(1, 1)
+            });
+            events.extend(kinds(&state.next(state.player_action(None))));
+        }
+        let eaten = events
+            .iter()
+            .filter(|kind| matches!(kind, GameEventKind::FoodEaten { .. }))
+            .count();
+        assert_eq!(eaten, 3);
+        assert_eq!(
+            events.last(),
+            Some(&GameEventKind::Died {
+                cause: GameOutcome::WallCollision
+            })
+        );
+    }
+
+    #[test]
+    fn test_events_of_effects_and_speed() {
+        let mut state = GameState::with_seed(30, 15, 3);
+        state.speed = Speed {
+            points_per_step: 1,
+            ..state.speed
+        };
+        let start = state.snake.head;
+        state.foods[0] = Food {
+            position: Position::new(start.x + 1, start.y),
+            kind: FruitKind::Hourglass,
+        };
+        let events = kinds(&state.next(state.player_action(None)));
+        assert!(events.contains(&GameEventKind::EffectGained {
+            kind: EffectKind::SlowMotion
+        }));
+        assert_eq!(
+            events.last(),
+            Some(&GameEventKind::SpeedChanged { level: 1 })
+        );
+
+        state.phase_pellet = Some(Position::new(start.x + 2, start.y));
+        let events = kinds(&state.next(state.player_action(None)));
+        assert_eq!(
+            events[1..],
+            [GameEventKind::EffectGained {
+                kind: EffectKind::Phasing
+            }]
+        );
+    }
+
+    #[test]
+    fn test_events_of_dying_and_respawning() {
+        let mut state = GameState::with_seed(30, 15, 3);
+        state.snake.head = Position::new(28, 7);
+        let events = kinds(&state.next(state.player_action(None)));
+        assert_eq!(
+            events[1..],
+            [GameEventKind::Died {
+                cause: GameOutcome::WallCollision
+            }]
+        );
+
+        let mut state = GameState::with_seed(30, 15, 3).with_mode(GameMode::Lives);
+        state.snake.head = Position::new(28, 7);
+        let events = kinds(&state.next(state.player_action(None)));
+        assert_eq!(
+            events[1..],
+            [
+                GameEventKind::Died {
+                    cause: GameOutcome::WallCollision
+                },
+                GameEventKind::Respawned { lives: LIVES - 1 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_events_of_winning() {
+        // Every cell but the food's taken, and the head about to eat it
+        let mut state = GameState::with_seed(5, 5, 3);
+        state.snake = Snake::with_length(3, 2, Direction::Down, 0);
+        state.snake.tail = [(3, 1), (2, 1), (1, 1), (1, 2), (2, 2), (2, 3), (1, 3)]
+            .into_iter()
+            .map(|(x, y)| Position::new(x, y))
+            .collect();
+        state.foods[0] = Food::at(Position::new(3, 3));
+        let events = kinds(&state.next(state.player_action(None)));
+        assert_eq!(
+            events[2..],
+            [GameEventKind::Grew { length: 9 }, GameEventKind::Won]
+        );
     }
 }
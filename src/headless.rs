@@ -0,0 +1,225 @@
+use crate::{
+    controller::Controller,
+    game::{GameEventKind, GameState, PLAYER},
+};
+use std::{collections::BTreeMap, fmt::Write};
+
+// Games still going after this many ticks are called off, a bot that never
+// dies would otherwise keep a batch running forever
+pub const MAX_TICKS: u64 = 10_000;
+
+// How a batch of games went
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Summary {
+    pub games: u32,
+    pub total_score: u64,
+    pub max_score: u32,
+    pub total_ticks: u64,
+    // Games by how they ended, "unfinished" for the ones called off
+    pub endings: BTreeMap<&'static str, u32>,
+}
+
+impl Summary {
+    pub fn record(&mut self, state: &GameState) {
+        self.games += 1;
+        self.total_score += u64::from(state.score);
+        self.max_score = self.max_score.max(state.score);
+        self.total_ticks += state.ticks;
+        let ending = state
+            .outcome()
+            .map_or("unfinished", |outcome| outcome.name());
+        *self.endings.entry(ending).or_default() += 1;
+    }
+
+    pub fn mean_score(&self) -> f64 {
+        if self.games == 0 {
+            return 0.0;
+        }
+        self.total_score as f64 / f64::from(self.games)
+    }
+
+    pub fn table(&self) -> String {
+        let mut table = String::new();
+        let _ = writeln!(table, "games       {}", self.games);
+        let _ = writeln!(table, "mean score  {:.2}", self.mean_score());
+        let _ = writeln!(table, "max score   {}", self.max_score);
+        let _ = writeln!(table, "ticks       {}", self.total_ticks);
+        let _ = writeln!(table, "ended by");
+        for (ending, games) in &self.endings {
+            let _ = writeln!(table, "  {:<10}{}", ending, games);
+        }
+        table
+    }
+
+    pub fn json(&self) -> String {
+        let endings: Vec<String> = self
+            .endings
+            .iter()
+            .map(|(ending, games)| format!("\"{}\":{}", ending, games))
+            .collect();
+        format!(
+            "{{\"games\":{},\"mean_score\":{:.2},\"max_score\":{},\"ticks\":{},\"endings\":{{{}}}}}",
+            self.games,
+            self.mean_score(),
+            self.max_score,
+            self.total_ticks,
+            endings.join(",")
+        )
+    }
+}
+
+// Plays one game to its end with no terminal at all. Game time moves on by
+// a step each tick, so timed games and a shrinking arena end as they would
+// have on screen.
+pub fn play(state: &mut GameState, controller: &mut dyn Controller, max_ticks: u64) {
+    controller.reset();
+    // The outcome walks the whole snake, so it is only looked at once a tick
+    let mut over = state.outcome().is_some();
+    while !over && state.ticks < max_ticks {
+        let interval = state.step_interval();
+        let change_direction = controller.decide(state, PLAYER);
+        let events = state.next(state.player_action(change_direction));
+        if events
+            .iter()
+            .any(|event| matches!(event.kind, GameEventKind::Respawned { .. }))
+        {
+            controller.reset();
+        }
+        over = state.outcome().is_some();
+        // Only a clock that is running can end the game on its own
+        if !over && (state.time_left.is_some() || state.next_shrink.is_some()) {
+            state.advance_clock(interval);
+            over = state.outcome().is_some();
+        }
+    }
+}
+
+// Plays `games` games, each set up by `new_game` from its number in the
+// batch, and sums up how they went
+pub fn simulate(
+    games: u32,
+    mut new_game: impl FnMut(u32) -> GameState,
+    controller: &mut dyn Controller,
+    max_ticks: u64,
+) -> Summary {
+    let mut summary = Summary::default();
+    for game in 0..games {
+        let mut state = new_game(game);
+        play(&mut state, controller, max_ticks);
+        summary.record(&state);
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        controller::{AutoController, ScriptedController},
+        game::{Direction, GameMode, GameOutcome},
+    };
+    use std::time::Instant;
+
+    #[test]
+    fn test_a_batch_sums_up_every_game() {
+        // Straight into the right wall every time
+        let mut controller = ScriptedController::new([]);
+        let summary = simulate(
+            5,
+            |game| GameState::with_seed(30, 15, u64::from(game)),
+            &mut controller,
+            MAX_TICKS,
+        );
+        assert_eq!(summary.games, 5);
+        assert_eq!(summary.endings.get("wall"), Some(&5));
+        assert_eq!(summary.total_ticks, 5 * 14);
+        assert!(summary.table().contains("wall      5"));
+        assert!(summary.json().starts_with("{\"games\":5,"));
+        assert!(summary.json().ends_with("\"endings\":{\"wall\":5}}"));
+    }
+
+    #[test]
+    fn test_the_same_seeds_give_the_same_batch() {
+        let batch = || {
+            simulate(
+                20,
+                |game| GameState::with_seed(30, 15, u64::from(game)),
+                &mut AutoController::new(1),
+                500,
+            )
+        };
+        let summary = batch();
+        assert!(summary.max_score > 0);
+        assert_eq!(batch(), summary);
+    }
+
+    #[test]
+    fn test_the_same_script_plays_out_the_same() {
+        // The bot's turns for a while, then straight on into a wall
+        let mut state = GameState::with_seed(20, 10, 9);
+        let mut bot = AutoController::new(1);
+        let script: Vec<_> = (0..150)
+            .map(|_| {
+                let turn = bot.decide(&state, PLAYER);
+                state.next(state.player_action(turn));
+                turn
+            })
+            .collect();
+
+        let replay = || {
+            let mut state = GameState::with_seed(20, 10, 9);
+            play(
+                &mut state,
+                &mut ScriptedController::new(script.clone()),
+                MAX_TICKS,
+            );
+            state
+        };
+        let (first, second) = (replay(), replay());
+        assert!(first.score > 0);
+        assert!(first.outcome().is_some());
+        assert_eq!(
+            (first.score, first.ticks, first.outcome()),
+            (second.score, second.ticks, second.outcome())
+        );
+        assert_eq!(first.to_ascii(), second.to_ascii());
+    }
+
+    #[test]
+    fn test_timed_games_run_out_of_time() {
+        // Round and round a little square, never dying
+        let turns = [
+            Direction::Down,
+            Direction::Left,
+            Direction::Up,
+            Direction::Right,
+        ];
+        let script = turns.iter().cycle().take(4_000).map(|&turn| Some(turn));
+        let mut controller = ScriptedController::new(script);
+        let mut state = GameState::with_seed(30, 15, 1).with_mode(GameMode::Timed);
+        play(&mut state, &mut controller, MAX_TICKS);
+        assert_eq!(state.outcome(), Some(GameOutcome::TimesUp));
+        assert!(state.ticks < MAX_TICKS);
+    }
+
+    // A budget rather than a benchmark, low enough to hold in a debug build
+    // on a slow machine: fifty bot games on a 30x15 board, over fifty
+    // thousand ticks between them, at more than twenty thousand ticks a
+    // second
+    #[test]
+    fn test_bot_games_run_at_over_twenty_thousand_ticks_a_second() {
+        let mut bot = AutoController::new(1);
+        let start = Instant::now();
+        let summary = simulate(
+            50,
+            |game| GameState::with_seed(30, 15, u64::from(game)),
+            &mut bot,
+            MAX_TICKS,
+        );
+        let elapsed = start.elapsed();
+        assert_eq!(summary.games, 50);
+        assert!(summary.total_ticks > 50_000, "{}", summary.total_ticks);
+        let rate = summary.total_ticks as f64 / elapsed.as_secs_f64();
+        assert!(rate > 20_000.0, "{:.0} ticks a second", rate);
+    }
+}
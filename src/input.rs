@@ -0,0 +1,224 @@
+use crate::game::Direction;
+use std::{collections::VecDeque, fmt::Write};
+
+// What the game understands, whatever the input came from. Each source (only
+// the keyboard for now) translates its own events into these.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputCommand {
+    Turn(Direction),
+    // A turn for the second player's snake in a two-player game
+    SecondTurn(Direction),
+    Pause,
+    Undo,
+    Quit,
+    // Switch to the next color theme
+    CycleTheme,
+    // Show or hide the measured frame and tick rates
+    ToggleFps,
+    // Show or hide the side panel, for more room on narrow terminals
+    TogglePanel,
+    Back,
+    Restart,
+    // Pressed or released
+    Boost(bool),
+    MenuNav(MenuNav),
+    // A character typed into a text field, or an unbound key during play
+    Raw(char),
+    // The terminal is now this many columns and rows
+    Resize(u16, u16),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MenuNav {
+    NextField,
+    Confirm,
+    Erase,
+}
+
+// How many queued commands are looked at per frame. A stuck key or a terminal
+// flooding repeats would otherwise eat the whole frame budget.
+pub const MAX_EVENTS_PER_FRAME: usize = 64;
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Drain {
+    // What to apply this frame, oldest first
+    pub commands: Vec<InputCommand>,
+    // Repeats of the previous turn that were merged into it
+    pub coalesced: usize,
+    // Commands past the bound, left queued for the next frame
+    pub deferred: usize,
+}
+
+// Takes at most `limit` commands off the front of `pending`, merging runs of
+// the same turn, either player's, into one. Whatever is past the bound stays
+// queued, but pause, quit, back and restart are always taken so they are
+// never stuck behind a flood of turns or typed keys.
+pub fn drain(pending: &mut VecDeque<InputCommand>, limit: usize) -> Drain {
+    let mut drained = Drain::default();
+    let mut last_turn = None;
+    let mut deferred = VecDeque::new();
+
+    for (index, command) in pending.drain(..).enumerate() {
+        if index >= limit {
+            if is_control(command) {
+                drained.commands.push(command);
+            } else {
+                deferred.push_back(command);
+            }
+            continue;
+        }
+
+        let turn = matches!(command, InputCommand::Turn(_) | InputCommand::SecondTurn(_))
+            .then_some(command);
+        if turn.is_some() && turn == last_turn {
+            drained.coalesced += 1;
+            continue;
+        }
+        last_turn = turn;
+        drained.commands.push(command);
+    }
+
+    drained.deferred = deferred.len();
+    *pending = deferred;
+    drained
+}
+
+// Commands that stop or leave the game, which no flood may hold back
+fn is_control(command: InputCommand) -> bool {
+    matches!(
+        command,
+        InputCommand::Pause | InputCommand::Quit | InputCommand::Back | InputCommand::Restart
+    )
+}
+
+// Running totals, shown with the frame rate
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DrainStats {
+    pub coalesced: u64,
+    pub deferred: u64,
+}
+
+impl DrainStats {
+    pub fn record(&mut self, drain: &Drain) {
+        self.coalesced = self.coalesced.saturating_add(drain.coalesced as u64);
+        self.deferred = self.deferred.saturating_add(drain.deferred as u64);
+    }
+
+    // Into `label`, kept from frame to frame like the frame rate's
+    pub fn write_label(&self, label: &mut String) {
+        label.clear();
+        let _ = write!(label, "merged {} late {}", self.coalesced, self.deferred);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use InputCommand::{Pause, Quit, Raw, SecondTurn, ToggleFps, Turn, Undo};
+
+    const UP: InputCommand = Turn(Direction::Up);
+    const DOWN: InputCommand = Turn(Direction::Down);
+    const LEFT: InputCommand = Turn(Direction::Left);
+    const RIGHT: InputCommand = Turn(Direction::Right);
+
+    #[test]
+    fn test_drain_is_bounded() {
+        let mut pending: VecDeque<_> = [UP, LEFT].into_iter().cycle().take(100).collect();
+
+        let drained = drain(&mut pending, MAX_EVENTS_PER_FRAME);
+        assert_eq!(drained.commands.len(), MAX_EVENTS_PER_FRAME);
+        assert_eq!(drained.deferred, 100 - MAX_EVENTS_PER_FRAME);
+        assert_eq!(pending.len(), 100 - MAX_EVENTS_PER_FRAME);
+
+        let drained = drain(&mut pending, MAX_EVENTS_PER_FRAME);
+        assert_eq!(drained.commands.len(), 100 - MAX_EVENTS_PER_FRAME);
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn test_drain_coalesces_repeated_directions() {
+        let mut pending = VecDeque::from([UP, UP, UP, LEFT, Undo, LEFT, LEFT]);
+
+        let drained = drain(&mut pending, MAX_EVENTS_PER_FRAME);
+        // A command in between breaks the run
+        assert_eq!(drained.commands, [UP, LEFT, Undo, LEFT]);
+        assert_eq!(drained.coalesced, 3);
+        assert_eq!(drained.deferred, 0);
+    }
+
+    #[test]
+    fn test_commands_past_the_bound_are_not_lost() {
+        let mut pending = VecDeque::from([UP, LEFT, DOWN, Pause, RIGHT, Quit]);
+
+        let drained = drain(&mut pending, 2);
+        assert_eq!(drained.commands, [UP, LEFT, Pause, Quit]);
+        assert_eq!(pending, [DOWN, RIGHT]);
+        assert_eq!(drained.deferred, 2);
+    }
+
+    #[test]
+    fn test_only_control_commands_skip_the_bound() {
+        let second_up = SecondTurn(Direction::Up);
+        let mut pending = VecDeque::from([
+            UP,
+            second_up,
+            second_up,
+            Raw('x'),
+            SecondTurn(Direction::Left),
+            ToggleFps,
+            Undo,
+            Quit,
+        ]);
+
+        let drained = drain(&mut pending, 3);
+        // The second player's repeats are merged like the first's
+        assert_eq!(drained.commands, [UP, second_up, Quit]);
+        assert_eq!(drained.coalesced, 1);
+        assert_eq!(
+            pending,
+            [Raw('x'), SecondTurn(Direction::Left), ToggleFps, Undo]
+        );
+        assert_eq!(drained.deferred, 4);
+
+        let mut stats = DrainStats::default();
+        stats.record(&drained);
+        let mut label = String::new();
+        stats.write_label(&mut label);
+        assert_eq!(label, "merged 1 late 4");
+    }
+
+    #[test]
+    fn test_drain_stress() {
+        let directions = [UP, UP, RIGHT, DOWN];
+        let mut pending: VecDeque<_> = (0..10_000)
+            .map(|i| {
+                if i == 9_999 {
+                    Quit
+                } else {
+                    directions[i % directions.len()]
+                }
+            })
+            .collect();
+
+        let mut stats = DrainStats::default();
+        let drained = drain(&mut pending, MAX_EVENTS_PER_FRAME);
+        stats.record(&drained);
+
+        assert!(drained.commands.len() <= MAX_EVENTS_PER_FRAME + 1);
+        assert_eq!(drained.commands.last(), Some(&Quit));
+        assert_eq!(pending.len(), 10_000 - MAX_EVENTS_PER_FRAME - 1);
+        assert_eq!(stats.deferred, pending.len() as u64);
+
+        // Everything left is worked off in bounded frames
+        let mut frames = 1;
+        while !pending.is_empty() {
+            let drained = drain(&mut pending, MAX_EVENTS_PER_FRAME);
+            assert!(drained.commands.len() <= MAX_EVENTS_PER_FRAME);
+            stats.record(&drained);
+            frames += 1;
+        }
+        let left_after_first = 10_000 - MAX_EVENTS_PER_FRAME - 1;
+        assert_eq!(frames, 1 + left_after_first.div_ceil(MAX_EVENTS_PER_FRAME));
+        assert!(stats.coalesced > 0);
+    }
+}
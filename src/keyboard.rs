@@ -0,0 +1,449 @@
+use crate::{
+    game::Direction,
+    input::{InputCommand, MenuNav},
+};
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+
+// Which keys trigger each game command. Every action can have several keys,
+// the first one is the one shown in help text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyBindings {
+    pub up: Vec<KeyCode>,
+    pub down: Vec<KeyCode>,
+    pub left: Vec<KeyCode>,
+    pub right: Vec<KeyCode>,
+    pub pause: Vec<KeyCode>,
+    pub undo: Vec<KeyCode>,
+    pub quit: Vec<KeyCode>,
+    pub theme: Vec<KeyCode>,
+    pub fps: Vec<KeyCode>,
+    pub panel: Vec<KeyCode>,
+    pub boost: Vec<KeyCode>,
+    // The second player's, none unless there is one
+    pub second_up: Vec<KeyCode>,
+    pub second_down: Vec<KeyCode>,
+    pub second_left: Vec<KeyCode>,
+    pub second_right: Vec<KeyCode>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            up: vec![KeyCode::Up, KeyCode::Char('w')],
+            down: vec![KeyCode::Down, KeyCode::Char('s')],
+            left: vec![KeyCode::Left, KeyCode::Char('a')],
+            right: vec![KeyCode::Right, KeyCode::Char('d')],
+            // Not 's', that is down for WASD players
+            pause: vec![KeyCode::Char('p')],
+            undo: vec![KeyCode::Char('u')],
+            quit: vec![KeyCode::Esc],
+            theme: vec![KeyCode::Char('t')],
+            fps: vec![KeyCode::F(3)],
+            // Not 'p', that pauses
+            panel: vec![KeyCode::Char('h')],
+            boost: vec![KeyCode::Char(' ')],
+            second_up: Vec::new(),
+            second_down: Vec::new(),
+            second_left: Vec::new(),
+            second_right: Vec::new(),
+        }
+    }
+}
+
+impl KeyBindings {
+    fn actions(&self) -> [(&'static str, &Vec<KeyCode>, InputCommand); 15] {
+        [
+            ("up", &self.up, InputCommand::Turn(Direction::Up)),
+            ("down", &self.down, InputCommand::Turn(Direction::Down)),
+            ("left", &self.left, InputCommand::Turn(Direction::Left)),
+            ("right", &self.right, InputCommand::Turn(Direction::Right)),
+            ("pause", &self.pause, InputCommand::Pause),
+            ("undo", &self.undo, InputCommand::Undo),
+            ("quit", &self.quit, InputCommand::Quit),
+            ("theme", &self.theme, InputCommand::CycleTheme),
+            ("fps", &self.fps, InputCommand::ToggleFps),
+            ("panel", &self.panel, InputCommand::TogglePanel),
+            ("boost", &self.boost, InputCommand::Boost(true)),
+            (
+                "p2-up",
+                &self.second_up,
+                InputCommand::SecondTurn(Direction::Up),
+            ),
+            (
+                "p2-down",
+                &self.second_down,
+                InputCommand::SecondTurn(Direction::Down),
+            ),
+            (
+                "p2-left",
+                &self.second_left,
+                InputCommand::SecondTurn(Direction::Left),
+            ),
+            (
+                "p2-right",
+                &self.second_right,
+                InputCommand::SecondTurn(Direction::Right),
+            ),
+        ]
+    }
+
+    // WASD goes to the second player unless they were given keys of their
+    // own, and whatever keys they have the first player no longer turns with
+    pub fn split_for_two_players(&mut self) {
+        for (keys, default) in [
+            (&mut self.second_up, 'w'),
+            (&mut self.second_down, 's'),
+            (&mut self.second_left, 'a'),
+            (&mut self.second_right, 'd'),
+        ] {
+            if keys.is_empty() {
+                keys.push(KeyCode::Char(default));
+            }
+        }
+        let taken: Vec<KeyCode> = [
+            &self.second_up,
+            &self.second_down,
+            &self.second_left,
+            &self.second_right,
+        ]
+        .into_iter()
+        .flatten()
+        .copied()
+        .collect();
+        for keys in [
+            &mut self.up,
+            &mut self.down,
+            &mut self.left,
+            &mut self.right,
+        ] {
+            keys.retain(|key| !taken.contains(key));
+        }
+    }
+
+    // `action=key[,key...]`, replacing the keys the action had
+    pub fn bind(&mut self, spec: &str) -> Result<(), String> {
+        let (action, keys) = spec
+            .split_once('=')
+            .ok_or_else(|| format!("expected ACTION=KEY, got '{}'", spec))?;
+        let keys = keys
+            .split(',')
+            .map(|name| parse_key(name).ok_or_else(|| format!("unknown key '{}'", name)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let slot = match action {
+            "up" => &mut self.up,
+            "down" => &mut self.down,
+            "left" => &mut self.left,
+            "right" => &mut self.right,
+            "pause" => &mut self.pause,
+            "undo" => &mut self.undo,
+            "quit" => &mut self.quit,
+            "theme" => &mut self.theme,
+            "fps" => &mut self.fps,
+            "panel" => &mut self.panel,
+            "boost" => &mut self.boost,
+            "p2-up" => &mut self.second_up,
+            "p2-down" => &mut self.second_down,
+            "p2-left" => &mut self.second_left,
+            "p2-right" => &mut self.second_right,
+            _ => return Err(format!("unknown action '{}'", action)),
+        };
+        *slot = keys;
+        Ok(())
+    }
+
+    // A key may only do one thing
+    pub fn validate(&self) -> Result<(), String> {
+        let actions = self.actions();
+        for (i, (action, keys, _)) in actions.iter().enumerate() {
+            for (other, other_keys, _) in &actions[i + 1..] {
+                if let Some(key) = keys.iter().find(|key| other_keys.contains(key)) {
+                    return Err(format!(
+                        "key '{}' is bound to both {} and {}",
+                        key_name(*key),
+                        action,
+                        other
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // The first key of an action, for help text
+    pub fn label(&self, command: InputCommand) -> String {
+        self.actions()
+            .iter()
+            .find(|(_, _, bound)| *bound == command)
+            .and_then(|(_, keys, _)| keys.first())
+            .map_or_else(|| "?".to_string(), |key| key_name(*key))
+    }
+
+    // Translates a terminal key into a command. While `typing` into a text
+    // field every printable key is taken literally, otherwise letters bound to
+    // game commands take precedence. Of the releases, which only terminals
+    // with the keyboard enhancement report, the boost key's ends the boost.
+    pub fn translate(&self, key: KeyEvent, typing: bool) -> Option<InputCommand> {
+        if key.kind == KeyEventKind::Release {
+            let boost = !typing && self.boost.contains(&key.code);
+            return boost.then_some(InputCommand::Boost(false));
+        }
+        // Raw mode swallows the interrupt signal, so Ctrl+C has to be handled here
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return Some(InputCommand::Quit);
+        }
+
+        if let KeyCode::Char(c) = key.code {
+            if typing {
+                return Some(InputCommand::Raw(c));
+            }
+        }
+        let bound = self
+            .actions()
+            .into_iter()
+            .find(|(_, keys, _)| keys.contains(&key.code))
+            .map(|(_, _, command)| command);
+        if bound.is_some() {
+            return bound;
+        }
+
+        let command = match key.code {
+            KeyCode::Tab => InputCommand::MenuNav(MenuNav::NextField),
+            KeyCode::Enter => InputCommand::MenuNav(MenuNav::Confirm),
+            KeyCode::Backspace => InputCommand::MenuNav(MenuNav::Erase),
+            KeyCode::Char(c) => InputCommand::Raw(c),
+            _ => return None,
+        };
+        Some(command)
+    }
+}
+
+// With the default bindings, for screens that don't let keys be rebound
+pub fn translate(key: KeyEvent, typing: bool) -> Option<InputCommand> {
+    KeyBindings::default().translate(key, typing)
+}
+
+// A single character or one of the named keys below, case insensitive
+pub fn parse_key(name: &str) -> Option<KeyCode> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(KeyCode::Char(c));
+    }
+    let code = match name.to_ascii_lowercase().as_str() {
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "esc" => KeyCode::Esc,
+        "enter" => KeyCode::Enter,
+        "tab" => KeyCode::Tab,
+        "backspace" => KeyCode::Backspace,
+        "space" => KeyCode::Char(' '),
+        name => match name.strip_prefix('f').and_then(|n| n.parse().ok()) {
+            Some(n @ 1..=12) => KeyCode::F(n),
+            _ => return None,
+        },
+    };
+    Some(code)
+}
+
+pub fn key_name(code: KeyCode) -> String {
+    match code {
+        KeyCode::Char(' ') => "SPACE".to_string(),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::Up => "UP".to_string(),
+        KeyCode::Down => "DOWN".to_string(),
+        KeyCode::Left => "LEFT".to_string(),
+        KeyCode::Right => "RIGHT".to_string(),
+        KeyCode::Esc => "ESC".to_string(),
+        KeyCode::Enter => "ENTER".to_string(),
+        KeyCode::Tab => "TAB".to_string(),
+        KeyCode::Backspace => "BACKSPACE".to_string(),
+        KeyCode::F(n) => format!("F{}", n),
+        other => format!("{:?}", other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::InputBuffer;
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn test_translate_game_keys() {
+        let cases = [
+            (KeyCode::Up, Some(InputCommand::Turn(Direction::Up))),
+            (KeyCode::Down, Some(InputCommand::Turn(Direction::Down))),
+            (KeyCode::Left, Some(InputCommand::Turn(Direction::Left))),
+            (KeyCode::Right, Some(InputCommand::Turn(Direction::Right))),
+            (KeyCode::Char('w'), Some(InputCommand::Turn(Direction::Up))),
+            (
+                KeyCode::Char('s'),
+                Some(InputCommand::Turn(Direction::Down)),
+            ),
+            (
+                KeyCode::Char('a'),
+                Some(InputCommand::Turn(Direction::Left)),
+            ),
+            (
+                KeyCode::Char('d'),
+                Some(InputCommand::Turn(Direction::Right)),
+            ),
+            (KeyCode::Esc, Some(InputCommand::Quit)),
+            (KeyCode::Char('p'), Some(InputCommand::Pause)),
+            (KeyCode::Char('u'), Some(InputCommand::Undo)),
+            (KeyCode::Char('t'), Some(InputCommand::CycleTheme)),
+            (KeyCode::F(3), Some(InputCommand::ToggleFps)),
+            (KeyCode::Char('h'), Some(InputCommand::TogglePanel)),
+            (KeyCode::Char(' '), Some(InputCommand::Boost(true))),
+            (KeyCode::Char('x'), Some(InputCommand::Raw('x'))),
+            (
+                KeyCode::Tab,
+                Some(InputCommand::MenuNav(MenuNav::NextField)),
+            ),
+            (
+                KeyCode::Enter,
+                Some(InputCommand::MenuNav(MenuNav::Confirm)),
+            ),
+            (
+                KeyCode::Backspace,
+                Some(InputCommand::MenuNav(MenuNav::Erase)),
+            ),
+            (KeyCode::F(1), None),
+            (KeyCode::Home, None),
+        ];
+        for (code, expected) in cases {
+            assert_eq!(translate(key(code), false), expected, "{:?}", code);
+        }
+    }
+
+    #[test]
+    fn test_typing_takes_letters_literally() {
+        for c in ['w', 'a', 's', 'd', 'p', 'u', 't', 'x', 'ñ', ' '] {
+            assert_eq!(
+                translate(key(KeyCode::Char(c)), true),
+                Some(InputCommand::Raw(c))
+            );
+        }
+        // Navigation still works in text fields
+        assert_eq!(
+            translate(key(KeyCode::Left), true),
+            Some(InputCommand::Turn(Direction::Left))
+        );
+        assert_eq!(translate(key(KeyCode::Esc), true), Some(InputCommand::Quit));
+    }
+
+    #[test]
+    fn test_ctrl_c_and_releases() {
+        let ctrl_c = KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL);
+        assert_eq!(translate(ctrl_c, false), Some(InputCommand::Quit));
+        assert_eq!(translate(ctrl_c, true), Some(InputCommand::Quit));
+
+        let mut release = key(KeyCode::Up);
+        release.kind = KeyEventKind::Release;
+        assert_eq!(translate(release, false), None);
+
+        // Letting go of the boost key stops the boost
+        let mut release = key(KeyCode::Char(' '));
+        release.kind = KeyEventKind::Release;
+        assert_eq!(translate(release, false), Some(InputCommand::Boost(false)));
+        assert_eq!(translate(release, true), None);
+    }
+
+    #[test]
+    fn test_rebinding_keys() {
+        let mut bindings = KeyBindings::default();
+        for spec in [
+            "up=i",
+            "left=j",
+            "down=k",
+            "right=l",
+            "boost=b",
+            "pause=space",
+            "quit=q,esc",
+        ] {
+            bindings.bind(spec).unwrap();
+        }
+        bindings.validate().unwrap();
+
+        let turn = |c| bindings.translate(key(KeyCode::Char(c)), false);
+        assert_eq!(turn('i'), Some(InputCommand::Turn(Direction::Up)));
+        assert_eq!(turn('j'), Some(InputCommand::Turn(Direction::Left)));
+        assert_eq!(turn(' '), Some(InputCommand::Pause));
+        assert_eq!(turn('b'), Some(InputCommand::Boost(true)));
+        assert_eq!(turn('w'), Some(InputCommand::Raw('w')));
+        assert_eq!(
+            bindings.translate(key(KeyCode::Esc), false),
+            Some(InputCommand::Quit)
+        );
+        // Replaced keys no longer turn
+        assert_eq!(bindings.translate(key(KeyCode::Up), false), None);
+        assert_eq!(bindings.label(InputCommand::Quit), "q");
+        assert_eq!(bindings.label(InputCommand::Pause), "SPACE");
+        assert_eq!(bindings.label(InputCommand::Undo), "u");
+
+        // Reversing is still refused whatever key asked for it
+        let mut input = InputBuffer::new();
+        input.push_command(turn('j').unwrap());
+        assert_eq!(input.pop_valid(Direction::Right), None);
+    }
+
+    #[test]
+    fn test_wasd_reversal_is_refused() {
+        let bindings = KeyBindings::default();
+        let mut input = InputBuffer::new();
+        for c in ['a', 'd'] {
+            input.push_command(bindings.translate(key(KeyCode::Char(c)), false).unwrap());
+        }
+        // Moving right, 'a' is a reversal and 'd' keeps going
+        assert_eq!(input.pop_valid(Direction::Right), None);
+    }
+
+    #[test]
+    fn test_two_players_split_the_keys() {
+        let mut bindings = KeyBindings::default();
+        bindings.split_for_two_players();
+        bindings.validate().unwrap();
+        let turn = |c| bindings.translate(key(KeyCode::Char(c)), false);
+        assert_eq!(turn('w'), Some(InputCommand::SecondTurn(Direction::Up)));
+        assert_eq!(turn('d'), Some(InputCommand::SecondTurn(Direction::Right)));
+        assert_eq!(
+            bindings.translate(key(KeyCode::Up), false),
+            Some(InputCommand::Turn(Direction::Up))
+        );
+
+        // Keys of their own leave the first player WASD
+        let mut bindings = KeyBindings::default();
+        bindings.bind("p2-up=i").unwrap();
+        bindings.bind("p2-down=k").unwrap();
+        bindings.split_for_two_players();
+        bindings.validate().unwrap();
+        assert_eq!(bindings.up, vec![KeyCode::Up, KeyCode::Char('w')]);
+        assert_eq!(bindings.left, vec![KeyCode::Left]);
+    }
+
+    #[test]
+    fn test_invalid_bindings() {
+        let mut bindings = KeyBindings::default();
+        assert!(bindings.bind("up").is_err());
+        assert!(bindings.bind("jump=w").is_err());
+        assert!(bindings.bind("up=f13").is_err());
+
+        bindings.bind("up=p").unwrap();
+        assert_eq!(
+            bindings.validate(),
+            Err("key 'p' is bound to both up and pause".to_string())
+        );
+    }
+
+    #[test]
+    fn test_key_names() {
+        for name in ["w", "UP", "esc", "Space", "enter", "f3"] {
+            let code = parse_key(name).unwrap();
+            assert!(key_name(code).eq_ignore_ascii_case(name));
+        }
+    }
+}
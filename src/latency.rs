@@ -0,0 +1,239 @@
+use std::{
+    fmt::Write,
+    time::{Duration, Instant},
+};
+
+// The intervals a single frame is split into, measured from the moment the
+// key event is received until the terminal write returns
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Update,
+    Render,
+    Flush,
+    Total,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 4] = [Stage::Update, Stage::Render, Stage::Flush, Stage::Total];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Stage::Update => "event -> state",
+            Stage::Render => "state -> frame",
+            Stage::Flush => "frame -> flush",
+            Stage::Total => "event -> flush",
+        }
+    }
+
+    pub fn culprit(&self) -> &'static str {
+        match self {
+            Stage::Update => "slow input handling or game logic",
+            Stage::Render => "render cost, too many cells queued per frame",
+            Stage::Flush => "terminal or SSH throughput",
+            Stage::Total => "sum of the stages above",
+        }
+    }
+
+    fn index(&self) -> usize {
+        match self {
+            Stage::Update => 0,
+            Stage::Render => 1,
+            Stage::Flush => 2,
+            Stage::Total => 3,
+        }
+    }
+}
+
+// Timestamps for the checkpoints of one frame
+#[derive(Debug, Clone, Copy)]
+pub struct FrameTimer {
+    event_received: Instant,
+    state_applied: Option<Instant>,
+    frame_built: Option<Instant>,
+    flushed: Option<Instant>,
+}
+
+impl FrameTimer {
+    pub fn start(now: Instant) -> Self {
+        Self {
+            event_received: now,
+            state_applied: None,
+            frame_built: None,
+            flushed: None,
+        }
+    }
+
+    pub fn started(&self) -> Instant {
+        self.event_received
+    }
+
+    pub fn state_applied(&mut self, now: Instant) {
+        self.state_applied = Some(now);
+    }
+
+    pub fn frame_built(&mut self, now: Instant) {
+        self.frame_built = Some(now);
+    }
+
+    pub fn flushed(&mut self, now: Instant) {
+        self.flushed = Some(now);
+    }
+
+    // None until every checkpoint has been marked
+    pub fn duration(&self, stage: Stage) -> Option<Duration> {
+        let state_applied = self.state_applied?;
+        let frame_built = self.frame_built?;
+        let flushed = self.flushed?;
+        Some(match stage {
+            Stage::Update => state_applied.saturating_duration_since(self.event_received),
+            Stage::Render => frame_built.saturating_duration_since(state_applied),
+            Stage::Flush => flushed.saturating_duration_since(frame_built),
+            Stage::Total => flushed.saturating_duration_since(self.event_received),
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct LatencyStats {
+    samples: [Vec<Duration>; 4],
+}
+
+impl LatencyStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, timer: &FrameTimer) {
+        for stage in Stage::ALL {
+            if let Some(duration) = timer.duration(stage) {
+                self.samples[stage.index()].push(duration);
+            }
+        }
+    }
+
+    pub fn record_duration(&mut self, stage: Stage, duration: Duration) {
+        self.samples[stage.index()].push(duration);
+    }
+
+    pub fn count(&self, stage: Stage) -> usize {
+        self.samples[stage.index()].len()
+    }
+
+    // Nearest-rank percentile, `percentile` in 0..=100
+    pub fn percentile(&self, stage: Stage, percentile: u8) -> Option<Duration> {
+        let mut samples = self.samples[stage.index()].clone();
+        if samples.is_empty() {
+            return None;
+        }
+        samples.sort_unstable();
+        let rank = (percentile.min(100) as usize * samples.len()).div_ceil(100);
+        Some(samples[rank.saturating_sub(1)])
+    }
+
+    pub fn summary(&self) -> String {
+        let mut summary = String::new();
+        let _ = writeln!(
+            summary,
+            "{:<16} {:>8} {:>10} {:>10}",
+            "STAGE", "SAMPLES", "P50", "P95"
+        );
+        for stage in Stage::ALL {
+            let format = |duration: Option<Duration>| {
+                duration
+                    .map(|d| format!("{:.2}ms", d.as_secs_f64() * 1000.0))
+                    .unwrap_or_else(|| "-".to_string())
+            };
+            let _ = writeln!(
+                summary,
+                "{:<16} {:>8} {:>10} {:>10}",
+                stage.label(),
+                self.count(stage),
+                format(self.percentile(stage, 50)),
+                format(self.percentile(stage, 95))
+            );
+        }
+
+        let _ = writeln!(summary);
+        let _ = writeln!(summary, "If a stage dominates, the likely culprit is:");
+        for stage in [Stage::Update, Stage::Render, Stage::Flush] {
+            let _ = writeln!(summary, "  {:<16} {}", stage.label(), stage.culprit());
+        }
+        let _ = writeln!(
+            summary,
+            "If every stage is fast but the game still feels laggy, the delay happens\n\
+             before the key event arrives: the input thread, the terminal or SSH."
+        );
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_timer_splits_stages() {
+        let start = Instant::now();
+        let mut timer = FrameTimer::start(start);
+        assert_eq!(timer.duration(Stage::Total), None);
+
+        timer.state_applied(start + Duration::from_millis(1));
+        timer.frame_built(start + Duration::from_millis(3));
+        timer.flushed(start + Duration::from_millis(6));
+
+        assert_eq!(
+            timer.duration(Stage::Update),
+            Some(Duration::from_millis(1))
+        );
+        assert_eq!(
+            timer.duration(Stage::Render),
+            Some(Duration::from_millis(2))
+        );
+        assert_eq!(timer.duration(Stage::Flush), Some(Duration::from_millis(3)));
+        assert_eq!(timer.duration(Stage::Total), Some(Duration::from_millis(6)));
+    }
+
+    #[test]
+    fn test_percentiles() {
+        let mut stats = LatencyStats::new();
+        assert_eq!(stats.percentile(Stage::Flush, 50), None);
+
+        for ms in (1..=100).rev() {
+            stats.record_duration(Stage::Flush, Duration::from_millis(ms));
+        }
+
+        assert_eq!(stats.count(Stage::Flush), 100);
+        assert_eq!(
+            stats.percentile(Stage::Flush, 50),
+            Some(Duration::from_millis(50))
+        );
+        assert_eq!(
+            stats.percentile(Stage::Flush, 95),
+            Some(Duration::from_millis(95))
+        );
+        assert_eq!(
+            stats.percentile(Stage::Flush, 100),
+            Some(Duration::from_millis(100))
+        );
+        assert_eq!(
+            stats.percentile(Stage::Flush, 0),
+            Some(Duration::from_millis(1))
+        );
+    }
+
+    #[test]
+    fn test_incomplete_frames_are_not_recorded() {
+        let mut stats = LatencyStats::new();
+        let mut timer = FrameTimer::start(Instant::now());
+        timer.state_applied(Instant::now());
+        stats.record(&timer);
+        assert_eq!(stats.count(Stage::Total), 0);
+
+        timer.frame_built(Instant::now());
+        timer.flushed(Instant::now());
+        stats.record(&timer);
+        for stage in Stage::ALL {
+            assert_eq!(stats.count(stage), 1);
+        }
+    }
+}
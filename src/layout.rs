@@ -0,0 +1,118 @@
+use crate::game::MIN_BOARD_SIZE;
+
+// The side panel starts two columns after the board and its right border sits
+// `panel_width` columns after that, so the last drawn column is
+// `game_width + 2 + panel_width`. Saturates, as no terminal is that big anyway.
+pub fn required_size(game_width: u16, panel_width: u16, height: u16) -> (u16, u16) {
+    (
+        game_width.saturating_add(panel_width).saturating_add(3),
+        height,
+    )
+}
+
+// Lines the status bar takes under the board, in place of the side panel
+pub const STATUS_ROWS: u16 = 2;
+
+// The board on its own with the status bar below it
+pub fn required_size_below(game_width: u16, height: u16) -> (u16, u16) {
+    (game_width, height.saturating_add(STATUS_ROWS))
+}
+
+pub fn fits(required: (u16, u16), terminal: (u16, u16)) -> bool {
+    required.0 <= terminal.0 && required.1 <= terminal.1
+}
+
+// Shrinks the board so board and panel fit the terminal, or None if not even
+// the smallest board does
+pub fn auto_fit(
+    game_width: u16,
+    panel_width: u16,
+    height: u16,
+    terminal: (u16, u16),
+) -> Option<(u16, u16)> {
+    let (required_width, _) = required_size(0, panel_width, 0);
+    let width = game_width.min(terminal.0.checked_sub(required_width)?);
+    let height = height.min(terminal.1);
+    if width < MIN_BOARD_SIZE || height < MIN_BOARD_SIZE {
+        return None;
+    }
+    Some((width, height))
+}
+
+// Shrinks the board so it and the status bar under it fit the terminal, or
+// None if not even the smallest board does
+pub fn fit_below(game_width: u16, height: u16, terminal: (u16, u16)) -> Option<(u16, u16)> {
+    let width = game_width.min(terminal.0);
+    let height = height.min(terminal.1.checked_sub(STATUS_ROWS)?);
+    if width < MIN_BOARD_SIZE || height < MIN_BOARD_SIZE {
+        return None;
+    }
+    Some((width, height))
+}
+
+// The largest board that leaves room for the side panel, or None if the
+// terminal cannot hold even the smallest board next to it
+pub fn fill(panel_width: u16, terminal: (u16, u16)) -> Option<(u16, u16)> {
+    auto_fit(u16::MAX, panel_width, u16::MAX, terminal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_required_size_includes_panel_right_border() {
+        assert_eq!(required_size(30, 20, 15), (53, 15));
+        assert!(fits((53, 15), (53, 15)));
+        assert!(!fits((53, 15), (52, 15)));
+        assert!(!fits((53, 15), (53, 14)));
+
+        // Never wraps round to something that fits
+        assert_eq!(required_size(u16::MAX, 20, 15), (u16::MAX, 15));
+        assert!(!fits(required_size(u16::MAX, 20, 15), (200, 60)));
+        assert_eq!(required_size_below(30, u16::MAX), (30, u16::MAX));
+    }
+
+    #[test]
+    fn test_auto_fit() {
+        // Already fits, nothing changes
+        assert_eq!(auto_fit(30, 20, 15, (80, 24)), Some((30, 15)));
+        // Exactly one column short
+        assert_eq!(auto_fit(30, 20, 15, (52, 15)), Some((29, 15)));
+        assert_eq!(auto_fit(30, 20, 15, (40, 12)), Some((17, 12)));
+
+        let (width, height) = auto_fit(30, 20, 15, (40, 12)).unwrap();
+        assert!(fits(required_size(width, 20, height), (40, 12)));
+    }
+
+    #[test]
+    fn test_auto_fit_too_small() {
+        assert_eq!(auto_fit(30, 20, 15, (27, 15)), None);
+        assert_eq!(auto_fit(30, 20, 15, (10, 15)), None);
+        assert_eq!(auto_fit(30, 20, 15, (80, 4)), None);
+        assert_eq!(auto_fit(30, 20, 15, (28, 5)), Some((5, 5)));
+    }
+
+    #[test]
+    fn test_status_bar_takes_rows_instead_of_columns() {
+        // Too narrow for the panel, but the board and two more rows fit
+        assert!(!fits(required_size(30, 20, 15), (40, 24)));
+        assert!(fits(required_size_below(30, 15), (40, 24)));
+        assert!(!fits(required_size_below(30, 15), (40, 16)));
+
+        assert_eq!(fit_below(30, 15, (40, 24)), Some((30, 15)));
+        assert_eq!(fit_below(30, 15, (20, 12)), Some((20, 10)));
+        assert_eq!(fit_below(30, 15, (20, 6)), None);
+        assert_eq!(fit_below(30, 15, (4, 24)), None);
+    }
+
+    #[test]
+    fn test_fill() {
+        assert_eq!(fill(20, (80, 24)), Some((57, 24)));
+        assert!(fits(required_size(57, 20, 24), (80, 24)));
+        assert!(!fits(required_size(58, 20, 24), (80, 24)));
+        assert_eq!(fill(20, (28, 5)), Some((5, 5)));
+        assert_eq!(fill(20, (27, 24)), None);
+        assert_eq!(fill(20, (80, 4)), None);
+    }
+}
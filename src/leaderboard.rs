@@ -0,0 +1,249 @@
+use crate::{difficulty::Difficulty, game::GameMode, score};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+// Places on the board, kept per difficulty and mode like the high score
+pub const SIZE: usize = 10;
+const LEADERBOARD_FILE: &str = "leaderboard";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub name: String,
+    pub score: u32,
+    // How long the game lasted, not known for entries saved before it was
+    pub time: Option<Duration>,
+    // YYYY-MM-DD, in UTC
+    pub date: String,
+    // Brought in from someone's run card rather than played here
+    pub imported: bool,
+}
+
+impl Entry {
+    // Dated today. Line breaks would split the entry over two lines of the
+    // file, so they become spaces.
+    pub fn new(name: &str, score: u32, time: Duration) -> Self {
+        Self {
+            name: name.trim().replace(['\n', '\r'], " "),
+            score,
+            // Whole seconds, as saved
+            time: Some(Duration::from_secs(time.as_secs())),
+            date: today(),
+            imported: false,
+        }
+    }
+
+    pub fn imported(name: &str, score: u32, time: Duration) -> Self {
+        Self {
+            imported: true,
+            ..Self::new(name, score, time)
+        }
+    }
+
+    // `score/seconds date name`, the name last as it may have spaces in it.
+    // Entries without a time are plain `score date name`, and imported ones
+    // end the first field in `*`.
+    fn to_line(&self) -> String {
+        let time = self
+            .time
+            .map_or(String::new(), |time| format!("/{}", time.as_secs()));
+        let imported = if self.imported { "*" } else { "" };
+        format!(
+            "{}{}{} {} {}",
+            self.score, time, imported, self.date, self.name
+        )
+    }
+
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.splitn(3, ' ');
+        let first = fields.next()?;
+        let (first, imported) = match first.strip_suffix('*') {
+            Some(first) => (first, true),
+            None => (first, false),
+        };
+        let (score, time) = match first.split_once('/') {
+            Some((score, seconds)) => (score, Some(Duration::from_secs(seconds.parse().ok()?))),
+            None => (first, None),
+        };
+        let score = score.parse().ok()?;
+        let date = fields.next().filter(|date| is_date(date))?;
+        let name = fields.next().unwrap_or_default();
+        Some(Self {
+            name: name.to_string(),
+            score,
+            time,
+            date: date.to_string(),
+            imported,
+        })
+    }
+}
+
+pub fn load(difficulty: Difficulty, mode: GameMode) -> Vec<Entry> {
+    load_from(&path(difficulty, mode))
+}
+
+pub fn save(difficulty: Difficulty, mode: GameMode, entries: &[Entry]) -> io::Result<()> {
+    save_to(&path(difficulty, mode), entries)
+}
+
+fn path(difficulty: Difficulty, mode: GameMode) -> PathBuf {
+    score::data_dir().join(score::data_file(LEADERBOARD_FILE, difficulty, mode))
+}
+
+// A missing file is an empty board. Lines that don't read as an entry are
+// left out rather than losing the whole board to one bad line.
+pub fn load_from(path: &Path) -> Vec<Entry> {
+    let contents = fs::read_to_string(path).unwrap_or_default();
+    let mut entries: Vec<Entry> = contents.lines().filter_map(Entry::parse).collect();
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.score));
+    entries.truncate(SIZE);
+    entries
+}
+
+pub fn save_to(path: &Path, entries: &[Entry]) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let lines: String = entries.iter().map(|entry| entry.to_line() + "\n").collect();
+    fs::write(path, lines)
+}
+
+// Where a score would go on the board, from 0, below the ones it ties with.
+// None when it doesn't make it, or is no score at all.
+pub fn place(entries: &[Entry], score: u32) -> Option<usize> {
+    let place = entries
+        .iter()
+        .position(|entry| score > entry.score)
+        .unwrap_or(entries.len());
+    (score > 0 && place < SIZE).then_some(place)
+}
+
+// Returns the place the entry went to, if it made it
+pub fn submit(entries: &mut Vec<Entry>, entry: Entry) -> Option<usize> {
+    let place = place(entries, entry.score)?;
+    entries.insert(place, entry);
+    entries.truncate(SIZE);
+    Some(place)
+}
+
+pub fn today() -> String {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    let (year, month, day) = civil_date(seconds / 86_400);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+// The calendar date `days` after 1970-01-01, after Howard Hinnant's
+// `civil_from_days`
+fn civil_date(days: u64) -> (u64, u64, u64) {
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+fn is_date(text: &str) -> bool {
+    let bytes = text.as_bytes();
+    bytes.len() == 10
+        && bytes.iter().enumerate().all(|(i, byte)| match i {
+            4 | 7 => *byte == b'-',
+            _ => byte.is_ascii_digit(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn entry(name: &str, score: u32) -> Entry {
+        Entry {
+            name: name.to_string(),
+            score,
+            time: Some(Duration::from_secs(score as u64 * 3)),
+            date: "2026-10-17".to_string(),
+            imported: false,
+        }
+    }
+
+    #[test]
+    fn test_submit_keeps_the_best_ten() {
+        let mut entries = Vec::new();
+        for score in 1..=SIZE as u32 {
+            assert_eq!(place(&entries, score * 10), Some(0));
+            submit(&mut entries, entry("a", score * 10));
+        }
+        assert_eq!(entries.first().unwrap().score, 100);
+        assert_eq!(entries.last().unwrap().score, 10);
+
+        assert_eq!(place(&entries, 10), None);
+        assert_eq!(submit(&mut entries, entry("late", 10)), None);
+        assert_eq!(place(&entries, 11), Some(9));
+        // Below the earlier 50, which was there first
+        assert_eq!(submit(&mut entries, entry("tie", 50)), Some(6));
+        assert_eq!(entries.len(), SIZE);
+        assert_eq!(entries[5].name, "a");
+        assert_eq!(entries[6].name, "tie");
+        assert_eq!(entries.last().unwrap().score, 20);
+
+        assert_eq!(place(&[], 0), None);
+    }
+
+    #[test]
+    fn test_leaderboard_round_trip_skips_bad_lines() {
+        let path = env::temp_dir()
+            .join(format!("rust-snake-test-{}", std::process::id()))
+            .join("leaderboard");
+        let entries = vec![
+            entry("Ana María", 30),
+            entry("", 12),
+            Entry {
+                imported: true,
+                ..entry("Bo", 9)
+            },
+            Entry::imported("Cy", 4, Duration::from_secs(3)),
+        ];
+        save_to(&path, &entries).unwrap();
+        assert_eq!(load_from(&path), entries);
+
+        let damaged = "12 2026-10-17 Bo\nnot a line\n40 yesterday Cy\n90/75 2026-10-16 Di\n\
+                       50/soon 2026-10-16 Ed\n";
+        fs::write(&path, damaged).unwrap();
+        let loaded = load_from(&path);
+        let names: Vec<&str> = loaded.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["Di", "Bo"]);
+        // Lines from before times were kept still load, without one
+        assert_eq!(loaded[0].time, Some(Duration::from_secs(75)));
+        assert_eq!(loaded[1].time, None);
+        fs::remove_file(&path).unwrap();
+
+        assert!(load_from(&path).is_empty());
+    }
+
+    #[test]
+    fn test_entries_are_dated_and_kept_on_one_line() {
+        assert_eq!(civil_date(0), (1970, 1, 1));
+        assert_eq!(civil_date(11_016), (2000, 2, 29));
+        assert_eq!(civil_date(20_743), (2026, 10, 17));
+        assert!(is_date(&today()));
+
+        let entry = Entry::new(" two\nlines ", 5, Duration::from_millis(61_900));
+        assert_eq!(entry.name, "two lines");
+        assert!(entry.to_line().starts_with("5/61 "));
+        assert_eq!(Entry::parse(&entry.to_line()), Some(entry));
+    }
+}
@@ -0,0 +1,399 @@
+use crate::game::{ObstacleLayout, Position, MIN_BOARD_SIZE};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{fmt, fs, io, path::Path};
+
+// Share of the interior random levels try to fill with walls
+pub const DEFAULT_DENSITY: f64 = 0.15;
+pub const MAX_DENSITY: f64 = 0.4;
+// Cells kept clear in front of where the snake starts, so it has time to turn
+const CLEAR_AHEAD: u16 = 3;
+const MAX_WALL_LENGTH: u16 = 4;
+
+// A level drawn as text, one character per cell of the whole board:
+//
+//   ##########
+//   #........#
+//   #..S..F..#
+//   #...##...#
+//   ##########
+//
+// `#` is a wall, `.` is empty, `S` is where the snake starts and `F` is a
+// food that is there from the start. The outer ring is the board border and
+// has to be all walls, `#` inside it become obstacles.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Level {
+    pub width: u16,
+    pub height: u16,
+    pub obstacles: Vec<Position>,
+    pub snake_start: Position,
+    // Without any the game places a random one
+    pub foods: Vec<Position>,
+}
+
+// What the inside of the board looks like when no map is given
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Arena {
+    #[default]
+    Open,
+    Obstacles(ObstacleLayout),
+    Random {
+        density: f64,
+    },
+}
+
+impl Arena {
+    // The menu's choices, with `current` standing in for its own kind
+    pub fn choices(current: Arena) -> Vec<Arena> {
+        let random = match current {
+            Arena::Random { .. } => current,
+            _ => Arena::Random {
+                density: DEFAULT_DENSITY,
+            },
+        };
+        vec![
+            Arena::Open,
+            Arena::Obstacles(ObstacleLayout::CentralBar),
+            Arena::Obstacles(ObstacleLayout::CornerBlocks),
+            random,
+        ]
+    }
+
+    pub fn level(self, width: u16, height: u16, seed: u64) -> Option<Level> {
+        match self {
+            Arena::Open => None,
+            Arena::Obstacles(layout) => Some(Level::from_layout(layout, width, height)),
+            Arena::Random { density } => Some(Level::random(width, height, seed, density)),
+        }
+    }
+}
+
+impl fmt::Display for Arena {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Arena::Open => write!(f, "Open"),
+            Arena::Obstacles(ObstacleLayout::CentralBar) => write!(f, "Bar"),
+            Arena::Obstacles(ObstacleLayout::CornerBlocks) => write!(f, "Corners"),
+            Arena::Random { .. } => write!(f, "Random"),
+        }
+    }
+}
+
+pub fn load_map(path: &Path) -> io::Result<Level> {
+    parse_map(&fs::read_to_string(path)?)
+}
+
+pub fn parse_map(text: &str) -> io::Result<Level> {
+    let rows: Vec<Vec<char>> = text
+        .trim_end()
+        .lines()
+        .map(|line| line.trim_end_matches('\r').chars().collect())
+        .collect();
+
+    let width = rows.first().map_or(0, |row| row.len());
+    for (y, row) in rows.iter().enumerate() {
+        if row.len() != width {
+            return Err(invalid(format!(
+                "map is not rectangular: row {} is {} wide, row 1 is {}",
+                y + 1,
+                row.len(),
+                width
+            )));
+        }
+    }
+    let (width, height) = (width as u16, rows.len() as u16);
+    if width < MIN_BOARD_SIZE || height < MIN_BOARD_SIZE {
+        return Err(invalid(format!(
+            "map must be at least {}x{}, got {}x{}",
+            MIN_BOARD_SIZE, MIN_BOARD_SIZE, width, height
+        )));
+    }
+
+    let mut obstacles = Vec::new();
+    let mut starts = Vec::new();
+    let mut foods = Vec::new();
+    for (y, row) in rows.iter().enumerate() {
+        for (x, &cell) in row.iter().enumerate() {
+            let position = Position::new(x as u16, y as u16);
+            if position.is_on_border(width, height) {
+                if cell != '#' {
+                    return Err(invalid(format!(
+                        "map border must be walls, found '{}' at {},{}",
+                        cell, x, y
+                    )));
+                }
+                continue;
+            }
+            match cell {
+                '#' => obstacles.push(position),
+                '.' => {}
+                'S' => starts.push(position),
+                'F' => foods.push(position),
+                _ => {
+                    return Err(invalid(format!(
+                        "unknown map cell '{}' at {},{}",
+                        cell, x, y
+                    )))
+                }
+            }
+        }
+    }
+
+    let snake_start = match starts[..] {
+        [start] => start,
+        [] => return Err(invalid("map has no snake start 'S'".to_string())),
+        _ => {
+            return Err(invalid(format!(
+                "map has {} snake starts 'S', expected exactly one",
+                starts.len()
+            )))
+        }
+    };
+
+    Ok(Level {
+        width,
+        height,
+        obstacles,
+        snake_start,
+        foods,
+    })
+}
+
+impl Level {
+    // A built-in layout on an otherwise empty board, the snake starts in the
+    // middle as usual
+    pub fn from_layout(layout: ObstacleLayout, width: u16, height: u16) -> Self {
+        let snake_start = Position::new(width / 2, height / 2);
+        let mut obstacles = Vec::new();
+        for position in layout.positions(width, height) {
+            let inside = position.x > 0
+                && position.y > 0
+                && position.x < width - 1
+                && position.y < height - 1;
+            if inside && position != snake_start && !obstacles.contains(&position) {
+                obstacles.push(position);
+            }
+        }
+        // In reading order, like `parse_map` finds them
+        obstacles.sort_by_key(|position| (position.y, position.x));
+        Self {
+            width,
+            height,
+            obstacles,
+            snake_start,
+            foods: Vec::new(),
+        }
+    }
+
+    // Short wall segments scattered around, each one only kept if every free
+    // cell can still be reached from the start. The same seed always builds
+    // the same level.
+    pub fn random(width: u16, height: u16, seed: u64, density: f64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let snake_start = Position::new(width / 2, height / 2);
+        let mut level = Self {
+            width,
+            height,
+            obstacles: Vec::new(),
+            snake_start,
+            foods: Vec::new(),
+        };
+
+        let inside = |p: &Position| p.x > 0 && p.y > 0 && p.x < width - 1 && p.y < height - 1;
+        let clear: Vec<Position> = (0..=CLEAR_AHEAD)
+            .map(|dx| Position::new(snake_start.x + dx, snake_start.y))
+            .collect();
+        let interior = (width - 2) as usize * (height - 2) as usize;
+        let target = (interior as f64 * density.clamp(0.0, MAX_DENSITY)) as usize;
+
+        // Walls that would cut the board in two are thrown away, so give up
+        // after a while on crowded boards
+        for _ in 0..target * 8 {
+            if level.obstacles.len() >= target {
+                break;
+            }
+            let horizontal = rng.gen_bool(0.5);
+            let length = rng.gen_range(1..=MAX_WALL_LENGTH);
+            let x = rng.gen_range(1..width - 1);
+            let y = rng.gen_range(1..height - 1);
+            let wall: Vec<Position> = (0..length)
+                .map(|i| {
+                    if horizontal {
+                        Position::new(x + i, y)
+                    } else {
+                        Position::new(x, y + i)
+                    }
+                })
+                .filter(inside)
+                .collect();
+            if wall
+                .iter()
+                .any(|cell| clear.contains(cell) || level.obstacles.contains(cell))
+            {
+                continue;
+            }
+            level.obstacles.extend(&wall);
+            if !level.is_connected() {
+                level.obstacles.truncate(level.obstacles.len() - wall.len());
+            }
+        }
+
+        level
+            .obstacles
+            .sort_by_key(|position| (position.y, position.x));
+        level
+    }
+
+    // Every interior cell without a wall can be reached from the snake start
+    pub fn is_connected(&self) -> bool {
+        let (width, height) = (self.width as usize, self.height as usize);
+        let index = |p: Position| p.y as usize * width + p.x as usize;
+        let mut blocked = vec![false; width * height];
+        for position in &self.obstacles {
+            blocked[index(*position)] = true;
+        }
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let position = Position::new(x, y);
+                if position.is_on_border(self.width, self.height) {
+                    blocked[index(position)] = true;
+                }
+            }
+        }
+
+        // Flood fill from the start, anything left unmarked is sealed off
+        let mut stack = vec![self.snake_start];
+        blocked[index(self.snake_start)] = true;
+        while let Some(position) = stack.pop() {
+            let neighbours = [
+                Position::new(position.x - 1, position.y),
+                Position::new(position.x + 1, position.y),
+                Position::new(position.x, position.y - 1),
+                Position::new(position.x, position.y + 1),
+            ];
+            for neighbour in neighbours {
+                if !blocked[index(neighbour)] {
+                    blocked[index(neighbour)] = true;
+                    stack.push(neighbour);
+                }
+            }
+        }
+        !blocked.contains(&false)
+    }
+
+    // Back to the text `parse_map` reads
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let position = Position::new(x, y);
+                text.push(if position == self.snake_start {
+                    'S'
+                } else if position.is_on_border(self.width, self.height)
+                    || self.obstacles.contains(&position)
+                {
+                    '#'
+                } else if self.foods.contains(&position) {
+                    'F'
+                } else {
+                    '.'
+                });
+            }
+            text.push('\n');
+        }
+        text
+    }
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAP: &str = "\
+########
+#......#
+#.S.#F.#
+#...#..#
+########
+";
+
+    #[test]
+    fn test_parse_map() {
+        let level = parse_map(MAP).unwrap();
+        assert_eq!((level.width, level.height), (8, 5));
+        assert_eq!(level.snake_start, Position::new(2, 2));
+        assert_eq!(level.foods, vec![Position::new(5, 2)]);
+        assert_eq!(
+            level.obstacles,
+            vec![Position::new(4, 2), Position::new(4, 3)]
+        );
+        assert_eq!(level.to_text(), MAP);
+
+        // Windows line endings and a missing final newline are fine
+        let crlf = MAP.trim_end().replace('\n', "\r\n");
+        assert_eq!(parse_map(&crlf).unwrap(), level);
+    }
+
+    #[test]
+    fn test_layouts_become_levels() {
+        for layout in [ObstacleLayout::CentralBar, ObstacleLayout::CornerBlocks] {
+            let level = Level::from_layout(layout, 30, 15);
+            assert!(!level.obstacles.is_empty());
+            // Round trips through the map format, which is how replays keep it
+            assert_eq!(parse_map(&level.to_text()).unwrap(), level);
+        }
+        // Tiny boards drop the cells that would land on the border
+        let level = Level::from_layout(ObstacleLayout::CornerBlocks, 5, 5);
+        assert_eq!(parse_map(&level.to_text()).unwrap(), level);
+    }
+
+    #[test]
+    fn test_random_levels_stay_connected() {
+        for seed in 0..40 {
+            for (width, height) in [(30, 15), (12, 8), (5, 5)] {
+                let level = Level::random(width, height, seed, MAX_DENSITY);
+                assert!(level.is_connected(), "seed {} {}x{}", seed, width, height);
+
+                // The snake starts facing right with room to react
+                for dx in 0..=CLEAR_AHEAD {
+                    let ahead = Position::new(level.snake_start.x + dx, level.snake_start.y);
+                    assert!(!level.obstacles.contains(&ahead));
+                }
+                assert_eq!(parse_map(&level.to_text()).unwrap(), level);
+            }
+        }
+    }
+
+    #[test]
+    fn test_random_levels_follow_the_seed() {
+        let level = Level::random(30, 15, 7, DEFAULT_DENSITY);
+        assert_eq!(Level::random(30, 15, 7, DEFAULT_DENSITY), level);
+        assert_ne!(Level::random(30, 15, 8, DEFAULT_DENSITY), level);
+        assert!(level.obstacles.len() > 28 * 13 / 10);
+
+        assert!(Level::random(30, 15, 7, 0.0).obstacles.is_empty());
+    }
+
+    #[test]
+    fn test_sealed_pockets_are_detected() {
+        let level = parse_map("#######\n#S.#..#\n#..#..#\n#..#..#\n#######").unwrap();
+        assert!(!level.is_connected());
+        assert!(parse_map(MAP).unwrap().is_connected());
+    }
+
+    #[test]
+    fn test_invalid_maps_are_explained() {
+        let error = |map: &str| parse_map(map).unwrap_err().to_string();
+
+        assert!(error("#####\n#.S.#\n#...##\n#...#\n#####").contains("not rectangular"));
+        assert!(error("#####\n#...#\n#...#\n#...#\n#####").contains("no snake start"));
+        assert!(error("#####\n#S.S#\n#...#\n#...#\n#####").contains("2 snake starts"));
+        assert!(error("#####\n#.S.#\n#.x.#\n#...#\n#####").contains("unknown map cell 'x'"));
+        assert!(error("#####\n..S.#\n#...#\n#...#\n#####").contains("border"));
+        assert!(error("####\n#S.#\n####").contains("at least"));
+        assert!(error("").contains("at least"));
+    }
+}
@@ -1,3 +1,39 @@
+// The game engine, free of any terminal code so tests, benches and other
+// frontends can drive it
+pub mod animation;
+pub mod controller;
+pub mod difficulty;
+pub mod framerate;
 pub mod game;
+pub mod headless;
+pub mod input;
+pub mod latency;
+pub mod layout;
+pub mod leaderboard;
+pub mod level;
+pub mod relay;
+pub mod replay;
+pub mod rival;
+pub mod runcard;
+pub mod score;
+pub mod sound;
+pub mod speed;
+
+// What a frontend needs to run a game, without reaching into the modules
+pub use game::{Action, Direction, Food, GameState, Position, Snake};
+
+// The crossterm frontend
+#[cfg(feature = "terminal")]
+pub mod cli;
+#[cfg(feature = "terminal")]
+pub mod commentary;
+#[cfg(feature = "terminal")]
+pub mod keyboard;
+#[cfg(feature = "terminal")]
 pub mod menu;
+#[cfg(feature = "terminal")]
+pub mod render;
+#[cfg(all(test, feature = "terminal"))]
+mod screen;
+#[cfg(feature = "terminal")]
 pub mod theme;
@@ -1,28 +1,573 @@
 use crossterm::{
     cursor,
-    event::{self, Event, KeyCode},
-    execute, terminal,
+    event::{self, Event, KeyboardEnhancementFlags},
+    execute, queue,
+    style::{self, Stylize},
+    terminal,
 };
 use rust_snake::{
-    game::{Action, GameGrid, GameState},
+    cli::{self, Args, ColorChoice, Command},
+    commentary::Commentator,
+    controller::{AutoController, Controller, KeyboardController},
+    difficulty::Difficulty,
+    framerate::FrameRate,
+    game::{self, GameEventKind, GameState, PLAYER, RIVAL},
+    headless,
+    input::{self, DrainStats, InputCommand},
+    keyboard::{self, KeyBindings},
+    latency::{FrameTimer, LatencyStats, Stage},
+    layout,
+    leaderboard::{self, Entry},
+    level::{self, Level},
     menu,
-    menu::SidePanel,
+    menu::{MenuOutcome, MenuResult, SidePanel, StatusBar},
+    relay::Relay,
+    render::{self, Capabilities, Renderer},
+    replay::Replay,
+    runcard::RunCard,
+    score,
+    sound::{self, Cue},
+    speed::Boost,
+    theme::{self, ColorSupport, GlyphSet, Theme},
 };
 use std::{
+    collections::VecDeque,
     io::Write,
-    thread,
+    iter, thread,
     time::{Duration, Instant},
 };
 
-const GAME_WIDTH: u16 = 30;
-const PANEL_WIDTH: u16 = 20;
-const HEIGHT: u16 = 15;
-const FRAME_DURATION: Duration = Duration::from_millis(75); // ~13 FPS
+const LAST_REPLAY_FILE: &str = "last.replay";
+// Input is polled and the screen redrawn this often, whatever the snake speed
+const RENDER_INTERVAL: Duration = Duration::from_micros(16_667); // ~60 FPS
+
+// How long the game holds still after losing a life
+const RESPAWN_PAUSE: Duration = Duration::from_millis(1000);
 
 fn main() -> std::io::Result<()> {
+    let mut args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("rust-snake: {}", message);
+            eprintln!("Try 'rust-snake --help' for more information.");
+            std::process::exit(2);
+        }
+    };
+    if args.help {
+        println!("{}", cli::USAGE);
+        return Ok(());
+    }
+
+    if let Some(command) = &args.command {
+        if let Err(err) = run_command(command) {
+            eprintln!("rust-snake: {}", err);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     let mut stdout = std::io::stdout();
-    if let Some(player_name) = menu::show(&mut stdout, GAME_WIDTH, PANEL_WIDTH, HEIGHT)? {
-        run_game(&mut stdout, player_name)?;
+    if args.measure_latency {
+        let glyphs = if ascii_only(&args) {
+            &GlyphSet::ASCII
+        } else {
+            &GlyphSet::UNICODE
+        };
+        let stats = measure_latency(&mut stdout, glyphs)?;
+        println!("{}", stats.summary());
+        return Ok(());
+    }
+
+    let replay = match args.replay.as_deref().map(Replay::load).transpose() {
+        Ok(replay) => replay,
+        Err(err) => {
+            eprintln!("rust-snake: could not load replay: {}", err);
+            std::process::exit(1);
+        }
+    };
+
+    let colors = color_support(&args);
+    let mut theme = match args.theme_file.as_deref().map(theme::load_theme) {
+        Some(Ok(theme)) => theme,
+        Some(Err(err)) => {
+            eprintln!("rust-snake: could not load theme: {}", err);
+            std::process::exit(1);
+        }
+        None if args.colorblind => Theme::COLORBLIND,
+        // A theme picked on purpose is brought down to the colors the
+        // terminal can show, otherwise 16 colors get one picked for them
+        None if colors == ColorSupport::Ansi16 => Theme::ansi_fallback(),
+        None => Theme::default(),
+    }
+    .with_colors(colors);
+    if ascii_only(&args) {
+        theme.glyphs = &GlyphSet::ASCII;
+    }
+
+    let level = match args.map.as_deref().map(level::load_map).transpose() {
+        Ok(level) => level,
+        Err(err) => {
+            eprintln!("rust-snake: could not load map: {}", err);
+            std::process::exit(1);
+        }
+    };
+    if let Some(level) = &level {
+        (args.width, args.height) = (level.width, level.height);
+    }
+
+    if let Some(games) = args.headless {
+        // The rival's bot at its sharpest, each game on the seed after the last
+        let mut controller = AutoController::new(1);
+        let first_seed = args.seed.unwrap_or_else(rand::random);
+        let summary = headless::simulate(
+            games,
+            |game| {
+                let seed = first_seed.wrapping_add(u64::from(game));
+                let level = level
+                    .clone()
+                    .or_else(|| args.arena.level(args.width, args.height, seed));
+                args.new_game(level, seed)
+            },
+            &mut controller,
+            headless::MAX_TICKS,
+        );
+        if args.json {
+            println!("{}", summary.json());
+        } else {
+            print!("{}", summary.table());
+        }
+        return Ok(());
+    }
+
+    let board = replay.as_ref().map_or((args.width, args.height), |replay| {
+        (replay.width, replay.height)
+    });
+    // A replay or a map has to be played on its own board, so it never gets shrunk
+    let auto_fit = args.auto_fit && replay.is_none() && level.is_none();
+    let footer_rows = u16::from(args.commentary && replay.is_none());
+    match fit_terminal(
+        board,
+        footer_rows,
+        auto_fit,
+        args.fullscreen,
+        args.status_bar_asked,
+        args.panel_width,
+    ) {
+        Ok(((width, height), status_bar)) => {
+            (args.width, args.height) = (width, height);
+            args.status_bar = status_bar;
+        }
+        Err(message) => {
+            eprintln!("rust-snake: {}", message);
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(replay) = replay {
+        widen_panel(&mut args, ["REPLAY"], footer_rows);
+        let high_score = score::load_high_score(args.difficulty, replay.mode);
+        let state = play_replay(&mut stdout, &args, &replay, high_score, &theme)?;
+        if let Some(outcome) = state.outcome() {
+            menu::show_game_over(
+                &mut stdout,
+                replay.width + panel_width(&args),
+                replay.height,
+                outcome,
+                &state,
+                &[],
+                &theme,
+            )?;
+        }
+        return restore_terminal(&mut stdout);
+    }
+
+    let mut finished_game = None;
+    let menu_outcome = if args.no_menu {
+        MenuOutcome::Play(MenuResult {
+            name: args
+                .name
+                .clone()
+                .unwrap_or_else(|| menu::DEFAULT_NAME.to_string()),
+            partner_name: args.relay.map(|_| menu::DEFAULT_PARTNER.to_string()),
+            difficulty: args.difficulty,
+            mode: args.mode,
+            arena: args.arena,
+            walls: args.walls,
+            theme,
+        })
+    } else {
+        menu::show(
+            &mut stdout,
+            args.width,
+            panel_width(&args),
+            args.height,
+            MenuResult {
+                name: args
+                    .name
+                    .clone()
+                    .or_else(|| score::load_last_name().map(|name| menu::sanitize_name(&name)))
+                    .unwrap_or_default(),
+                partner_name: args.relay.map(|_| String::new()),
+                difficulty: args.difficulty,
+                mode: args.mode,
+                arena: args.arena,
+                walls: args.walls,
+                theme,
+            },
+            // A map already decides where the walls go
+            level.is_none(),
+        )?
+    };
+    let mut high_score = 0;
+    if let MenuOutcome::Play(menu_result) = menu_outcome {
+        if !args.no_menu && !menu_result.name.trim().is_empty() {
+            // Only saves typing next time, not worth stopping the game for
+            let _ = score::save_last_name(&menu_result.name);
+        }
+        // Picking another difficulty in the menu replaces what the options set
+        if menu_result.difficulty != args.difficulty {
+            args.apply_difficulty(menu_result.difficulty);
+            if let Some(level) = &level {
+                (args.width, args.height) = (level.width, level.height);
+            }
+            match fit_terminal(
+                (args.width, args.height),
+                footer_rows,
+                auto_fit,
+                args.fullscreen,
+                args.status_bar_asked,
+                args.panel_width,
+            ) {
+                Ok(((width, height), status_bar)) => {
+                    (args.width, args.height) = (width, height);
+                    args.status_bar = status_bar;
+                }
+                Err(message) => {
+                    restore_terminal(&mut stdout)?;
+                    eprintln!("rust-snake: {}", message);
+                    std::process::exit(1);
+                }
+            }
+        }
+        let names =
+            iter::once(menu_result.name.as_str()).chain(menu_result.partner_name.as_deref());
+        widen_panel(&mut args, names, footer_rows);
+        args.mode = menu_result.mode;
+        args.walls = menu_result.walls;
+        theme = menu_result.theme;
+        high_score = score::load_high_score(args.difficulty, args.mode);
+        // Generated levels fit whatever board size was settled on, and share
+        // the game's seed so it rebuilds the same one
+        let seed = args.seed.unwrap_or_else(rand::random);
+        let level = level.or_else(|| menu_result.arena.level(args.width, args.height, seed));
+        let player = menu_result.name.clone();
+        let (state, relay) = run_game(
+            &mut stdout,
+            &args,
+            level,
+            seed,
+            menu_result,
+            high_score,
+            &mut theme,
+        )?;
+        if let Some(outcome) = state.outcome() {
+            let mut details: Vec<String> = relay
+                .iter()
+                .flat_map(|relay| &relay.players)
+                .map(|player| {
+                    format!(
+                        "{}: {} ticks, {} food",
+                        player.name, player.ticks_survived, player.foods_eaten
+                    )
+                })
+                .collect();
+            if args.demo {
+                details.push("Demo, so no high score".to_string());
+            } else if state.practice {
+                details.push("Practice, so no high score".to_string());
+            } else if state.is_rewound() {
+                details.push("Rewound, so no high score".to_string());
+            }
+            menu::show_game_over(
+                &mut stdout,
+                args.width + panel_width(&args),
+                args.height,
+                outcome,
+                &state,
+                &details,
+                &theme,
+            )?;
+            if state.is_ranked() && !args.demo {
+                submit_to_leaderboard(&mut stdout, &args, &state, &player, &theme)?;
+            }
+        }
+        finished_game = Some((state, player));
+    }
+
+    restore_terminal(&mut stdout)?;
+
+    if let Some((state, player)) = finished_game {
+        let replay = Replay::from_state(&state).with_player(player.trim_end());
+        replay.save(&score::data_dir().join(LAST_REPLAY_FILE))?;
+        if let Some(path) = &args.record {
+            replay.save(path)?;
+        }
+        if state.score > high_score && state.is_ranked() && !args.demo {
+            score::save_high_score(args.difficulty, args.mode, state.score)?;
+        }
+    }
+    Ok(())
+}
+
+// Offers a score good enough for the leaderboard a place on it, under a name
+// the player can still change, then shows the board
+fn submit_to_leaderboard(
+    stdout: &mut std::io::Stdout,
+    args: &Args,
+    state: &GameState,
+    player: &str,
+    theme: &Theme,
+) -> std::io::Result<()> {
+    let mut entries = leaderboard::load(args.difficulty, args.mode);
+    let Some(place) = leaderboard::place(&entries, state.score) else {
+        return Ok(());
+    };
+    let total_width = args.width + panel_width(args);
+    let name = player.trim_end();
+    let Some(name) = menu::confirm_name(stdout, total_width, args.height, name, place, theme)?
+    else {
+        return Ok(());
+    };
+    let place = leaderboard::submit(
+        &mut entries,
+        Entry::new(&name, state.score, state.game_time),
+    );
+    leaderboard::save(args.difficulty, args.mode, &entries)?;
+    menu::leaderboard_screen(stdout, total_width, args.height, &entries, place, theme)
+}
+
+// Checks the board and side panel fit in the terminal before anything is
+// drawn, or with `fullscreen` picks the biggest board that does. Where only
+// the board fits the width, or with `status_bar`, the status bar under it
+// takes the panel's place, and the size comes with whether it does.
+fn fit_terminal(
+    board: (u16, u16),
+    footer_rows: u16,
+    auto_fit: bool,
+    fullscreen: bool,
+    status_bar: bool,
+    panel_width: u16,
+) -> Result<((u16, u16), bool), String> {
+    let Ok(terminal_size) = terminal::size() else {
+        // Not attached to a terminal we can measure, let it be
+        return Ok((board, status_bar));
+    };
+    let available = (terminal_size.0, terminal_size.1.saturating_sub(footer_rows));
+    if fullscreen {
+        let fitted = layout::fill(panel_width, available);
+        return fitted.map(|board| (board, false)).ok_or_else(|| {
+            let required = layout::required_size(
+                game::MIN_BOARD_SIZE,
+                panel_width,
+                game::MIN_BOARD_SIZE + footer_rows,
+            );
+            format!(
+                "terminal too small for --fullscreen: the smallest board and the side panel need {}x{}, have {}x{}",
+                required.0, required.1, terminal_size.0, terminal_size.1
+            )
+        });
+    }
+    if let Some(status_bar) =
+        pick_layout(board, footer_rows, status_bar, panel_width, terminal_size)
+    {
+        return Ok((board, status_bar));
+    }
+    if auto_fit {
+        if let Some(fitted) =
+            layout::auto_fit(board.0, panel_width, board.1, available).filter(|_| !status_bar)
+        {
+            return Ok((fitted, false));
+        }
+        if let Some(fitted) = layout::fit_below(board.0, board.1, available) {
+            return Ok((fitted, true));
+        }
+    }
+    let required = if status_bar {
+        layout::required_size_below(board.0, board.1.saturating_add(footer_rows))
+    } else {
+        layout::required_size(board.0, panel_width, board.1.saturating_add(footer_rows))
+    };
+    Err(format!(
+        "terminal too small: need {}x{}, have {}x{}{}",
+        required.0,
+        required.1,
+        terminal_size.0,
+        terminal_size.1,
+        if auto_fit { "" } else { " (try --auto-fit)" }
+    ))
+}
+
+// Whether a board of this size goes with the status bar rather than the side
+// panel in a terminal of `terminal_size`, the status bar where only the board
+// fits the width. None when even that does not fit.
+fn pick_layout(
+    board: (u16, u16),
+    footer_rows: u16,
+    status_bar: bool,
+    panel_width: u16,
+    terminal_size: (u16, u16),
+) -> Option<bool> {
+    let rows = board.1.saturating_add(footer_rows);
+    let required = if status_bar {
+        layout::required_size_below(board.0, rows)
+    } else {
+        layout::required_size(board.0, panel_width, rows)
+    };
+    if layout::fits(required, terminal_size) {
+        return Some(status_bar);
+    }
+    let available = (terminal_size.0, terminal_size.1.saturating_sub(footer_rows));
+    layout::fits(layout::required_size_below(board.0, board.1), available).then_some(true)
+}
+
+// Columns the side panel takes next to the board, none with the status bar
+fn panel_width(args: &Args) -> u16 {
+    hud_width(args, args.status_bar)
+}
+
+fn hud_width(args: &Args, status_bar: bool) -> u16 {
+    if status_bar {
+        0
+    } else {
+        args.panel_width
+    }
+}
+
+// Rows printed under the board: the commentary line, then the status bar
+fn hud_rows(args: &Args, status_bar: bool) -> u16 {
+    let status_rows = if status_bar { layout::STATUS_ROWS } else { 0 };
+    u16::from(args.commentary) + status_rows
+}
+
+// Fits the layout to a resized terminal mid-game. The board keeps its size,
+// but the status bar takes the panel's place or gives it back, and either way
+// whatever the terminal kept of the old screen is wiped for a full redraw.
+fn relayout(
+    stdout: &mut std::io::Stdout,
+    args: &Args,
+    status_bar: bool,
+    terminal_size: (u16, u16),
+    renderer: &mut Renderer,
+) -> std::io::Result<bool> {
+    let picked = pick_layout(
+        (args.width, args.height),
+        u16::from(args.commentary),
+        args.status_bar_asked,
+        args.panel_width,
+        terminal_size,
+    );
+    execute!(stdout, terminal::Clear(terminal::ClearType::All))?;
+    render::reassert_hidden(stdout)?;
+    renderer.invalidate();
+    // Too small for either, the layout stays until there is room again
+    Ok(picked.unwrap_or(status_bar))
+}
+
+// As wide as the longest of `names` needs, or as close to it as the terminal
+// has room for next to the board
+fn widen_panel<'a>(args: &mut Args, names: impl IntoIterator<Item = &'a str>, footer_rows: u16) {
+    let wanted = names
+        .into_iter()
+        .map(|name| SidePanel::width_for(name, &args.bindings))
+        .max()
+        .unwrap_or(args.panel_width);
+    let rows = args.height.saturating_add(footer_rows);
+    let fits = |panel_width| match terminal::size() {
+        Ok(size) => layout::fits(layout::required_size(args.width, panel_width, rows), size),
+        // Not attached to a terminal we can measure, let it be
+        Err(_) => true,
+    };
+    if let Some(panel_width) = (args.panel_width..=wanted).rev().find(|&width| fits(width)) {
+        args.panel_width = panel_width;
+    }
+}
+
+fn restore_terminal(stdout: &mut std::io::Stdout) -> std::io::Result<()> {
+    execute!(
+        stdout,
+        terminal::Clear(terminal::ClearType::All),
+        cursor::MoveTo(0, 0),
+        cursor::Show
+    )?;
+    terminal::disable_raw_mode()
+}
+
+// --color or --no-color when given, otherwise what the terminal says
+fn color_support(args: &Args) -> ColorSupport {
+    match args.color {
+        _ if args.no_color => ColorSupport::Ansi16,
+        ColorChoice::Always => ColorSupport::TrueColor,
+        ColorChoice::Never => ColorSupport::Monochrome,
+        ColorChoice::Auto => Capabilities::detect().colors,
+    }
+}
+
+// Plain ASCII when asked for, or when the terminal can't be trusted with more
+fn ascii_only(args: &Args) -> bool {
+    args.ascii || Capabilities::detect().ascii_only
+}
+
+// Flips a target cell on every key press and times each stage between the
+// event arriving and the terminal write returning
+fn measure_latency(
+    stdout: &mut std::io::Stdout,
+    glyphs: &GlyphSet,
+) -> std::io::Result<LatencyStats> {
+    const KEY_PRESSES: usize = 100;
+    let target = (2, 4);
+
+    terminal::enable_raw_mode()?;
+    execute!(
+        stdout,
+        terminal::Clear(terminal::ClearType::All),
+        cursor::Hide,
+        cursor::MoveTo(2, 1),
+        style::Print(format!(
+            "Press any key {} times to measure latency, ESC to stop early",
+            KEY_PRESSES
+        ))
+    )?;
+
+    let theme = Theme::default();
+    let mut stats = LatencyStats::new();
+    let mut lit = false;
+    while stats.count(Stage::Total) < KEY_PRESSES {
+        let key_event = match event::read()? {
+            Event::Key(key_event) => key_event,
+            _ => continue,
+        };
+        let mut timer = FrameTimer::start(Instant::now());
+        if keyboard::translate(key_event, false) == Some(InputCommand::Quit) {
+            break;
+        }
+
+        lit = !lit;
+        timer.state_applied(Instant::now());
+
+        let color = if lit { theme.primary } else { theme.secondary };
+        queue!(
+            stdout,
+            cursor::MoveTo(target.0, target.1),
+            style::PrintStyledContent(glyphs.wall.repeat(2).with(color)),
+            cursor::MoveTo(target.0 + 4, target.1),
+            style::Print(format!("{}/{}", stats.count(Stage::Total) + 1, KEY_PRESSES))
+        )?;
+        timer.frame_built(Instant::now());
+
+        stdout.flush()?;
+        timer.flushed(Instant::now());
+
+        stats.record(&timer);
     }
 
     execute!(
@@ -32,93 +577,600 @@ fn main() -> std::io::Result<()> {
         cursor::Show
     )?;
     terminal::disable_raw_mode()?;
-    Ok(())
+    Ok(stats)
+}
+
+// Everything on the side panel that follows the game state
+fn update_side_panel(side_panel: &mut SidePanel, state: &GameState) {
+    side_panel.update_score(state.score);
+    side_panel.update_fruits(state.fruits_eaten());
+    side_panel.update_speed(&state.speed.label(state.score));
+    side_panel.update_bonus(state.bonus.map(|bonus| bonus.ttl));
+    side_panel.update_combo(state.combo_multiplier());
+    side_panel.update_effects(&state.effects);
+    side_panel.update_elapsed(state.game_time);
+    side_panel.update_length(state.snake.len() as u32);
+    side_panel.update_lives(state.lives, state.mode.lives());
+    side_panel.update_timer(state.time_left);
+    side_panel.update_shrink(state.mode, state.next_shrink);
+    side_panel.update_rival(state.rival.as_ref());
+}
+
+// The side panel, or the status bar in its place, whose few values are
+// brought up to date here
+fn queue_hud(
+    stdout: &mut std::io::Stdout,
+    side_panel: &SidePanel,
+    status_bar: Option<&mut StatusBar>,
+    state: &GameState,
+    theme: &Theme,
+) -> std::io::Result<()> {
+    match status_bar {
+        Some(status_bar) => {
+            status_bar.update_score(state.score);
+            status_bar.update_elapsed(state.game_time);
+            status_bar.queue(stdout)
+        }
+        None => side_panel.queue(stdout, theme),
+    }
 }
 
-fn run_game(stdout: &mut std::io::Stdout, player_name: String) -> std::io::Result<()> {
+fn run_game(
+    stdout: &mut std::io::Stdout,
+    args: &Args,
+    level: Option<Level>,
+    seed: u64,
+    menu_result: MenuResult,
+    high_score: u32,
+    theme: &mut Theme,
+) -> std::io::Result<(GameState, Option<Relay>)> {
     terminal::enable_raw_mode()?;
     execute!(stdout, terminal::Clear(terminal::ClearType::All))?;
     execute!(stdout, cursor::Hide)?;
+    // Key releases are only reported with the keyboard enhancement, so the
+    // boost ends on letting go of its key where the terminal has it, and
+    // only on its timeout elsewhere
+    let report_releases = terminal::supports_keyboard_enhancement().unwrap_or(false);
+    if report_releases {
+        execute!(
+            stdout,
+            event::PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+        )?;
+    }
+
+    let mut renderer =
+        Renderer::new(args.width, args.height).with_reduced_motion(args.reduce_motion);
+    let mut state = args.new_game(level, seed);
+    let mut relay = args
+        .relay
+        .zip(menu_result.partner_name)
+        .map(|(turn_length, partner)| Relay::new(menu_result.name.clone(), partner, turn_length));
+    // Below the commentary when there is some, kept up to date even while
+    // the side panel is shown so a resize can switch over to it
+    let mut status_bar = StatusBar::new(
+        args.height + u16::from(args.commentary),
+        args.width,
+        menu_result.name.clone(),
+        high_score,
+    );
+    let mut show_status_bar = args.status_bar;
+    let mut side_panel = SidePanel::new(
+        args.width,
+        args.height,
+        args.panel_width,
+        menu_result.name,
+        high_score,
+        &args.bindings,
+        theme.glyphs,
+    );
+
+    let mut capabilities = Capabilities::detect();
+    capabilities.cursor_hide_unreliable |= args.cursor_fallback;
+    let mut total_width = args.width + panel_width(args);
+    let mut parking_spot = render::parking_spot(
+        total_width,
+        args.height,
+        hud_rows(args, show_status_bar),
+        capabilities,
+    );
+
+    // Whatever steers the player, the rival's own is run by the game state
+    let player: Box<dyn Controller> = if args.demo {
+        Box::new(AutoController::new(1))
+    } else {
+        Box::new(KeyboardController::new())
+    };
+    let mut controllers = vec![player];
+    if state.is_two_player() {
+        controllers.push(Box::new(KeyboardController::second_player()));
+    }
+    // The board goes up first so the countdown runs over it, and the clock
+    // only starts once it is done
+    renderer.render(stdout, &state, theme)?;
+    update_side_panel(&mut side_panel, &state);
+    let hud = show_status_bar.then_some(&mut status_bar);
+    queue_hud(stdout, &side_panel, hud, &state, theme)?;
+    menu::countdown(stdout, args.width, args.height, theme)?;
+    renderer.invalidate_row(args.height / 2);
 
-    let game_grid = GameGrid::new(GAME_WIDTH, HEIGHT);
-    let mut state = GameState::new(GAME_WIDTH, HEIGHT);
-    let mut side_panel = SidePanel::new(GAME_WIDTH, HEIGHT, PANEL_WIDTH, player_name);
+    let mut pending = VecDeque::new();
+    let mut drain_stats = DrainStats::default();
+    let mut commentator = args.commentary.then(Commentator::new);
+    // Game time owed to the simulation, paid out one step at a time
+    let mut accumulator = Duration::ZERO;
+    let mut last_frame = Instant::now();
+    let mut rewind = false;
+    let mut respawned = false;
+    let mut game_time = Duration::ZERO;
+    let mut frame_rate = FrameRate::new();
+    let mut show_frame_rate = args.debug_hud;
+    let mut panel_visible = true;
+    let mut boost = Boost::default();
 
     'game_loop: loop {
         let frame_start = Instant::now();
+        let mut timer = FrameTimer::start(frame_start);
+        // After a stall, catch up by a couple of steps rather than a burst
+        // Paused time never reaches here, so it is not on the clock either
+        let bounds = state.bounds;
+        state.advance_clock(frame_start - last_frame);
+        accumulator = (accumulator + (frame_start - last_frame))
+            .min(boost.interval(state.step_interval(), frame_start) * 2 + RENDER_INTERVAL);
+        last_frame = frame_start;
 
-        let user_input = event::poll(Duration::from_millis(5))?
-            .then(event::read)
-            .and_then(|result| result.ok())
-            .and_then(|event| match event {
-                Event::Key(key_event) => Some(key_event.code),
-                _ => None,
-            });
+        // Turns are buffered, anything else is handled right away, in the
+        // order it came
+        let mut user_input = Vec::new();
+        let mut resized = None;
+        poll_commands(stdout, &args.bindings, &mut pending)?;
+        let drained = input::drain(&mut pending, input::MAX_EVENTS_PER_FRAME);
+        drain_stats.record(&drained);
+        for command in drained.commands {
+            // Only the last size counts
+            if let InputCommand::Resize(columns, rows) = command {
+                resized = Some((columns, rows));
+                continue;
+            }
+            let taken = controllers
+                .iter_mut()
+                .any(|controller| controller.handle(command));
+            if !taken {
+                user_input.push(command);
+            }
+        }
 
-        if let Some(KeyCode::Esc) = user_input {
+        if user_input.contains(&InputCommand::Quit) {
             break 'game_loop;
         }
 
-        if let Some(KeyCode::Char('s')) = user_input {
-            loop {
-                let user_input = event::poll(Duration::from_millis(5))?
-                    .then(event::read)
-                    .and_then(|result| result.ok())
-                    .and_then(|event| match event {
-                        Event::Key(key_event) => Some(key_event.code),
-                        _ => None,
-                    });
+        for command in user_input {
+            match command {
+                InputCommand::Pause => {
+                    loop {
+                        let user_input = poll_command(stdout, &args.bindings)?;
+
+                        if let Some(InputCommand::Quit) = user_input {
+                            break 'game_loop;
+                        }
+
+                        if let Some(InputCommand::Pause) = user_input {
+                            render::reassert_hidden(stdout)?;
+                            // Time spent paused is not owed to the simulation
+                            last_frame = Instant::now();
+                            break;
+                        }
+
+                        let redraw = match user_input {
+                            Some(InputCommand::Undo) => state.undo().is_some(),
+                            Some(InputCommand::Resize(columns, rows)) => {
+                                show_status_bar = relayout(
+                                    stdout,
+                                    args,
+                                    show_status_bar,
+                                    (columns, rows),
+                                    &mut renderer,
+                                )?;
+                                total_width = args.width + hud_width(args, show_status_bar);
+                                parking_spot = render::parking_spot(
+                                    total_width,
+                                    args.height,
+                                    hud_rows(args, show_status_bar),
+                                    capabilities,
+                                );
+                                true
+                            }
+                            _ => false,
+                        };
+                        if redraw {
+                            update_side_panel(&mut side_panel, &state);
 
-                if let Some(KeyCode::Esc) = user_input {
-                    break 'game_loop;
+                            renderer.render(stdout, &state, theme)?;
+                            if panel_visible {
+                                let hud = show_status_bar.then_some(&mut status_bar);
+                                queue_hud(stdout, &side_panel, hud, &state, theme)?;
+                            }
+                            render::park_cursor(stdout, parking_spot, capabilities)?;
+                            stdout.flush()?;
+                        }
+                    }
                 }
+                InputCommand::Undo => rewind = true,
+                InputCommand::CycleTheme => {
+                    *theme = theme.next();
+                    // Everything is drawn again below, clear what the old theme left
+                    execute!(stdout, terminal::Clear(terminal::ClearType::All))?;
+                    renderer.invalidate();
+                }
+                InputCommand::ToggleFps => show_frame_rate = !show_frame_rate,
+                InputCommand::TogglePanel => {
+                    panel_visible = !panel_visible;
+                    // Shown again it is all drawn below, hidden it has to be wiped
+                    if !panel_visible {
+                        if show_status_bar {
+                            status_bar.clear(stdout)?;
+                        } else {
+                            side_panel.clear(stdout)?;
+                        }
+                    }
+                }
+                InputCommand::Boost(true) => boost.start(frame_start),
+                InputCommand::Boost(false) => boost.stop(),
+                _ => {}
+            }
+        }
+        if let Some(size) = resized {
+            show_status_bar = relayout(stdout, args, show_status_bar, size, &mut renderer)?;
+            total_width = args.width + hud_width(args, show_status_bar);
+            parking_spot = render::parking_spot(
+                total_width,
+                args.height,
+                hud_rows(args, show_status_bar),
+                capabilities,
+            );
+        }
+
+        // The cells this frame's ticks changed, None to compare the whole board
+        let mut changed = Some(Vec::new());
+        let mut ticks = 0;
+        let mut ate = false;
+        while accumulator >= boost.interval(state.step_interval(), frame_start)
+            && state.outcome().is_none()
+        {
+            let step = state.step_interval();
+            accumulator -= boost.interval(step, frame_start);
+
+            if rewind {
+                // Holding 'u' keeps rewinding instead of moving forward
+                state.undo();
+                changed = None;
+                rewind = false;
+                continue;
+            }
+
+            let change_direction = controllers[PLAYER].decide(&state, PLAYER);
+            let mut action = state.player_action(change_direction);
+            if let Some(second) = controllers.get_mut(RIVAL) {
+                action.second_turn = second.decide(&state, RIVAL);
+            }
+            let events = state.next(action);
+            game_time += step;
+            ticks += 1;
+            let ate_now = events
+                .iter()
+                .any(|event| matches!(event.kind, GameEventKind::FoodEaten { .. }));
+            ate |= ate_now;
+            match (&mut changed, state.changed_cells()) {
+                (Some(changed), Some(cells)) => changed.extend(cells),
+                _ => changed = None,
+            }
 
-                if let Some(KeyCode::Char('s')) = user_input {
-                    break;
+            if let Some(commentator) = &mut commentator {
+                let name = relay.as_ref().map_or(&side_panel.player_row.data, |relay| {
+                    &relay.active_player().name
+                });
+                commentator.observe(&state, ate_now, name.trim_end(), game_time);
+            }
+            if let Some(relay) = &mut relay {
+                if relay.tick(step, ate_now) {
+                    side_panel.update_player(&relay.active_player().name);
+                    status_bar.update_player(&relay.active_player().name);
                 }
+            }
+            // Stop here to show the snake back at the start before moving on
+            if events
+                .iter()
+                .any(|event| matches!(event.kind, GameEventKind::Respawned { .. }))
+            {
+                respawned = true;
+                accumulator = Duration::ZERO;
+                break;
+            }
+        }
 
-                if let Some(KeyCode::Char('b')) = user_input {
-                    if let Some(action) = state.actions.pop() {
-                        let reverse_action = Action::reverse(action);
-                        state.next(reverse_action);
+        if state.bounds != bounds {
+            changed = None;
+        }
+        timer.state_applied(Instant::now());
+        match &changed {
+            Some(cells) => renderer.render_cells(stdout, &state, theme, cells)?,
+            None => renderer.render(stdout, &state, theme)?,
+        }
+        update_side_panel(&mut side_panel, &state);
+        side_panel.update_frame_rate(show_frame_rate.then_some(&frame_rate));
+        side_panel.update_input_stats(show_frame_rate.then_some(&drain_stats));
+        if panel_visible {
+            let hud = show_status_bar.then_some(&mut status_bar);
+            queue_hud(stdout, &side_panel, hud, &state, theme)?;
+        }
+        if let Some(relay) = &relay {
+            if let Some(seconds) = relay.warning() {
+                let banner = format!(
+                    " HANDOFF to {} in {}{} ",
+                    relay.next_player().name,
+                    seconds,
+                    theme.glyphs.ellipsis
+                );
+                menu::queue_banner(stdout, args.width, &banner, theme)?;
+                renderer.invalidate_row(0);
+            }
+        }
+        if let Some(commentator) = &commentator {
+            commentator.queue(stdout, args.height, total_width, theme)?;
+        }
+        if respawned {
+            let banner = format!(" CRASH! {} {} left ", state.lives, theme.glyphs.life);
+            menu::queue_banner(stdout, args.width, &banner, theme)?;
+            renderer.invalidate_row(0);
+        }
+        render::park_cursor(stdout, parking_spot, capabilities)?;
+        timer.frame_built(Instant::now());
+        stdout.flush()?;
+        timer.flushed(Instant::now());
+        frame_rate.record(&timer, ticks);
+
+        if state.outcome().is_some() {
+            if args.sound {
+                sound::play(stdout, Cue::GameOver)?;
+            }
+            break 'game_loop;
+        }
+        if args.sound && ate {
+            sound::play(stdout, Cue::Eat)?;
+        }
 
-                        side_panel.update_score(state.score);
+        if respawned {
+            // Turns queued up before the crash would steer the new snake
+            for controller in &mut controllers {
+                controller.reset();
+            }
+            boost.stop();
+            wait_for_frame_end(
+                stdout,
+                &args.bindings,
+                &mut pending,
+                Instant::now() + RESPAWN_PAUSE,
+            )?;
+            last_frame = Instant::now();
+            respawned = false;
+            continue;
+        }
 
-                        game_grid.queue(stdout)?;
-                        side_panel.queue(stdout)?;
-                        state.queue(stdout)?;
-                        stdout.flush()?;
+        wait_for_frame_end(
+            stdout,
+            &args.bindings,
+            &mut pending,
+            frame_start + RENDER_INTERVAL,
+        )?;
+    }
 
-                        if state.is_game_over() {
-                            break 'game_loop;
-                        }
-                    } else {
-                        break 'game_loop;
+    if report_releases {
+        execute!(stdout, event::PopKeyboardEnhancementFlags)?;
+    }
+    Ok((state, relay))
+}
+
+fn run_command(command: &Command) -> std::io::Result<()> {
+    match command {
+        Command::ExportRun { output } => {
+            let path = score::data_dir().join(LAST_REPLAY_FILE);
+            let replay = Replay::load(&path).map_err(|err| {
+                std::io::Error::new(
+                    err.kind(),
+                    format!(
+                        "could not read the last game from {}: {}",
+                        path.display(),
+                        err
+                    ),
+                )
+            })?;
+            RunCard::from_replay(replay).save(output)?;
+            println!("Saved the last run to {}", output.display());
+        }
+        Command::ImportRun { path } => {
+            // Only the replay is checksummed, so what is shown and kept comes
+            // from playing it through, not from the card's own summary and board
+            let card = RunCard::from_replay(RunCard::load(path)?.replay);
+            let summary = &card.summary;
+            println!(
+                "Score {}, length {}, {} ticks in {}, {} mode with {} walls, seed {}, ended by {}",
+                summary.score,
+                summary.length,
+                summary.ticks,
+                menu::format_duration(summary.duration),
+                summary.mode,
+                summary.walls,
+                summary.seed,
+                summary.outcome
+            );
+            print!("{}", card.board);
+
+            // Run cards don't record a difficulty, so they compete with normal
+            let mode = card.replay.mode;
+            let mut entries = leaderboard::load(Difficulty::Normal, mode);
+            if leaderboard::place(&entries, card.summary.score).is_some() {
+                print!(
+                    "Add {} to the leaderboard, marked as imported? [y/N] ",
+                    card.summary.score
+                );
+                std::io::stdout().flush()?;
+                let mut answer = String::new();
+                std::io::stdin().read_line(&mut answer)?;
+                if answer.trim().eq_ignore_ascii_case("y") {
+                    let name = card
+                        .replay
+                        .player
+                        .as_deref()
+                        .map(menu::sanitize_name)
+                        .filter(|name| !name.is_empty())
+                        .unwrap_or_else(|| menu::DEFAULT_NAME.to_string());
+                    let entry = Entry::imported(&name, card.summary.score, card.summary.duration);
+                    if let Some(place) = leaderboard::submit(&mut entries, entry) {
+                        leaderboard::save(Difficulty::Normal, mode, &entries)?;
+                        println!("Added at place {}", place + 1);
                     }
                 }
             }
         }
+    }
+    Ok(())
+}
 
-        let action = state.get_action(user_input);
+fn play_replay(
+    stdout: &mut std::io::Stdout,
+    args: &Args,
+    replay: &Replay,
+    high_score: u32,
+    theme: &Theme,
+) -> std::io::Result<GameState> {
+    terminal::enable_raw_mode()?;
+    execute!(
+        stdout,
+        terminal::Clear(terminal::ClearType::All),
+        cursor::Hide
+    )?;
 
-        state.next(action);
+    let mut renderer =
+        Renderer::new(replay.width, replay.height).with_reduced_motion(args.reduce_motion);
+    let mut state = replay.initial_state();
+    state.speed = args.speed();
+    // Replays only record ticks, not how long a timed game had left
+    state.time_left = None;
+    let mut status_bar = args.status_bar.then(|| {
+        StatusBar::new(
+            replay.height,
+            replay.width,
+            "REPLAY".to_string(),
+            high_score,
+        )
+    });
+    let mut side_panel = SidePanel::new(
+        replay.width,
+        replay.height,
+        args.panel_width,
+        "REPLAY".to_string(),
+        high_score,
+        &args.bindings,
+        theme.glyphs,
+    );
 
-        game_grid.queue(stdout)?;
-        side_panel.update_score(state.score);
-        side_panel.queue(stdout)?;
-        state.queue(stdout)?;
+    let mut capabilities = Capabilities::detect();
+    capabilities.cursor_hide_unreliable |= args.cursor_fallback;
+    let parking_spot = render::parking_spot(
+        replay.width + panel_width(args),
+        replay.height,
+        0,
+        capabilities,
+    );
+
+    for &tick in &replay.ticks {
+        let frame_start = Instant::now();
+
+        if let Some(InputCommand::Quit) = poll_command(stdout, &args.bindings)? {
+            break;
+        }
+
+        Replay::apply(&mut state, tick);
+
+        match state.changed_cells() {
+            Some(cells) => renderer.render_cells(stdout, &state, theme, &cells)?,
+            None => renderer.render(stdout, &state, theme)?,
+        }
+        update_side_panel(&mut side_panel, &state);
+        queue_hud(stdout, &side_panel, status_bar.as_mut(), &state, theme)?;
+        render::park_cursor(stdout, parking_spot, capabilities)?;
         stdout.flush()?;
 
-        if state.is_game_over() {
-            break 'game_loop;
+        if state.outcome().is_some() {
+            break;
         }
 
-        // Calculate remaining time in frame and sleep
         let elapsed = frame_start.elapsed();
-        if elapsed < FRAME_DURATION {
-            thread::sleep(FRAME_DURATION - elapsed);
+        if let Some(remaining) = state.step_interval().checked_sub(elapsed) {
+            thread::sleep(remaining);
         }
     }
 
+    Ok(state)
+}
+
+// Most keys arrive while waiting for the previous frame to end
+const MAX_PENDING_KEYS: usize = input::MAX_EVENTS_PER_FRAME * 8;
+
+// Queues whatever is still waiting without blocking, oldest first. Reading is
+// capped too, anything past it stays in the terminal's queue for later frames.
+fn poll_commands(
+    stdout: &mut std::io::Stdout,
+    bindings: &KeyBindings,
+    pending: &mut VecDeque<InputCommand>,
+) -> std::io::Result<()> {
+    while pending.len() < MAX_PENDING_KEYS && event::poll(Duration::ZERO)? {
+        pending.extend(read_command(stdout, bindings)?);
+    }
     Ok(())
 }
+
+// Spends the rest of the frame waiting on input rather than sleeping, so keys
+// pressed in between are queued for the next tick as they come in
+fn wait_for_frame_end(
+    stdout: &mut std::io::Stdout,
+    bindings: &KeyBindings,
+    pending: &mut VecDeque<InputCommand>,
+    deadline: Instant,
+) -> std::io::Result<()> {
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Ok(());
+        }
+        if pending.len() >= MAX_PENDING_KEYS {
+            thread::sleep(remaining);
+            return Ok(());
+        }
+        if event::poll(remaining)? {
+            pending.extend(read_command(stdout, bindings)?);
+        }
+    }
+}
+
+fn poll_command(
+    stdout: &mut std::io::Stdout,
+    bindings: &KeyBindings,
+) -> std::io::Result<Option<InputCommand>> {
+    if !event::poll(Duration::from_millis(5))? {
+        return Ok(None);
+    }
+    read_command(stdout, bindings)
+}
+
+fn read_command(
+    stdout: &mut std::io::Stdout,
+    bindings: &KeyBindings,
+) -> std::io::Result<Option<InputCommand>> {
+    let event = event::read()?;
+    let resized = match event {
+        Event::Resize(columns, rows) => Some(InputCommand::Resize(columns, rows)),
+        _ => None,
+    };
+    let key = render::handle_event(stdout, event)?;
+    Ok(resized.or_else(|| key.and_then(|key| bindings.translate(key, false))))
+}
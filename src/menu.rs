@@ -1,74 +1,415 @@
-use crate::theme;
+use crate::{
+    difficulty::Difficulty,
+    framerate::FrameRate,
+    game::{
+        Direction, Effect, FruitKind, GameMode, GameOutcome, GameState, WallMode, BONUS_LIFETIME,
+    },
+    input::{DrainStats, InputCommand, MenuNav},
+    keyboard::{self, KeyBindings},
+    leaderboard::Entry,
+    level::Arena,
+    rival::Rival,
+    theme::{GlyphSet, Theme},
+};
 use crossterm::{
     cursor,
-    event::{self, Event, KeyCode},
+    event::{self, Event, KeyEventKind},
     execute, queue,
     style::{self, Print, PrintStyledContent, Stylize},
     terminal,
 };
-use std::io::Write;
+use std::{
+    io::Write,
+    iter,
+    time::{Duration, Instant},
+};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+// Where the bonus countdown starts on the score line, and how long its bar is
+const BONUS_COLUMN: u16 = 7;
+const BONUS_BAR_WIDTH: u32 = 6;
+// The combo multiplier follows the bonus bar
+const COMBO_COLUMN: u16 = BONUS_COLUMN + BONUS_BAR_WIDTH as u16 + 2;
+// Fruit counts go on the score title line, each one glyph and three cells
+const FRUIT_COLUMN: u16 = 6;
+const FRUIT_COUNT_WIDTH: usize = 3;
+// The score has to end before the bonus bar does
+const SCORE_WIDTH: usize = BONUS_COLUMN as usize - 1;
+// Lives, the clock or the length share the max score lines after its title, as
+// the elapsed time does the speed lines
+const MODE_COLUMN: u16 = 11;
+// The side panel's rows are laid out in this, it only gets wider for a long
+// name or key binding, and never wider than the max
+pub const PANEL_WIDTH: u16 = 20;
+pub const MAX_PANEL_WIDTH: u16 = 32;
+// Long enough for most names, short enough for the side panel
+const MAX_NAME_LENGTH: usize = 12;
+// What names left blank play as
+pub const DEFAULT_NAME: &str = "player";
+pub const DEFAULT_PARTNER: &str = "partner";
 
 #[derive(Debug)]
 pub struct SidePanel {
     pub x: u16,
     pub width: u16,
     pub height: u16,
-    pub score_row: DynamicInfoRow<u32>,
+    pub score_row: DynamicInfoRow<String>,
     pub player_row: StaticInfoRow<String>,
     pub max_score_row: StaticInfoRow<u32>,
+    pub speed_row: DynamicInfoRow<String>,
+    // Only in games with more than one life
+    pub lives_row: Option<DynamicInfoRow<String>>,
+    // Only in timed games, or counting down to the next shrink
+    pub timer_row: Option<DynamicInfoRow<String>>,
+    // Only racing a rival, next to the player's name
+    pub rival_row: Option<DynamicInfoRow<String>>,
+    // How long the run has lasted, next to the speed
+    pub elapsed_row: DynamicInfoRow<String>,
+    // Head and tail, next to the max score when the lives or a timer don't
+    // need the place
+    pub length_row: Option<DynamicInfoRow<String>>,
+    pub controls: Vec<String>,
+    // Time left on the bonus, drawn next to the score while it is out
+    pub bonus_countdown: String,
+    // "x3" while eating keeps a combo going, blank otherwise
+    pub combo: String,
+    // Fruits eaten by kind, drawn after the score title
+    pub fruits_eaten: [u32; FruitKind::ALL.len()],
+    // Ticks left on each active effect, drawn after the speed
+    pub effects: String,
+    // Measured frame and tick rates and the last frame's draw time in the
+    // bottom border, empty while hidden
+    pub frame_rate: String,
+    // Keys merged and held back by the input drain, on the line above, over
+    // the last of the controls when the board is no taller than the default
+    pub input_stats: String,
+    // Hearts, the bonus bar and the borders are built from these
+    pub glyphs: &'static GlyphSet,
 }
 
 impl SidePanel {
-    pub fn new(game_width_offset: u16, height: u16, panel_width: u16, player_name: String) -> Self {
+    pub fn new(
+        game_width_offset: u16,
+        height: u16,
+        panel_width: u16,
+        player_name: String,
+        high_score: u32,
+        bindings: &KeyBindings,
+        glyphs: &'static GlyphSet,
+    ) -> Self {
         let x = game_width_offset + 2;
+        // Three lines is all the default height leaves
+        let controls = controls(bindings)
+            .iter()
+            .map(|line| {
+                truncate(
+                    line,
+                    panel_width.saturating_sub(2) as usize,
+                    glyphs.ellipsis,
+                )
+            })
+            .collect();
         Self {
             x,
             width: panel_width,
             height,
-            score_row: DynamicInfoRow::new("SCORE", 0, x, 1),
+            score_row: DynamicInfoRow::new("SCORE", pad("0", SCORE_WIDTH), x, 1),
             player_row: StaticInfoRow::new("PLAYER", player_name, x, 0),
-            max_score_row: StaticInfoRow::new("MAX SCORE", 25, x, 2), // TODO
+            max_score_row: StaticInfoRow::new("MAX SCORE", high_score, x, 2),
+            speed_row: DynamicInfoRow::new("SPEED", String::new(), x, 3),
+            lives_row: None,
+            timer_row: None,
+            rival_row: None,
+            length_row: None,
+            elapsed_row: DynamicInfoRow::new(
+                "ELAPSED",
+                format_duration(Duration::ZERO),
+                x + MODE_COLUMN,
+                3,
+            ),
+            controls,
+            bonus_countdown: String::new(),
+            combo: String::new(),
+            fruits_eaten: [0; FruitKind::ALL.len()],
+            effects: String::new(),
+            frame_rate: String::new(),
+            input_stats: String::new(),
+            glyphs,
         }
     }
 
-    pub fn queue(&self, stdout: &mut std::io::Stdout) -> std::io::Result<()> {
-        self.queue_borders_and_corners(stdout)?;
+    // Wide enough for the player's name and the controls, rows start two
+    // columns in and the name keeps one more clear of the border
+    pub fn width_for(player_name: &str, bindings: &KeyBindings) -> u16 {
+        let widest = controls(bindings)
+            .iter()
+            .map(|line| line.width() + 2)
+            .chain(iter::once(player_name.width() + 3))
+            .max()
+            .unwrap_or(0);
+        u16::try_from(widest)
+            .unwrap_or(u16::MAX)
+            .clamp(PANEL_WIDTH, MAX_PANEL_WIDTH)
+    }
+
+    pub fn queue(&self, stdout: &mut impl Write, theme: &Theme) -> std::io::Result<()> {
+        self.queue_borders_and_corners(stdout, theme)?;
         self.player_row.queue(stdout)?;
         self.score_row.queue(stdout)?;
         self.max_score_row.queue(stdout)?;
-
-        // Add help text with some spacing after the info rows
+        self.speed_row.queue(stdout)?;
+        self.elapsed_row.queue(stdout)?;
+        if let Some(lives_row) = &self.lives_row {
+            lives_row.queue(stdout)?;
+        }
+        if let Some(rival_row) = &self.rival_row {
+            rival_row.queue(stdout)?;
+        }
+        if let Some(timer_row) = &self.timer_row {
+            timer_row.queue(stdout)?;
+        }
+        if let (Some(length_row), None, None) = (&self.length_row, &self.lives_row, &self.timer_row)
+        {
+            length_row.queue(stdout)?;
+        }
         queue!(
             stdout,
-            cursor::MoveTo(self.x + 2, self.max_score_row.y_position + 3),
-            style::PrintStyledContent("CONTROLS".white()),
-            cursor::MoveTo(self.x + 2, self.max_score_row.y_position + 4),
-            style::PrintStyledContent("'s' to stop".white()),
-            cursor::MoveTo(self.x + 2, self.max_score_row.y_position + 5),
-            style::PrintStyledContent("'b' to go back".white()),
-            cursor::MoveTo(self.x + 2, self.max_score_row.y_position + 6),
-            style::PrintStyledContent("'ESC' to exit".white())
+            cursor::MoveTo(self.x + 2 + BONUS_COLUMN, self.score_row.y_position + 1),
+            style::PrintStyledContent(self.bonus_countdown.as_str().with(theme.gold))
         )?;
+        queue!(
+            stdout,
+            cursor::MoveTo(self.x + 2 + COMBO_COLUMN, self.score_row.y_position + 1),
+            style::PrintStyledContent(self.combo.as_str().with(theme.accent).bold())
+        )?;
+        // Cut short rather than run into the elapsed time
+        let effects_column = self.speed_row.data.trim_end().chars().count() as u16 + 1;
+        let room = MODE_COLUMN.saturating_sub(effects_column + 1) as usize;
+        let effects: String = self.effects.chars().take(room).collect();
+        queue!(
+            stdout,
+            cursor::MoveTo(self.x + 2 + effects_column, self.speed_row.y_position + 1),
+            style::PrintStyledContent(effects.with(theme.accent))
+        )?;
+        // The hourglass shows as an effect on the speed line instead
+        queue!(
+            stdout,
+            cursor::MoveTo(self.x + 2 + FRUIT_COLUMN, self.score_row.y_position)
+        )?;
+        let fruits = FruitKind::ALL.iter().zip(self.fruits_eaten);
+        for (kind, count) in fruits.filter(|(kind, _)| **kind != FruitKind::Hourglass) {
+            let count = compact_number(count, FRUIT_COUNT_WIDTH, self.glyphs.ellipsis);
+            queue!(
+                stdout,
+                style::PrintStyledContent(self.glyphs.fruit(*kind).with(kind.color(theme))),
+                style::PrintStyledContent(pad(&count, FRUIT_COUNT_WIDTH).white())
+            )?;
+        }
+
+        if !self.frame_rate.is_empty() {
+            let width = self.frame_rate.chars().count() as u16;
+            queue!(
+                stdout,
+                cursor::MoveTo(
+                    (self.x + self.width).saturating_sub(width + 1),
+                    self.height - 1
+                ),
+                style::PrintStyledContent(self.frame_rate.as_str().with(theme.inactive))
+            )?;
+        }
+
+        // Help text right after the info rows, it has to fit the default
+        // height. Padded to wipe the input stats once they are hidden.
+        let inner_width = self.width.saturating_sub(2) as usize;
+        for (line, y) in self.controls.iter().zip(self.speed_row.y_position + 2..) {
+            queue!(
+                stdout,
+                cursor::MoveTo(self.x + 2, y),
+                style::PrintStyledContent(pad(line, inner_width).white())
+            )?;
+        }
+        if !self.input_stats.is_empty() {
+            let stats = truncate(&self.input_stats, inner_width, self.glyphs.ellipsis);
+            queue!(
+                stdout,
+                cursor::MoveTo(self.x + 2, self.height.saturating_sub(2)),
+                style::PrintStyledContent(pad(&stats, inner_width).with(theme.inactive))
+            )?;
+        }
 
         Ok(())
     }
 
+    // Padded, an undo can take points back
     pub fn update_score(&mut self, score: u32) {
-        self.score_row.update(score);
+        let score = compact_number(score, SCORE_WIDTH, self.glyphs.ellipsis);
+        self.score_row.update(pad(&score, SCORE_WIDTH));
     }
 
-    pub fn queue_borders_and_corners(&self, stdout: &mut std::io::Stdout) -> std::io::Result<()> {
+    // Full hearts for the lives left, empty ones for those already lost
+    pub fn update_lives(&mut self, lives: u8, starting_lives: u8) {
+        if starting_lives <= 1 {
+            return;
+        }
+        let hearts = format!(
+            "{}{}",
+            self.glyphs.life.repeat(lives as usize),
+            self.glyphs
+                .lost_life
+                .repeat(starting_lives.saturating_sub(lives) as usize)
+        );
+        let x = self.x + MODE_COLUMN;
+        let row = self
+            .lives_row
+            .get_or_insert_with(|| DynamicInfoRow::new("LIVES", String::new(), x, 2));
+        row.update(hearts);
+    }
+
+    // MM:SS, rounded up so the clock reads 00:00 only once time is up
+    pub fn update_timer(&mut self, time_left: Option<Duration>) {
+        if let Some(time_left) = time_left {
+            self.timer("TIME").update(clock(time_left));
+        }
+    }
+
+    // Time to the next shrink, or dashes once the arena is as small as it gets
+    pub fn update_shrink(&mut self, mode: GameMode, next_shrink: Option<Duration>) {
+        if mode == GameMode::Shrinking {
+            let countdown = next_shrink.map_or_else(|| "--:--".to_string(), clock);
+            self.timer("SHRINK").update(countdown);
+        }
+    }
+
+    fn timer(&mut self, title: &str) -> &mut DynamicInfoRow<String> {
+        let x = self.x + MODE_COLUMN;
+        self.timer_row
+            .get_or_insert_with(|| DynamicInfoRow::new(title, String::new(), x, 2))
+    }
+
+    // Written over the last readout rather than into a new one, it changes
+    // every frame
+    pub fn update_frame_rate(&mut self, frame_rate: Option<&FrameRate>) {
+        match frame_rate {
+            Some(frame_rate) => frame_rate.write_label(&mut self.frame_rate),
+            None => self.frame_rate.clear(),
+        }
+    }
+
+    pub fn update_input_stats(&mut self, stats: Option<&DrainStats>) {
+        match stats {
+            Some(stats) => stats.write_label(&mut self.input_stats),
+            None => self.input_stats.clear(),
+        }
+    }
+
+    pub fn update_fruits(&mut self, fruits_eaten: [u32; FruitKind::ALL.len()]) {
+        self.fruits_eaten = fruits_eaten;
+    }
+
+    // An effect that wore off is blanked out by the padded speed
+    pub fn update_effects(&mut self, effects: &[Effect]) {
+        self.effects = effects
+            .iter()
+            .map(|effect| format!("{}{}", self.glyphs.effect(effect.kind), effect.remaining))
+            .collect();
+    }
+
+    // Padded, the snake can come out of an undo shorter
+    pub fn update_length(&mut self, length: u32) {
+        let x = self.x + MODE_COLUMN;
+        self.length_row
+            .get_or_insert_with(|| DynamicInfoRow::new("LENGTH", String::new(), x, 2))
+            .update(format!("{:<6}", length));
+    }
+
+    pub fn update_elapsed(&mut self, elapsed: Duration) {
+        self.elapsed_row.update(format_duration(elapsed));
+    }
+
+    // A star and a bar that empties as the bonus runs out, blank without one
+    pub fn update_bonus(&mut self, ttl: Option<u32>) {
+        self.bonus_countdown = match ttl {
+            Some(ttl) => {
+                let filled = (ttl * BONUS_BAR_WIDTH).div_ceil(BONUS_LIFETIME) as usize;
+                let filled = filled.min(BONUS_BAR_WIDTH as usize);
+                format!(
+                    "{}{}{}",
+                    self.glyphs.bonus,
+                    self.glyphs.bar_full.repeat(filled),
+                    self.glyphs
+                        .bar_empty
+                        .repeat(BONUS_BAR_WIDTH as usize - filled)
+                )
+            }
+            None => " ".repeat(BONUS_BAR_WIDTH as usize + 1),
+        };
+    }
+
+    // Only once it doubles the points, padded to blank out a lapsed one
+    pub fn update_combo(&mut self, multiplier: Option<u32>) {
+        self.combo = match multiplier.filter(|&multiplier| multiplier > 1) {
+            Some(multiplier) => format!("x{}", multiplier),
+            None => "  ".to_string(),
+        };
+    }
+
+    // Padded like the player name, "10/8" can go back to "9/8" after an undo,
+    // up to the elapsed time next to it
+    pub fn update_speed(&mut self, label: &str) {
+        let width = MODE_COLUMN as usize - 1;
+        self.speed_row.update(format!("{:<width$.width$}", label));
+    }
+
+    // Pads to the inner width so a shorter name overwrites a longer one
+    // Cut short when the rival's score takes the other half of the row
+    pub fn update_player(&mut self, player_name: &str) {
+        let inner_width = match self.rival_row {
+            Some(_) => MODE_COLUMN as usize - 1,
+            None => self.width.saturating_sub(3) as usize,
+        };
+        let name = truncate(player_name, inner_width, self.glyphs.ellipsis);
+        self.player_row.data = pad(&name, inner_width);
+    }
+
+    // Titled P2 for a second player's snake
+    pub fn update_rival(&mut self, rival: Option<&Rival>) {
+        let Some(rival) = rival else {
+            return;
+        };
+        // Up to the border, keeping one column clear of it like the name
+        let width = self.width.saturating_sub(MODE_COLUMN + 3) as usize;
+        let score = pad(
+            &compact_number(rival.score, width, self.glyphs.ellipsis),
+            width,
+        );
+        if self.rival_row.is_none() {
+            let x = self.x + MODE_COLUMN;
+            let title = if rival.second_player { "P2" } else { "RIVAL" };
+            self.rival_row = Some(DynamicInfoRow::new(title, score.clone(), x, 0));
+            let name = self.player_row.data.trim_end().to_string();
+            self.update_player(&name);
+        }
+        if let Some(row) = &mut self.rival_row {
+            row.update(score);
+        }
+    }
+
+    pub fn queue_borders_and_corners(
+        &self,
+        stdout: &mut impl Write,
+        theme: &Theme,
+    ) -> std::io::Result<()> {
         // Draw vertical borders
         for y in 0..self.height {
             queue!(
                 stdout,
                 cursor::MoveTo(self.x, y),
-                style::PrintStyledContent("│".with(theme::SURFACE))
+                style::PrintStyledContent(self.glyphs.vertical.with(theme.surface))
             )?;
             queue!(
                 stdout,
                 cursor::MoveTo(self.x + self.width, y),
-                style::PrintStyledContent("│".with(theme::SURFACE))
+                style::PrintStyledContent(self.glyphs.vertical.with(theme.surface))
             )?;
         }
 
@@ -77,12 +418,12 @@ impl SidePanel {
             queue!(
                 stdout,
                 cursor::MoveTo(x, 0),
-                style::PrintStyledContent("─".with(theme::SURFACE))
+                style::PrintStyledContent(self.glyphs.horizontal.with(theme.surface))
             )?;
             queue!(
                 stdout,
                 cursor::MoveTo(x, self.height - 1),
-                style::PrintStyledContent("─".with(theme::SURFACE))
+                style::PrintStyledContent(self.glyphs.horizontal.with(theme.surface))
             )?;
         }
 
@@ -90,26 +431,112 @@ impl SidePanel {
         queue!(
             stdout,
             cursor::MoveTo(self.x, 0),
-            style::PrintStyledContent("┌".with(theme::SURFACE))
+            style::PrintStyledContent(self.glyphs.top_left.with(theme.surface))
         )?;
         queue!(
             stdout,
             cursor::MoveTo(self.x + self.width, 0),
-            style::PrintStyledContent("┐".with(theme::SURFACE))
+            style::PrintStyledContent(self.glyphs.top_right.with(theme.surface))
         )?;
         queue!(
             stdout,
             cursor::MoveTo(self.x, self.height - 1),
-            style::PrintStyledContent("└".with(theme::SURFACE))
+            style::PrintStyledContent(self.glyphs.bottom_left.with(theme.surface))
         )?;
         queue!(
             stdout,
             cursor::MoveTo(self.x + self.width, self.height - 1),
-            style::PrintStyledContent("┘".with(theme::SURFACE))
+            style::PrintStyledContent(self.glyphs.bottom_right.with(theme.surface))
         )?;
 
         Ok(())
     }
+
+    // Blanks the whole panel, borders too, so nothing of it stays on screen
+    // while hidden
+    pub fn clear(&self, stdout: &mut impl Write) -> std::io::Result<()> {
+        let blank = " ".repeat(self.width as usize + 1);
+        for y in 0..self.height {
+            queue!(stdout, cursor::MoveTo(self.x, y), style::Print(&blank))?;
+        }
+        Ok(())
+    }
+}
+
+// The side panel's essentials as lines under the board, for terminals too
+// narrow for the panel
+#[derive(Debug)]
+pub struct StatusBar {
+    // The first of its rows, and as wide as the board
+    pub y: u16,
+    pub width: u16,
+    pub player: String,
+    pub score: u32,
+    pub high_score: u32,
+    pub elapsed: Duration,
+}
+
+impl StatusBar {
+    pub fn new(y: u16, width: u16, player: String, high_score: u32) -> Self {
+        Self {
+            y,
+            width,
+            player,
+            score: 0,
+            high_score,
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    pub fn update_score(&mut self, score: u32) {
+        self.score = score;
+    }
+
+    pub fn update_elapsed(&mut self, elapsed: Duration) {
+        self.elapsed = elapsed;
+    }
+
+    pub fn update_player(&mut self, player_name: &str) {
+        self.player = player_name.to_string();
+    }
+
+    // The score and the best so far, then who is playing and for how long,
+    // the time kept in view when a long name has to be cut
+    pub fn lines(&self) -> [String; 2] {
+        let width = self.width as usize;
+        let score = format!("SCORE {}  MAX {}", self.score, self.high_score);
+        let time = format_duration(self.elapsed);
+        let room = width.saturating_sub(time.chars().count() + 1);
+        let player = truncate(&self.player, room, "");
+        [
+            score.chars().take(width).collect(),
+            truncate(&format!("{} {}", pad(&player, room), time), width, ""),
+        ]
+    }
+
+    // Each line cleared first, a shorter value leaves nothing behind
+    pub fn queue(&self, stdout: &mut impl Write) -> std::io::Result<()> {
+        for (line, y) in self.lines().iter().zip(self.y..) {
+            queue!(
+                stdout,
+                cursor::MoveTo(0, y),
+                terminal::Clear(terminal::ClearType::CurrentLine),
+                style::PrintStyledContent(line.as_str().white())
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn clear(&self, stdout: &mut impl Write) -> std::io::Result<()> {
+        for y in self.y..self.y + self.lines().len() as u16 {
+            queue!(
+                stdout,
+                cursor::MoveTo(0, y),
+                terminal::Clear(terminal::ClearType::CurrentLine)
+            )?;
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
@@ -134,7 +561,7 @@ impl<T: std::fmt::Display> DynamicInfoRow<T> {
         self.data = data;
     }
 
-    pub fn queue(&self, stdout: &mut std::io::Stdout) -> std::io::Result<()> {
+    pub fn queue(&self, stdout: &mut impl Write) -> std::io::Result<()> {
         queue!(
             stdout,
             cursor::MoveTo(self.x_offset + 2, self.y_position),
@@ -167,7 +594,7 @@ impl<T: std::fmt::Display> StaticInfoRow<T> {
         }
     }
 
-    pub fn queue(&self, stdout: &mut std::io::Stdout) -> std::io::Result<()> {
+    pub fn queue(&self, stdout: &mut impl Write) -> std::io::Result<()> {
         queue!(
             stdout,
             cursor::MoveTo(self.x_offset + 2, self.y_position),
@@ -188,53 +615,77 @@ pub struct InputInfoRow {
     pub label: String,
     pub value: String,
     pub cursor_position: usize,
+    pub focused: bool,
 }
 
 impl InputInfoRow {
-    pub fn new(x: u16, y: u16, label: &str) -> Self {
+    // The cursor starts after the initial value, ready to edit it
+    pub fn new(x: u16, y: u16, label: &str, value: Option<&str>) -> Self {
+        let value = value.unwrap_or_default().to_string();
         Self {
             x,
             y,
             label: label.to_string(),
-            value: String::new(),
-            cursor_position: 0,
+            cursor_position: value.chars().count(),
+            value,
+            focused: true,
+        }
+    }
+
+    // The value with trailing blanks dropped, `default` if nothing is left
+    pub fn value_or(&self, default: &str) -> String {
+        match self.value.trim_end() {
+            "" => default.to_string(),
+            value => value.to_string(),
         }
     }
 
-    pub fn queue(&self, stdout: &mut std::io::Stdout) -> std::io::Result<()> {
+    // Where the character at `cursor_position` starts in `value`
+    fn byte_index(&self, cursor_position: usize) -> usize {
+        self.value
+            .char_indices()
+            .nth(cursor_position)
+            .map_or(self.value.len(), |(index, _)| index)
+    }
+
+    pub fn queue(&self, stdout: &mut impl Write, glyphs: &GlyphSet) -> std::io::Result<()> {
         queue!(
             stdout,
             cursor::MoveTo(self.x, self.y),
             terminal::Clear(terminal::ClearType::CurrentLine), // Clear the line first
             Print(format!("{}: ", self.label)),
-            Print(&self.value),
-            cursor::MoveTo(
-                self.x + self.label.len() as u16 + 2 + self.cursor_position as u16,
-                self.y,
-            ),
-            Print("▎")
+            Print(&self.value)
         )?;
+        if self.focused {
+            queue!(
+                stdout,
+                cursor::MoveTo(
+                    self.x + self.label.len() as u16 + 2 + self.cursor_position as u16,
+                    self.y,
+                ),
+                Print(glyphs.cursor)
+            )?;
+        }
         Ok(())
     }
 
-    pub fn handle_input(&mut self, key: KeyCode) {
-        match key {
-            KeyCode::Char(c) => {
-                self.value.insert(self.cursor_position, c);
+    pub fn handle_input(&mut self, command: InputCommand) {
+        let length = self.value.chars().count();
+        match command {
+            InputCommand::Raw(c) if length < MAX_NAME_LENGTH && allowed_in_name(c) => {
+                let index = self.byte_index(self.cursor_position);
+                self.value.insert(index, c);
                 self.cursor_position += 1;
             }
-            KeyCode::Backspace if self.cursor_position > 0 => {
-                // Fix: First store the target position
-                let target_pos = self.cursor_position - 1;
-                // Then remove the character at that position
-                self.value.remove(target_pos);
-                // Finally update cursor
-                self.cursor_position = target_pos;
+            InputCommand::MenuNav(MenuNav::Erase) if self.cursor_position > 0 => {
+                self.cursor_position -= 1;
+                let index = self.byte_index(self.cursor_position);
+                self.value.remove(index);
             }
-            KeyCode::Left if self.cursor_position > 0 => {
+            InputCommand::Turn(Direction::Left) if self.cursor_position > 0 => {
                 self.cursor_position -= 1;
             }
-            KeyCode::Right if self.cursor_position < self.value.len() => {
+            InputCommand::Turn(Direction::Right) if self.cursor_position < length => {
                 self.cursor_position += 1;
             }
             _ => {}
@@ -242,6 +693,75 @@ impl InputInfoRow {
     }
 }
 
+// One of a fixed set of options, changed with Left/Right while focused
+pub struct Selector<T: Copy + std::fmt::Display> {
+    pub x: u16,
+    pub y: u16,
+    pub label: String,
+    pub options: Vec<T>,
+    pub index: usize,
+    pub focused: bool,
+}
+
+impl<T: Copy + PartialEq + std::fmt::Display> Selector<T> {
+    pub fn new(x: u16, y: u16, label: &str, options: Vec<T>, selected: T) -> Self {
+        let index = options
+            .iter()
+            .position(|option| *option == selected)
+            .unwrap_or(0);
+        Self {
+            x,
+            y,
+            label: label.to_string(),
+            options,
+            index,
+            focused: false,
+        }
+    }
+
+    pub fn value(&self) -> T {
+        self.options[self.index]
+    }
+
+    // Left as it is when `value` is not one of the options
+    pub fn select(&mut self, value: T) {
+        if let Some(index) = self.options.iter().position(|option| *option == value) {
+            self.index = index;
+        }
+    }
+
+    pub fn queue(&self, stdout: &mut impl Write, glyphs: &GlyphSet) -> std::io::Result<()> {
+        let (left, right) = if self.focused {
+            (glyphs.previous, glyphs.next)
+        } else {
+            (" ", " ")
+        };
+        queue!(
+            stdout,
+            cursor::MoveTo(self.x, self.y),
+            terminal::Clear(terminal::ClearType::UntilNewLine),
+            Print(format!(
+                "{}: {} {} {}",
+                self.label,
+                left,
+                self.value(),
+                right
+            ))
+        )
+    }
+
+    // Stops at both ends rather than wrapping around
+    pub fn handle_input(&mut self, command: InputCommand) {
+        match command {
+            InputCommand::Turn(Direction::Left) if self.index > 0 => self.index -= 1,
+            InputCommand::Turn(Direction::Right) if self.index + 1 < self.options.len() => {
+                self.index += 1
+            }
+            _ => {}
+        }
+    }
+}
+
 pub struct Button {
     pub x: u16,
     pub y: u16,
@@ -259,40 +779,246 @@ impl Button {
         }
     }
 
-    pub fn queue(&self, stdout: &mut std::io::Stdout) -> std::io::Result<()> {
-        let border = "─".repeat(self.label.len() + 2);
+    pub fn queue(&self, stdout: &mut impl Write, glyphs: &GlyphSet) -> std::io::Result<()> {
+        let border = glyphs.horizontal.repeat(self.label.len() + 2);
+        let top = format!("{}{}{}", glyphs.top_left, border, glyphs.top_right);
+        let middle = format!("{} {} {}", glyphs.vertical, self.label, glyphs.vertical);
+        let bottom = format!("{}{}{}", glyphs.bottom_left, border, glyphs.bottom_right);
 
         if self.selected {
             queue!(
                 stdout,
                 cursor::MoveTo(self.x - 2, self.y),
-                Print(format!("> ┌{}┐", border)),
+                Print(format!("> {}", top)),
                 cursor::MoveTo(self.x - 2, self.y + 1),
-                Print(format!("  │ {} │", self.label)),
+                Print(format!("  {}", middle)),
                 cursor::MoveTo(self.x - 2, self.y + 2),
-                Print(format!("  └{}┘ <", border))
+                Print(format!("  {} <", bottom))
             )?;
         } else {
             queue!(
                 stdout,
                 cursor::MoveTo(self.x - 2, self.y),
-                Print(format!("  ┌{}┐", border)), // Added 2 spaces to align with selected state
+                Print(format!("  {}", top)), // Added 2 spaces to align with selected state
                 cursor::MoveTo(self.x - 2, self.y + 1),
-                Print(format!("  │ {} │", self.label)),
+                Print(format!("  {}", middle)),
                 cursor::MoveTo(self.x - 2, self.y + 2),
-                Print(format!("  └{}┘  ", border)) // Added 2 spaces to clear the '<'
+                Print(format!("  {}  ", bottom)) // Added 2 spaces to clear the '<'
             )?;
         }
         Ok(())
     }
 }
 
+// Letters, digits, spaces and ASCII punctuation that draw one cell wide, no
+// control characters, emoji or fullwidth and CJK letters
+fn allowed_in_name(c: char) -> bool {
+    (c.is_alphanumeric() || c.is_ascii_graphic() || c == ' ') && c.width() == Some(1)
+}
+
+// What the menu would have let through, for names from --name, the last-name
+// file or a run card
+pub fn sanitize_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .filter(|&c| allowed_in_name(c))
+        .take(MAX_NAME_LENGTH)
+        .collect();
+    name.trim().to_string()
+}
+
+// The three lines of help under the info rows
+fn controls(bindings: &KeyBindings) -> [String; 3] {
+    [
+        format!(
+            "'{}' stop, '{}' undo",
+            bindings.label(InputCommand::Pause),
+            bindings.label(InputCommand::Undo)
+        ),
+        format!("'{}' hides this", bindings.label(InputCommand::TogglePanel)),
+        format!("'{}' to exit", bindings.label(InputCommand::Quit)),
+    ]
+}
+
+// At most `width` terminal cells, ending in the ellipsis when some had to go.
+// Cut between characters so a name is never split inside one, and wide ones
+// like emoji or CJK count for the two cells they take.
+pub fn truncate(text: &str, width: usize, ellipsis: &str) -> String {
+    if text.width() <= width {
+        return text.to_string();
+    }
+    let kept = take_cells(text, width.saturating_sub(ellipsis.width()));
+    // Not "the …" when the cut falls after a space
+    let kept = format!("{}{}", kept.trim_end(), ellipsis);
+    take_cells(&kept, width).to_string()
+}
+
+// A count in at most `width` cells: as it is when it fits, otherwise in the
+// largest of thousands, millions or billions that fits, "12K" or "1.2M",
+// rounded down, and cut short with the ellipsis when not even that does
+fn compact_number(count: u32, width: usize, ellipsis: &str) -> String {
+    let digits = count.to_string();
+    if digits.len() <= width {
+        return digits;
+    }
+    [(1_000_000_000, 'B'), (1_000_000, 'M'), (1_000, 'K')]
+        .into_iter()
+        .filter(|&(unit, _)| count >= unit)
+        .flat_map(|(unit, suffix)| {
+            let whole = count / unit;
+            let tenths = count % unit / (unit / 10);
+            let decimal = (whole < 10).then(|| format!("{}.{}{}", whole, tenths, suffix));
+            decimal
+                .into_iter()
+                .chain(iter::once(format!("{}{}", whole, suffix)))
+        })
+        .find(|short| short.len() <= width)
+        .unwrap_or_else(|| truncate(&digits, width, ellipsis))
+}
+
+// Filled with spaces up to `width` cells, which `{:<width$}` counts as
+// characters
+pub fn pad(text: &str, width: usize) -> String {
+    format!("{}{}", text, " ".repeat(width.saturating_sub(text.width())))
+}
+
+// The longest start of `text` that fits in `width` cells
+fn take_cells(text: &str, width: usize) -> &str {
+    let mut used = 0;
+    let end = text
+        .char_indices()
+        .find(|&(_, c)| {
+            used += c.width().unwrap_or(0);
+            used > width
+        })
+        .map_or(text.len(), |(index, _)| index);
+    &text[..end]
+}
+
+// MM:SS of time gone by, only whole seconds count, H:MM:SS once there are
+// hours
+pub fn format_duration(elapsed: Duration) -> String {
+    let seconds = elapsed.as_secs();
+    match seconds / 3_600 {
+        0 => format!("{:02}:{:02}", seconds / 60, seconds % 60),
+        hours => format!("{}:{:02}:{:02}", hours, seconds / 60 % 60, seconds % 60),
+    }
+}
+
+pub fn clock(time_left: Duration) -> String {
+    let seconds = time_left.as_millis().div_ceil(1000);
+    format!("{:02}:{:02}", seconds / 60, seconds % 60)
+}
+
+// A single highlighted line centered over the top border of the board
+pub fn queue_banner(
+    stdout: &mut impl Write,
+    board_width: u16,
+    text: &str,
+    theme: &Theme,
+) -> std::io::Result<()> {
+    let x = board_width.saturating_sub(text.chars().count() as u16) / 2;
+    queue!(
+        stdout,
+        cursor::MoveTo(x, 0),
+        PrintStyledContent(text.with(theme.background).on(theme.accent).bold())
+    )
+}
+
+// Counted down in the middle of the board before the first tick, each shown
+// for `COUNTDOWN_STEP`
+const COUNTDOWN: [&str; 4] = [" 3 ", " 2 ", " 1 ", " GO! "];
+const COUNTDOWN_STEP: Duration = Duration::from_millis(600);
+
+// 3-2-1 over the board, which must already be drawn, so the player can get
+// their hands ready. Any key skips the rest of it.
+pub fn countdown(
+    stdout: &mut impl Write,
+    board_width: u16,
+    height: u16,
+    theme: &Theme,
+) -> std::io::Result<()> {
+    for text in COUNTDOWN {
+        let x = board_width.saturating_sub(text.chars().count() as u16) / 2;
+        queue!(
+            stdout,
+            cursor::MoveTo(x, height / 2),
+            PrintStyledContent(text.with(theme.background).on(theme.accent).bold())
+        )?;
+        stdout.flush()?;
+
+        let deadline = Instant::now() + COUNTDOWN_STEP;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+            // Not the release of the key that started the game, on terminals
+            // that report releases
+            if event::poll(remaining)? {
+                if let Event::Key(key) = event::read()? {
+                    if key.kind != KeyEventKind::Release {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn queue_frame(
+    stdout: &mut impl Write,
+    total_width: u16,
+    height: u16,
+    theme: &Theme,
+) -> std::io::Result<()> {
+    for y in 0..height {
+        for x in 0..total_width {
+            queue!(stdout, cursor::MoveTo(x, y))?;
+            if y == 0 || y == height - 1 || x == 0 || x == total_width - 1 {
+                queue!(
+                    stdout,
+                    PrintStyledContent(theme.glyphs.wall.with(theme.surface))
+                )?;
+            } else {
+                queue!(stdout, Print(" "))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MenuResult {
+    pub name: String,
+    // Second player when playing in relay mode
+    pub partner_name: Option<String>,
+    pub difficulty: Difficulty,
+    pub mode: GameMode,
+    pub arena: Arena,
+    // Chosen on the settings screen
+    pub walls: WallMode,
+    pub theme: Theme,
+}
+
+// What the player chose to do, EXIT and ESC both quit
+#[derive(Debug, Clone, PartialEq)]
+pub enum MenuOutcome {
+    Play(MenuResult),
+    Quit,
+}
+
 pub fn show(
-    stdout: &mut std::io::Stdout,
+    stdout: &mut impl Write,
     game_width: u16,
     panel_width: u16,
     height: u16,
-) -> std::io::Result<Option<String>> {
+    // Pre-filled choices, a partner name turns on the relay field
+    initial: MenuResult,
+    choose_arena: bool,
+) -> std::io::Result<MenuOutcome> {
+    let relay = initial.partner_name.is_some();
     let total_width = game_width + panel_width;
     terminal::enable_raw_mode()?;
 
@@ -302,28 +1028,40 @@ pub fn show(
         cursor::Hide
     )?;
 
-    // Draw complete frame
-    for y in 0..height {
-        for x in 0..total_width {
-            queue!(stdout, cursor::MoveTo(x, y))?;
-            if y == 0 || y == height - 1 || x == 0 || x == total_width - 1 {
-                queue!(stdout, PrintStyledContent("█".with(theme::SURFACE)))?;
-            } else {
-                queue!(stdout, Print(" "))?;
-            }
-        }
-    }
+    let mut settings = initial.clone();
+    queue_frame(stdout, total_width, height, &settings.theme)?;
 
-    let mut name_input = InputInfoRow::new(4, 2, "Your name");
+    let mut name_input = InputInfoRow::new(4, 2, "Your name", Some(&initial.name));
+    let mut partner_input = initial.partner_name.map(|name| {
+        let mut input = InputInfoRow::new(4, 3, "Player B", Some(&name));
+        input.focused = false;
+        input
+    });
+    let mut mode_selector = Selector::new(4, 4, "Mode", GameMode::ALL.to_vec(), initial.mode);
+    let arena = initial.arena;
+    let mut arena_selector =
+        choose_arena.then(|| Selector::new(4, 5, "Arena", Arena::choices(arena), arena));
     let center_x = total_width / 2;
-    let mut play_button = Button::new(center_x - 10, height / 2, "PLAY", true);
-    let mut exit_button = Button::new(center_x + 5, height / 2, "EXIT", false);
-    let mut selected_button = 0;
+    let mut play_button = Button::new(center_x - 16, height / 2, "PLAY", true);
+    let mut settings_button = Button::new(center_x - 5, height / 2, "SETTINGS", false);
+    let mut exit_button = Button::new(center_x + 10, height / 2, "EXIT", false);
+    // Tab cycles through PLAY, SETTINGS, EXIT, the mode and the arena
+    let mut focus = 0;
+    let fields = if arena_selector.is_some() { 5 } else { 4 };
 
     loop {
-        name_input.queue(stdout)?;
-        play_button.queue(stdout)?;
-        exit_button.queue(stdout)?;
+        let glyphs = settings.theme.glyphs;
+        name_input.queue(stdout, glyphs)?;
+        if let Some(partner_input) = &partner_input {
+            partner_input.queue(stdout, glyphs)?;
+        }
+        mode_selector.queue(stdout, glyphs)?;
+        if let Some(arena_selector) = &arena_selector {
+            arena_selector.queue(stdout, glyphs)?;
+        }
+        play_button.queue(stdout, glyphs)?;
+        settings_button.queue(stdout, glyphs)?;
+        exit_button.queue(stdout, glyphs)?;
 
         // Help text aligned left
         queue!(
@@ -333,29 +1071,798 @@ pub fn show(
             cursor::MoveTo(4, height / 2 + 4),
             Print("ENTER to select"),
             cursor::MoveTo(4, height / 2 + 5),
-            Print("Press TAB to switch buttons"),
+            Print("TAB to switch, LEFT/RIGHT to pick"),
             cursor::MoveTo(4, height / 2 + 6),
             Print("ESC to exit")
         )?;
+        if relay {
+            queue!(
+                stdout,
+                cursor::MoveTo(4, height / 2 + 7),
+                Print("UP/DOWN to switch players")
+            )?;
+        }
 
         stdout.flush()?;
 
-        if let Event::Key(key_event) = event::read()? {
-            match key_event.code {
-                KeyCode::Esc => return Ok(None),
-                KeyCode::Tab => {
-                    selected_button = 1 - selected_button;
-                    play_button.selected = selected_button == 0;
-                    exit_button.selected = selected_button == 1;
+        let Event::Key(key_event) = event::read()? else {
+            continue;
+        };
+        if let Some(command) = keyboard::translate(key_event, true) {
+            match command {
+                InputCommand::Quit => return Ok(MenuOutcome::Quit),
+                InputCommand::MenuNav(MenuNav::NextField) => {
+                    focus = (focus + 1) % fields;
+                    play_button.selected = focus == 0;
+                    settings_button.selected = focus == 1;
+                    exit_button.selected = focus == 2;
+                    mode_selector.focused = focus == 3;
+                    if let Some(arena_selector) = &mut arena_selector {
+                        arena_selector.focused = focus == 4;
+                    }
+                }
+                InputCommand::Turn(Direction::Left | Direction::Right) if focus >= 3 => {
+                    match &mut arena_selector {
+                        Some(arena_selector) if focus == 4 => arena_selector.handle_input(command),
+                        _ => mode_selector.handle_input(command),
+                    }
                 }
-                KeyCode::Enter => {
-                    return Ok(match selected_button {
-                        0 => Some(name_input.value.clone()),
-                        _ => None,
+                InputCommand::Turn(Direction::Up | Direction::Down) if partner_input.is_some() => {
+                    if let Some(partner_input) = &mut partner_input {
+                        name_input.focused = !name_input.focused;
+                        partner_input.focused = !name_input.focused;
+                    }
+                }
+                InputCommand::MenuNav(MenuNav::Confirm) if focus == 1 => {
+                    settings_screen(stdout, total_width, height, &mut settings)?;
+                    // Back to the menu, in the theme that was picked
+                    execute!(stdout, terminal::Clear(terminal::ClearType::All))?;
+                    queue_frame(stdout, total_width, height, &settings.theme)?;
+                }
+                InputCommand::MenuNav(MenuNav::Confirm) => {
+                    // Confirming the mode or arena starts the game with it
+                    return Ok(match focus {
+                        2 => MenuOutcome::Quit,
+                        _ => MenuOutcome::Play(MenuResult {
+                            name: name_input.value_or(DEFAULT_NAME),
+                            partner_name: partner_input
+                                .map(|input| input.value_or(DEFAULT_PARTNER)),
+                            mode: mode_selector.value(),
+                            arena: arena_selector
+                                .as_ref()
+                                .map_or(Arena::Open, |selector| selector.value()),
+                            ..settings
+                        }),
                     });
                 }
-                key => name_input.handle_input(key),
+                command => match &mut partner_input {
+                    Some(partner_input) if partner_input.focused => {
+                        partner_input.handle_input(command)
+                    }
+                    _ => name_input.handle_input(command),
+                },
+            }
+        }
+    }
+}
+
+// Walls, difficulty and theme, picked with the arrow keys. ENTER keeps the
+// changes, ESC leaves `settings` as it was.
+fn settings_screen(
+    stdout: &mut impl Write,
+    total_width: u16,
+    height: u16,
+    settings: &mut MenuResult,
+) -> std::io::Result<()> {
+    execute!(stdout, terminal::Clear(terminal::ClearType::All))?;
+    let mut walls = Selector::new(4, 2, "Walls", WallMode::ALL.to_vec(), settings.walls);
+    let mut difficulty = Selector::new(
+        4,
+        3,
+        "Difficulty",
+        Difficulty::ALL.to_vec(),
+        settings.difficulty,
+    );
+    let mut theme = Selector::new(
+        4,
+        4,
+        "Theme",
+        Theme::choices(settings.theme),
+        settings.theme,
+    );
+    walls.focused = true;
+    let mut focus = 0;
+    let mut redraw_frame = true;
+
+    loop {
+        // The theme being picked is the one drawn, as a preview
+        let shown = theme.value();
+        if redraw_frame {
+            queue_frame(stdout, total_width, height, &shown)?;
+            redraw_frame = false;
+        }
+        walls.queue(stdout, shown.glyphs)?;
+        difficulty.queue(stdout, shown.glyphs)?;
+        theme.queue(stdout, shown.glyphs)?;
+        queue!(
+            stdout,
+            cursor::MoveTo(4, height / 2 + 3),
+            Print("UP/DOWN to switch, LEFT/RIGHT to pick"),
+            cursor::MoveTo(4, height / 2 + 4),
+            Print("ENTER to keep, ESC to go back")
+        )?;
+        stdout.flush()?;
+
+        let Event::Key(key_event) = event::read()? else {
+            continue;
+        };
+        match keyboard::translate(key_event, true) {
+            Some(InputCommand::Quit) => return Ok(()),
+            Some(InputCommand::MenuNav(MenuNav::Confirm)) => {
+                settings.walls = walls.value();
+                settings.difficulty = difficulty.value();
+                settings.theme = theme.value();
+                return Ok(());
+            }
+            Some(command @ InputCommand::Turn(Direction::Left | Direction::Right)) => match focus {
+                0 => walls.handle_input(command),
+                // Each difficulty comes with its own walls, which can still
+                // be changed after
+                1 => {
+                    difficulty.handle_input(command);
+                    walls.select(difficulty.value().preset().walls);
+                }
+                _ => {
+                    theme.handle_input(command);
+                    redraw_frame = theme.value() != shown;
+                }
+            },
+            Some(InputCommand::Turn(direction @ (Direction::Up | Direction::Down))) => {
+                focus = match direction {
+                    Direction::Up => (focus + 2) % 3,
+                    _ => (focus + 1) % 3,
+                };
+                walls.focused = focus == 0;
+                difficulty.focused = focus == 1;
+                theme.focused = focus == 2;
+            }
+            Some(InputCommand::MenuNav(MenuNav::NextField)) => {
+                focus = (focus + 1) % 3;
+                walls.focused = focus == 0;
+                difficulty.focused = focus == 1;
+                theme.focused = focus == 2;
+            }
+            _ => {}
+        }
+    }
+}
+
+pub fn show_game_over(
+    stdout: &mut impl Write,
+    total_width: u16,
+    height: u16,
+    outcome: GameOutcome,
+    state: &GameState,
+    details: &[String],
+    theme: &Theme,
+) -> std::io::Result<()> {
+    queue_frame(stdout, total_width, height, theme)?;
+
+    let clock_ran_out = match state.time_limit {
+        Some(limit) => format!("The {} clock ran out", clock(limit)),
+        None => "The clock ran out".to_string(),
+    };
+    let (title, color, reason) = match outcome {
+        GameOutcome::Win => ("YOU WIN!", theme.primary, "The snake filled the board"),
+        GameOutcome::WallCollision => ("GAME OVER", theme.secondary, "You crashed into the wall"),
+        GameOutcome::ObstacleCollision => {
+            ("GAME OVER", theme.secondary, "You crashed into an obstacle")
+        }
+        GameOutcome::SelfCollision => ("GAME OVER", theme.secondary, "You bit your own tail"),
+        GameOutcome::RivalCollision if state.is_two_player() => {
+            ("GAME OVER", theme.secondary, "You ran into player 2")
+        }
+        GameOutcome::RivalCollision => ("GAME OVER", theme.secondary, "You ran into the rival"),
+        GameOutcome::SecondPlayerCrashed => ("PLAYER 1 WINS", theme.primary, "Player 2 crashed"),
+        GameOutcome::BothCrashed => ("DRAW", theme.text, "Both snakes crashed"),
+        GameOutcome::TimesUp => ("TIME'S UP", theme.primary, clock_ran_out.as_str()),
+    };
+    // A crash of the first player's is the second player's win
+    let title = match outcome {
+        outcome if outcome.is_collision() && state.is_two_player() => "PLAYER 2 WINS",
+        _ => title,
+    };
+    let score = match state.rival.as_ref().filter(|rival| rival.second_player) {
+        Some(rival) => format!("SCORE: {} - {}", state.score, rival.score),
+        None => format!("SCORE: {}", state.score),
+    };
+    let score = format!("{}  TIME: {}", score, format_duration(state.game_time));
+    let seed = format!("SEED: {}", state.seed);
+    let hint = "Press any key to exit";
+
+    let center = |text: &str| total_width.saturating_sub(text.chars().count() as u16) / 2;
+    // Extra detail lines push the whole block up to keep it centered
+    let top = (height / 2).saturating_sub(2 + details.len() as u16);
+    queue!(
+        stdout,
+        cursor::MoveTo(center(title), top),
+        PrintStyledContent(title.with(color).bold()),
+        cursor::MoveTo(center(reason), top + 2),
+        Print(reason),
+        cursor::MoveTo(center(&score), top + 3),
+        Print(&score),
+        cursor::MoveTo(center(&seed), top + 4),
+        PrintStyledContent(seed.as_str().with(theme.inactive))
+    )?;
+    let mut y = top + 5;
+    for detail in details {
+        queue!(stdout, cursor::MoveTo(center(detail), y), Print(detail))?;
+        y += 1;
+    }
+    queue!(
+        stdout,
+        cursor::MoveTo(center(hint), y + 1),
+        PrintStyledContent(hint.with(theme.inactive))
+    )?;
+    stdout.flush()?;
+
+    loop {
+        if let Event::Key(_) = event::read()? {
+            return Ok(());
+        }
+    }
+}
+
+// Asks for the name to put on the leaderboard at `place`, starting from the
+// one played under. None when skipped with ESC.
+pub fn confirm_name(
+    stdout: &mut impl Write,
+    total_width: u16,
+    height: u16,
+    name: &str,
+    place: usize,
+    theme: &Theme,
+) -> std::io::Result<Option<String>> {
+    queue_frame(stdout, total_width, height, theme)?;
+    let title = format!("#{} ON THE LEADERBOARD", place + 1);
+    let hint = "ENTER to save, ESC to skip";
+    let center = |text: &str| total_width.saturating_sub(text.chars().count() as u16) / 2;
+    let top = (height / 2).saturating_sub(2);
+    queue!(
+        stdout,
+        cursor::MoveTo(center(&title), top),
+        PrintStyledContent(title.as_str().with(theme.primary).bold()),
+        cursor::MoveTo(center(hint), top + 4),
+        PrintStyledContent(hint.with(theme.inactive))
+    )?;
+    let mut name_input = InputInfoRow::new(4, top + 2, "Your name", Some(name));
+
+    loop {
+        name_input.queue(stdout, theme.glyphs)?;
+        stdout.flush()?;
+        let Event::Key(key_event) = event::read()? else {
+            continue;
+        };
+        match keyboard::translate(key_event, true) {
+            Some(InputCommand::Quit) => return Ok(None),
+            Some(InputCommand::MenuNav(MenuNav::Confirm)) => {
+                return Ok(Some(name_input.value_or(DEFAULT_NAME)))
             }
+            Some(command) => name_input.handle_input(command),
+            None => {}
+        }
+    }
+}
+
+// The leaderboard as a table, best first, with `highlight` picked out. Rows
+// that don't fit the board are left off.
+pub fn queue_leaderboard(
+    stdout: &mut impl Write,
+    total_width: u16,
+    height: u16,
+    entries: &[Entry],
+    highlight: Option<usize>,
+    theme: &Theme,
+) -> std::io::Result<()> {
+    queue_frame(stdout, total_width, height, theme)?;
+    let title = "LEADERBOARD";
+    let rows = usize::from(height.saturating_sub(5));
+    let hint = if entries.iter().take(rows).any(|entry| entry.imported) {
+        "* from a run card. Press any key to exit"
+    } else {
+        "Press any key to exit"
+    };
+    let center = |text: &str| total_width.saturating_sub(text.chars().count() as u16) / 2;
+    queue!(
+        stdout,
+        cursor::MoveTo(center(title), 1),
+        PrintStyledContent(title.with(theme.primary).bold())
+    )?;
+
+    let lines: Vec<String> = entries
+        .iter()
+        .take(rows)
+        .enumerate()
+        .map(|(place, entry)| {
+            let name = pad(&truncate(&entry.name, 16, theme.glyphs.ellipsis), 16);
+            // Entries from before the time was kept have none
+            let time = entry.time.map_or("--:--".to_string(), format_duration);
+            format!(
+                "{:>2}. {} {:>6}{}{:>7}  {}",
+                place + 1,
+                name,
+                entry.score,
+                if entry.imported { '*' } else { ' ' },
+                time,
+                entry.date
+            )
+        })
+        .collect();
+    let width = lines.iter().map(|line| line.width()).max();
+    let x = center(&" ".repeat(width.unwrap_or(0)));
+    for (place, line) in lines.iter().enumerate() {
+        queue!(stdout, cursor::MoveTo(x, 3 + place as u16))?;
+        if highlight == Some(place) {
+            queue!(
+                stdout,
+                PrintStyledContent(line.as_str().with(theme.accent).bold())
+            )?;
+        } else {
+            queue!(stdout, Print(line))?;
+        }
+    }
+    if lines.is_empty() {
+        let empty = "No scores yet";
+        queue!(stdout, cursor::MoveTo(center(empty), 3), Print(empty))?;
+    }
+    queue!(
+        stdout,
+        cursor::MoveTo(center(hint), height.saturating_sub(2)),
+        PrintStyledContent(hint.with(theme.inactive))
+    )
+}
+
+pub fn leaderboard_screen(
+    stdout: &mut impl Write,
+    total_width: u16,
+    height: u16,
+    entries: &[Entry],
+    highlight: Option<usize>,
+    theme: &Theme,
+) -> std::io::Result<()> {
+    queue_leaderboard(stdout, total_width, height, entries, highlight, theme)?;
+    stdout.flush()?;
+    loop {
+        if let Event::Key(_) = event::read()? {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        game::{EffectKind, Food, Position, COMBO_CAP},
+        screen::{blank_screen, play, render_to_grid},
+    };
+
+    #[test]
+    fn test_a_frame_renders_into_a_buffer() {
+        let state = GameState::with_seed(30, 15, 1);
+        let bindings = KeyBindings::default();
+        let mut panel = SidePanel::new(
+            30,
+            15,
+            20,
+            "Al".to_string(),
+            12,
+            &bindings,
+            &GlyphSet::UNICODE,
+        );
+        panel.update_score(3);
+        panel.update_combo(Some(2));
+        panel.update_speed("1/8");
+        panel.update_fruits([4, 1, 0, 2]);
+        panel.update_effects(&[Effect {
+            kind: EffectKind::SlowMotion,
+            remaining: 30,
+        }]);
+        panel.update_elapsed(Duration::from_millis(83_900));
+        let grid = render_to_grid(&state, &panel);
+
+        let panel_rows: Vec<String> = grid
+            .iter()
+            .map(|row| row.chars().skip(32).collect())
+            .collect();
+        assert_eq!(panel_rows[0], "┌─PLAYER────────────┐");
+        assert_eq!(panel_rows[1], "│ Al                │");
+        // Fruits on the score's title line, the combo after its value
+        assert_eq!(panel_rows[3], "│ SCORE ●4  ♦1  ◗0  │");
+        assert_eq!(panel_rows[4], "│ 3              x2 │");
+        assert_eq!(panel_rows[7], "│ 12                │");
+        // The effects after the speed, the elapsed time next to it
+        assert_eq!(panel_rows[9], "│ SPEED      ELAPSED│");
+        assert_eq!(panel_rows[10], "│ 1/8 ⧗30    01:23  │");
+        assert_eq!(panel_rows[14], "└───────────────────┘");
+
+        // The board is all there, the food on it
+        let food = state.foods[0].position;
+        let food_row: Vec<char> = grid[food.y as usize].chars().collect();
+        assert_eq!(food_row[food.x as usize], '●');
+        assert!(grid[0].starts_with(&"#".repeat(30)));
+    }
+
+    #[test]
+    fn test_length_follows_the_snake_next_to_the_max_score() {
+        let mut state = GameState::with_seed(30, 15, 1);
+        let bindings = KeyBindings::default();
+        let mut panel = SidePanel::new(
+            30,
+            15,
+            20,
+            "Al".to_string(),
+            12,
+            &bindings,
+            &GlyphSet::UNICODE,
+        );
+        for _ in 0..3 {
+            let head = state.snake.head;
+            state.foods[0] = Food::at(Position::new(head.x + 1, head.y));
+            state.next(state.player_action(None));
+        }
+        panel.update_length(state.snake.len() as u32);
+        let row = |grid: &[String], y: usize| -> String { grid[y].chars().skip(32).collect() };
+        let grid = render_to_grid(&state, &panel);
+        assert_eq!(row(&grid, 6), "│ MAX SCORE  LENGTH │");
+        assert_eq!(row(&grid, 7), "│ 12         4      │");
+
+        // The lives have the place to themselves
+        panel.update_lives(3, 3);
+        let grid = render_to_grid(&state, &panel);
+        assert_eq!(row(&grid, 6), "│ MAX SCORE  LIVES  │");
+    }
+
+    #[test]
+    fn test_a_cleared_panel_leaves_nothing_behind() {
+        let bindings = KeyBindings::default();
+        let mut panel = SidePanel::new(
+            30,
+            15,
+            20,
+            "Al".to_string(),
+            12,
+            &bindings,
+            &GlyphSet::UNICODE,
+        );
+        panel.update_score(7);
+        let mut screen = blank_screen(53, 15);
+        let mut output = Vec::new();
+        panel.queue(&mut output, &Theme::default()).unwrap();
+        play(&mut screen, &output);
+        assert!(screen
+            .iter()
+            .all(|row| row[32].0 != ' ' && row[52].0 != ' '));
+
+        let mut output = Vec::new();
+        panel.clear(&mut output).unwrap();
+        play(&mut screen, &output);
+        assert!(screen.iter().flatten().all(|(glyph, _, _)| *glyph == ' '));
+    }
+
+    #[test]
+    fn test_panel_widens_for_long_names_and_bindings() {
+        let mut bindings = KeyBindings::default();
+        assert_eq!(SidePanel::width_for("antonio", &bindings), PANEL_WIDTH);
+        assert_eq!(SidePanel::width_for("antonio-the-great-s", &bindings), 22);
+        assert_eq!(SidePanel::width_for("名前のある人", &bindings), PANEL_WIDTH);
+        assert_eq!(
+            SidePanel::width_for(&"a".repeat(100), &bindings),
+            MAX_PANEL_WIDTH
+        );
+
+        // Long key names no longer run into the border
+        bindings.bind("pause=space").unwrap();
+        bindings.bind("undo=tab").unwrap();
+        assert_eq!(SidePanel::width_for("antonio", &bindings), 26);
+        bindings.bind("undo=backspace").unwrap();
+        assert_eq!(SidePanel::width_for("antonio", &bindings), MAX_PANEL_WIDTH);
+        let panel = SidePanel::new(
+            0,
+            20,
+            MAX_PANEL_WIDTH,
+            String::new(),
+            0,
+            &bindings,
+            &GlyphSet::UNICODE,
+        );
+        assert_eq!(panel.controls[0], "'SPACE' stop, 'BACKSPACE' undo");
+        let panel = SidePanel::new(0, 20, 24, String::new(), 0, &bindings, &GlyphSet::UNICODE);
+        assert_eq!(panel.controls[0], "'SPACE' stop, 'BACKSP…");
+    }
+
+    #[test]
+    fn test_truncate_by_cells() {
+        assert_eq!(truncate("antonio", 10, "…"), "antonio");
+        assert_eq!(truncate("antonio-the-great", 15, "…"), "antonio-the-gr…");
+        assert_eq!(truncate("antonio-the-great", 15, "..."), "antonio-the-...");
+        assert_eq!(truncate("antoñito el grande", 9, "…"), "antoñito…");
+        assert_eq!(truncate("🐍🐍🐍 snake", 4, "…"), "🐍…");
+        assert_eq!(truncate("🐍🐍🐍 snake", 6, "..."), "🐍...");
+        assert_eq!(truncate("名前のある人", 7, "…"), "名前の…");
+        assert_eq!(pad("名前", 6), "名前  ");
+        assert_eq!(truncate("abc", 2, "..."), "..");
+        assert_eq!(truncate("abc", 0, "…"), "");
+        assert_eq!(truncate("the great", 5, "…"), "the…");
+    }
+
+    #[test]
+    fn test_compact_numbers_fit_their_width() {
+        assert_eq!(compact_number(0, 3, "…"), "0");
+        assert_eq!(compact_number(999, 3, "…"), "999");
+        assert_eq!(compact_number(1_000, 3, "…"), "1K");
+        assert_eq!(compact_number(1_250, 4, "…"), "1250");
+        assert_eq!(compact_number(12_345, 4, "…"), "12K");
+        assert_eq!(compact_number(999_999, 4, "…"), "999K");
+        assert_eq!(compact_number(1_234_567, 6, "…"), "1.2M");
+        assert_eq!(compact_number(1_234_567, 3, "…"), "1M");
+        assert_eq!(compact_number(u32::MAX, 6, "…"), "4.2B");
+        assert_eq!(compact_number(u32::MAX, 3, "…"), "4B");
+        assert_eq!(compact_number(u32::MAX, 10, "…"), "4294967295");
+        // Nothing shorter says it, so it is cut
+        assert_eq!(compact_number(123_456, 3, "…"), "12…");
+        assert_eq!(compact_number(123_456, 3, "..."), "...");
+    }
+
+    #[test]
+    fn test_panel_fits_the_numbers_of_a_saturated_game() {
+        let mut state = GameState::with_seed(30, 15, 1);
+        state.score = u32::MAX - 1;
+        state.fruits_eaten = [u32::MAX - 1; FruitKind::ALL.len()];
+        let mut rival = Rival::new(Position::new(3, 3), 1);
+        rival.score = u32::MAX;
+        for _ in 0..3 {
+            let head = state.snake.head;
+            state.foods[0] = Food::at(Position::new(head.x + 1, head.y));
+            state.next(state.player_action(None));
+        }
+        assert_eq!(state.score, u32::MAX);
+        assert_eq!(state.fruits_eaten()[FruitKind::Apple as usize], u32::MAX);
+
+        let bindings = KeyBindings::default();
+        let mut panel = SidePanel::new(
+            30,
+            15,
+            20,
+            "Al".to_string(),
+            u32::MAX,
+            &bindings,
+            &GlyphSet::UNICODE,
+        );
+        panel.update_score(state.score);
+        panel.update_fruits(state.fruits_eaten());
+        panel.update_rival(Some(&rival));
+        let grid = render_to_grid(&state, &panel);
+
+        let panel_rows: Vec<String> = grid
+            .iter()
+            .map(|row| row.chars().skip(32).collect())
+            .collect();
+        assert_eq!(panel_rows[1], "│ Al         4.2B   │");
+        assert_eq!(panel_rows[3], "│ SCORE ●4B ♦4B ◗4B │");
+        assert_eq!(panel_rows[4], "│ 4.2B              │");
+        // Nothing runs over the right border
+        for row in &panel_rows {
+            assert_eq!(row.chars().count(), 21, "{:?}", row);
+            assert!(
+                matches!(row.chars().last(), Some('│' | '┐' | '┘')),
+                "{:?}",
+                row
+            );
+        }
+    }
+
+    #[test]
+    fn test_clock_rounds_up_to_the_second() {
+        assert_eq!(clock(Duration::from_secs(120)), "02:00");
+        assert_eq!(clock(Duration::from_millis(119_001)), "02:00");
+        assert_eq!(clock(Duration::from_millis(119_000)), "01:59");
+        assert_eq!(clock(Duration::from_millis(1)), "00:01");
+        assert_eq!(clock(Duration::ZERO), "00:00");
+    }
+
+    #[test]
+    fn test_elapsed_time_counts_whole_seconds() {
+        assert_eq!(format_duration(Duration::ZERO), "00:00");
+        assert_eq!(format_duration(Duration::from_millis(59_999)), "00:59");
+        assert_eq!(format_duration(Duration::from_secs(60)), "01:00");
+        assert_eq!(format_duration(Duration::from_secs(3_599)), "59:59");
+        assert_eq!(format_duration(Duration::from_secs(3_600)), "1:00:00");
+        assert_eq!(format_duration(Duration::from_secs(6_000)), "1:40:00");
+        assert_eq!(format_duration(Duration::from_secs(36_061)), "10:01:01");
+    }
+
+    #[test]
+    fn test_rival_score_makes_room_next_to_the_name() {
+        let bindings = KeyBindings::default();
+        let mut panel = SidePanel::new(
+            30,
+            15,
+            20,
+            "Bartholomew".to_string(),
+            0,
+            &bindings,
+            &GlyphSet::UNICODE,
+        );
+        panel.update_rival(None);
+        assert!(panel.rival_row.is_none());
+        assert_eq!(panel.player_row.data, "Bartholomew");
+
+        let mut rival = Rival::new(Position::new(3, 3), 1);
+        rival.score = 4;
+        panel.update_rival(Some(&rival));
+        rival.score = 7;
+        panel.update_rival(Some(&rival));
+        assert_eq!(panel.player_row.data, "Bartholom…");
+        let row = panel.rival_row.as_ref().unwrap();
+        assert_eq!((row.title.as_str(), row.data.as_str()), ("RIVAL", "7     "));
+        assert_eq!(row.x_offset, panel.x + MODE_COLUMN);
+
+        let mut two_player = SidePanel::new(
+            30,
+            15,
+            20,
+            "Al".to_string(),
+            0,
+            &bindings,
+            &GlyphSet::UNICODE,
+        );
+        two_player.update_rival(Some(&Rival::second_player(Position::new(3, 3))));
+        assert_eq!(two_player.rival_row.unwrap().title, "P2");
+
+        panel.update_player("Al");
+        assert_eq!(panel.player_row.data, "Al        ");
+    }
+
+    #[test]
+    fn test_combo_shows_once_it_multiplies_and_fits_the_panel() {
+        let bindings = KeyBindings::default();
+        let mut panel = SidePanel::new(
+            30,
+            15,
+            20,
+            "Al".to_string(),
+            0,
+            &bindings,
+            &GlyphSet::UNICODE,
+        );
+        panel.update_combo(Some(1));
+        assert_eq!(panel.combo, "  ");
+        panel.update_combo(Some(COMBO_CAP));
+        assert_eq!(panel.combo, format!("x{}", COMBO_CAP));
+        panel.update_combo(None);
+        assert_eq!(panel.combo, "  ");
+
+        let end = 2 + COMBO_COLUMN + format!("x{}", COMBO_CAP).len() as u16;
+        assert!(end < panel.width);
+    }
+
+    #[test]
+    fn test_leaderboard_table() {
+        let entry = |name: &str, score, time, imported| Entry {
+            name: name.to_string(),
+            score,
+            time,
+            date: "2026-10-17".to_string(),
+            imported,
+        };
+        let entries = [
+            entry(
+                "Bartholomew the Magnificent",
+                120,
+                Some(Duration::from_secs(95)),
+                false,
+            ),
+            entry("Al", 7, None, true),
+        ];
+        let theme = Theme::default();
+        let mut output = Vec::new();
+        queue_leaderboard(&mut output, 50, 15, &entries, Some(1), &theme).unwrap();
+        let mut screen = blank_screen(50, 15);
+        play(&mut screen, &output);
+        let row = |y: usize| -> String {
+            let text: String = screen[y].iter().map(|(glyph, _, _)| glyph).collect();
+            text.trim_matches(['█', ' ']).to_string()
+        };
+
+        assert_eq!(row(1), "LEADERBOARD");
+        assert_eq!(row(3), "1. Bartholomew the…    120   01:35  2026-10-17");
+        // Imported from a run card, starred after the score
+        assert_eq!(row(4), "2. Al                    7*  --:--  2026-10-17");
+        assert_eq!(row(5), "");
+        assert_eq!(row(13), "* from a run card. Press any key to exit");
+        // The new entry stands out
+        let accent = style::SetForegroundColor(theme.accent).to_string();
+        let (_, color, _) = &screen[4][10];
+        assert!(accent.contains(color.as_str()));
+
+        let mut output = Vec::new();
+        queue_leaderboard(&mut output, 50, 15, &[], None, &theme).unwrap();
+        let mut screen = blank_screen(50, 15);
+        play(&mut screen, &output);
+        let text: String = screen[3].iter().map(|(glyph, _, _)| glyph).collect();
+        assert!(text.contains("No scores yet"));
+    }
+
+    #[test]
+    fn test_name_input_caps_the_length() {
+        let mut input = InputInfoRow::new(0, 0, "Your name", Some("antoñito"));
+        assert_eq!(input.cursor_position, 8);
+        for c in "the great".chars() {
+            input.handle_input(InputCommand::Raw(c));
+        }
+        assert_eq!(input.value, "antoñitothe ");
+        assert_eq!(input.value.chars().count(), MAX_NAME_LENGTH);
+        assert_eq!(input.value_or(DEFAULT_NAME), "antoñitothe");
+    }
+
+    #[test]
+    fn test_names_from_anywhere_are_sanitized_alike() {
+        assert_eq!(sanitize_name("antoñito"), "antoñito");
+        assert_eq!(sanitize_name(" Al\n🐍名前ＡＢ\r\t-1 "), "Al-1");
+        assert_eq!(sanitize_name("antonio-the-great"), "antonio-the-");
+        assert_eq!(sanitize_name("🐍🐍"), "");
+    }
+
+    #[test]
+    fn test_name_input_filters_and_edits_by_character() {
+        let mut input = InputInfoRow::new(0, 0, "Your name", None);
+        for c in [
+            'A', '\u{7}', 'ñ', '\t', '🐍', '名', 'Ａ', '-', '1', ' ', '\u{1b}',
+        ] {
+            input.handle_input(InputCommand::Raw(c));
+        }
+        assert_eq!(input.value, "Añ-1 ");
+
+        // Back over the ñ and the dash, then in between
+        for command in [
+            InputCommand::Turn(Direction::Left),
+            InputCommand::Turn(Direction::Left),
+            InputCommand::MenuNav(MenuNav::Erase),
+            InputCommand::MenuNav(MenuNav::Erase),
+            InputCommand::Raw('é'),
+        ] {
+            input.handle_input(command);
+        }
+        assert_eq!(input.value, "Aé1 ");
+        assert_eq!(input.cursor_position, 2);
+
+        let blank = InputInfoRow::new(0, 0, "Your name", Some("   "));
+        assert_eq!(blank.value_or(DEFAULT_NAME), "player");
+    }
+
+    #[test]
+    fn test_editing_around_a_multibyte_character() {
+        // The cursor lands right after the ñ, two bytes into a one-char step
+        let mut input = InputInfoRow::new(0, 0, "Your name", Some("antoñito"));
+        for _ in 0..3 {
+            input.handle_input(InputCommand::Turn(Direction::Left));
+        }
+        input.handle_input(InputCommand::Raw('_'));
+        assert_eq!(input.value, "antoñ_ito");
+        input.handle_input(InputCommand::MenuNav(MenuNav::Erase));
+        input.handle_input(InputCommand::MenuNav(MenuNav::Erase));
+        assert_eq!(input.value, "antoito");
+        input.handle_input(InputCommand::Raw('ñ'));
+        input.handle_input(InputCommand::Raw('ñ'));
+        assert_eq!(input.value, "antoññito");
+
+        // Right stops at the end of the characters, not of the bytes
+        for _ in 0..20 {
+            input.handle_input(InputCommand::Turn(Direction::Right));
         }
+        assert_eq!(input.cursor_position, 9);
+        input.handle_input(InputCommand::MenuNav(MenuNav::Erase));
+        assert_eq!(input.value, "antoññit");
     }
 }
@@ -0,0 +1,149 @@
+use std::time::Duration;
+
+pub const DEFAULT_TURN_LENGTH: Duration = Duration::from_secs(10);
+pub const WARNING_LEAD_TIME: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RelayPlayer {
+    pub name: String,
+    pub ticks_survived: u64,
+    pub foods_eaten: u32,
+}
+
+impl RelayPlayer {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            ticks_survived: 0,
+            foods_eaten: 0,
+        }
+    }
+}
+
+// Two players share one snake and one keyboard, swapping control every
+// `turn_length` of game time. The clock only moves when the game ticks, so
+// pausing freezes it.
+#[derive(Debug, Clone)]
+pub struct Relay {
+    pub players: [RelayPlayer; 2],
+    pub active: usize,
+    pub turn_length: Duration,
+    elapsed_in_turn: Duration,
+}
+
+impl Relay {
+    pub fn new(first: String, second: String, turn_length: Duration) -> Self {
+        Self {
+            players: [RelayPlayer::new(first), RelayPlayer::new(second)],
+            active: 0,
+            turn_length,
+            elapsed_in_turn: Duration::ZERO,
+        }
+    }
+
+    pub fn active_player(&self) -> &RelayPlayer {
+        &self.players[self.active]
+    }
+
+    pub fn next_player(&self) -> &RelayPlayer {
+        &self.players[1 - self.active]
+    }
+
+    // Credits the tick to whoever is in control, then advances the clock.
+    // Returns true when control was handed over, so the tick that crosses the
+    // boundary still belongs to the outgoing player.
+    pub fn tick(&mut self, tick_duration: Duration, ate_food: bool) -> bool {
+        let player = &mut self.players[self.active];
+        player.ticks_survived = player.ticks_survived.saturating_add(1);
+        if ate_food {
+            player.foods_eaten = player.foods_eaten.saturating_add(1);
+        }
+
+        self.elapsed_in_turn += tick_duration;
+        if self.elapsed_in_turn < self.turn_length {
+            return false;
+        }
+        self.elapsed_in_turn -= self.turn_length;
+        self.active = 1 - self.active;
+        true
+    }
+
+    pub fn remaining_in_turn(&self) -> Duration {
+        self.turn_length.saturating_sub(self.elapsed_in_turn)
+    }
+
+    // Whole seconds left before the handoff, once inside the warning window
+    pub fn warning(&self) -> Option<u64> {
+        let remaining = self.remaining_in_turn();
+        if remaining > WARNING_LEAD_TIME {
+            return None;
+        }
+        Some(remaining.as_millis().div_ceil(1000) as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TICK: Duration = Duration::from_millis(100);
+
+    fn relay() -> Relay {
+        Relay::new("A".to_string(), "B".to_string(), Duration::from_secs(1))
+    }
+
+    #[test]
+    fn test_handoff_timing() {
+        let mut relay = relay();
+        for _ in 0..9 {
+            assert!(!relay.tick(TICK, false));
+            assert_eq!(relay.active, 0);
+        }
+        assert!(relay.tick(TICK, false));
+        assert_eq!(relay.active, 1);
+
+        for _ in 0..9 {
+            assert!(!relay.tick(TICK, false));
+        }
+        assert!(relay.tick(TICK, false));
+        assert_eq!(relay.active, 0);
+    }
+
+    #[test]
+    fn test_attribution_switches_at_boundary_tick() {
+        let mut relay = relay();
+        for _ in 0..9 {
+            relay.tick(TICK, false);
+        }
+        // The boundary tick is still credited to A
+        relay.tick(TICK, true);
+        assert_eq!(relay.players[0].ticks_survived, 10);
+        assert_eq!(relay.players[0].foods_eaten, 1);
+
+        relay.tick(TICK, true);
+        assert_eq!(relay.players[1].ticks_survived, 1);
+        assert_eq!(relay.players[1].foods_eaten, 1);
+        assert_eq!(relay.players[0].ticks_survived, 10);
+    }
+
+    #[test]
+    fn test_warning_lead_time() {
+        let mut relay = Relay::new("A".to_string(), "B".to_string(), DEFAULT_TURN_LENGTH);
+        let second = Duration::from_secs(1);
+        for _ in 0..6 {
+            relay.tick(second, false);
+        }
+        assert_eq!(relay.warning(), None);
+
+        relay.tick(Duration::from_millis(500), false);
+        assert_eq!(relay.warning(), None);
+        relay.tick(Duration::from_millis(500), false);
+        assert_eq!(relay.warning(), Some(3));
+        relay.tick(Duration::from_millis(100), false);
+        assert_eq!(relay.warning(), Some(3));
+        relay.tick(Duration::from_millis(900), false);
+        assert_eq!(relay.warning(), Some(2));
+        relay.tick(Duration::from_millis(1900), false);
+        assert_eq!(relay.warning(), Some(1));
+    }
+}
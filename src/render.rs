@@ -0,0 +1,772 @@
+use crate::{
+    animation::Animator,
+    game::{BonusFood, Bounds, Direction, Food, FruitKind, GameGrid, GameState, Position, Snake},
+    theme::{self, ColorSupport, Theme},
+};
+use crossterm::{
+    cursor,
+    event::{Event, KeyEvent},
+    queue,
+    style::{self, Color, Stylize},
+};
+use std::{
+    env,
+    io::{self, Write},
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Capabilities {
+    // Some terminals ignore or reset `cursor::Hide`, leaving a blinking
+    // hardware cursor wherever the last MoveTo left it
+    pub cursor_hide_unreliable: bool,
+    // Without 24-bit color, RGB themes come out as the nearest of 256 or 16
+    // colors, if at all
+    pub colors: ColorSupport,
+    // Block and box-drawing characters come out as garbage, on legacy Windows
+    // consoles and terminals whose locale is not UTF-8
+    pub ascii_only: bool,
+}
+
+impl Capabilities {
+    pub fn detect() -> Self {
+        // Legacy Windows consoles (outside Windows Terminal) are the known offenders
+        let legacy_windows_console = cfg!(windows) && env::var_os("WT_SESSION").is_none();
+        let colors = color_support(|name| env::var(name).ok());
+        // Windows has no locale variables, only the console to go by
+        let ascii_only = if cfg!(windows) {
+            legacy_windows_console
+        } else {
+            !utf8_locale(
+                ["LC_ALL", "LC_CTYPE", "LANG"]
+                    .into_iter()
+                    .filter_map(|name| env::var(name).ok())
+                    .find(|value| !value.is_empty())
+                    .as_deref(),
+            )
+        };
+        Self {
+            cursor_hide_unreliable: legacy_windows_console,
+            colors,
+            ascii_only,
+        }
+    }
+}
+
+// NO_COLOR turns colors off whatever the terminal, terminals that can show
+// RGB colors say so in COLORTERM, and TERM names the rest
+fn color_support(var: impl Fn(&str) -> Option<String>) -> ColorSupport {
+    let term = var("TERM").unwrap_or_default();
+    if var("NO_COLOR").is_some_and(|value| !value.is_empty()) || term == "dumb" {
+        ColorSupport::Monochrome
+    } else if var("COLORTERM").is_some_and(|value| matches!(value.as_str(), "truecolor" | "24bit"))
+    {
+        ColorSupport::TrueColor
+    } else if term.contains("256color") {
+        ColorSupport::Ansi256
+    } else {
+        ColorSupport::Ansi16
+    }
+}
+
+// Whether the locale in effect, the first of LC_ALL, LC_CTYPE and LANG that is
+// set, uses UTF-8, as in "en_US.UTF-8" or "C.utf8"
+fn utf8_locale(locale: Option<&str>) -> bool {
+    locale.is_some_and(|locale| {
+        let locale = locale.to_ascii_lowercase();
+        locale.contains("utf-8") || locale.contains("utf8")
+    })
+}
+
+// Where the cursor is left at the end of every frame. Normally the bottom-right
+// corner of the drawn area, which is a border cell; when hiding is unreliable
+// the row after the `hud_rows` under the board (commentary, status bar) so the
+// cursor never sits on the playfield or on anything the HUD prints.
+pub fn parking_spot(
+    total_width: u16,
+    height: u16,
+    hud_rows: u16,
+    capabilities: Capabilities,
+) -> (u16, u16) {
+    if capabilities.cursor_hide_unreliable {
+        (0, height.saturating_add(hud_rows))
+    } else {
+        (total_width.saturating_sub(1), height.saturating_sub(1))
+    }
+}
+
+pub fn park_cursor(
+    w: &mut impl Write,
+    spot: (u16, u16),
+    capabilities: Capabilities,
+) -> std::io::Result<()> {
+    if capabilities.cursor_hide_unreliable {
+        queue!(w, cursor::Hide)?;
+    }
+    queue!(w, cursor::MoveTo(spot.0, spot.1))
+}
+
+// Some terminals show the cursor again after a resize or after switching
+// screens, so hide it again whenever that happens
+pub fn reassert_hidden(w: &mut impl Write) -> std::io::Result<()> {
+    queue!(w, cursor::Hide)
+}
+
+// Returns the pressed key, if any, taking care of terminal housekeeping for the
+// other events
+pub fn handle_event(w: &mut impl Write, event: Event) -> std::io::Result<Option<KeyEvent>> {
+    match event {
+        Event::Key(key_event) => Ok(Some(key_event)),
+        Event::Resize(_, _) | Event::FocusGained => {
+            reassert_hidden(w)?;
+            Ok(None)
+        }
+        _ => Ok(None),
+    }
+}
+
+// Drawing lives here so the game logic stays free of the terminal backend
+
+// How far the end of the tail fades towards the background, and in how many
+// steps
+const TAIL_FADE: f32 = 0.6;
+const TAIL_BANDS: usize = 4;
+// How far food fades towards the background at the dim end of its pulse
+const FOOD_PULSE: f32 = 0.5;
+
+// One character cell of the board as it appears on screen
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Cell {
+    glyph: &'static str,
+    color: Color,
+    background: Option<Color>,
+}
+
+impl Cell {
+    fn new(glyph: &'static str, color: Color) -> Self {
+        Self {
+            glyph,
+            color,
+            background: None,
+        }
+    }
+
+    fn on(self, background: Color) -> Self {
+        Self {
+            background: Some(background),
+            ..self
+        }
+    }
+
+    fn queue(self, w: &mut impl Write) -> io::Result<()> {
+        let content = self.glyph.with(self.color);
+        match self.background {
+            Some(background) => queue!(w, style::PrintStyledContent(content.on(background))),
+            None => queue!(w, style::PrintStyledContent(content)),
+        }
+    }
+}
+
+// What the board should look like, row by row
+struct Frame {
+    width: u16,
+    height: u16,
+    cells: Vec<Cell>,
+}
+
+impl Frame {
+    fn set(&mut self, position: Position, cell: Cell) {
+        if position.x < self.width && position.y < self.height {
+            self.cells[position.y as usize * self.width as usize + position.x as usize] = cell;
+        }
+    }
+}
+
+// Draws the board, remembering what the terminal already shows so a frame
+// only sends the cells that changed since the one before
+pub struct Renderer {
+    grid: GameGrid,
+    // The last cells sent, None where the screen is not known to match
+    screen: Vec<Option<Cell>>,
+    animator: Animator,
+}
+
+impl Renderer {
+    pub fn new(width: u16, height: u16) -> Self {
+        Self {
+            grid: GameGrid::new(width, height),
+            screen: vec![None; width as usize * height as usize],
+            animator: Animator::default(),
+        }
+    }
+
+    pub fn with_reduced_motion(mut self, reduce_motion: bool) -> Self {
+        self.animator = Animator::new(reduce_motion);
+        self
+    }
+
+    // The next frame is sent in full, for after the screen was cleared
+    pub fn invalidate(&mut self) {
+        self.screen.fill(None);
+    }
+
+    // For after something else, like a banner, was drawn over a row
+    pub fn invalidate_row(&mut self, y: u16) {
+        let width = self.grid.width as usize;
+        if let Some(row) = self.screen.chunks_mut(width).nth(y as usize) {
+            row.fill(None);
+        }
+    }
+
+    pub fn render(
+        &mut self,
+        w: &mut impl Write,
+        state: &GameState,
+        theme: &Theme,
+    ) -> io::Result<()> {
+        let frame = self.frame(state, theme);
+        self.send(w, &frame, 0..frame.cells.len())
+    }
+
+    // Like `render`, but only looks at the given cells, as from
+    // `GameState::changed_cells`, at any that were invalidated, at the tails,
+    // which fade again as they move, and at the food, which pulses
+    pub fn render_cells(
+        &mut self,
+        w: &mut impl Write,
+        state: &GameState,
+        theme: &Theme,
+        cells: &[Position],
+    ) -> io::Result<()> {
+        let frame = self.frame(state, theme);
+        let width = frame.width as usize;
+        let mut indices: Vec<usize> = cells
+            .iter()
+            .chain(state.snake.tail.iter())
+            .chain(state.rival.iter().flat_map(|rival| rival.snake.tail.iter()))
+            .chain(state.foods.iter().map(|food| &food.position))
+            .filter(|position| position.x < frame.width && position.y < frame.height)
+            .map(|position| position.y as usize * width + position.x as usize)
+            .chain((0..self.screen.len()).filter(|&index| self.screen[index].is_none()))
+            .collect();
+        indices.sort_unstable();
+        indices.dedup();
+        self.send(w, &frame, indices)
+    }
+
+    // The next frame, which moves the animations on by one
+    fn frame(&mut self, state: &GameState, theme: &Theme) -> Frame {
+        let mut frame = Frame {
+            width: self.grid.width,
+            height: self.grid.height,
+            cells: Vec::with_capacity(self.screen.len()),
+        };
+        self.grid.paint(&mut frame, state.bounds, theme);
+        state.paint(&mut frame, theme, &self.animator);
+        self.animator.advance();
+        frame
+    }
+
+    // Sends the cells at `indices`, in order, that differ from the screen
+    fn send(
+        &mut self,
+        w: &mut impl Write,
+        frame: &Frame,
+        indices: impl IntoIterator<Item = usize>,
+    ) -> io::Result<()> {
+        // Printing moves the cursor along, so a run of changed cells only
+        // needs to be moved to once
+        let mut cursor = None;
+        for index in indices {
+            let cell = frame.cells[index];
+            let shown = &mut self.screen[index];
+            if *shown == Some(cell) {
+                continue;
+            }
+            let x = (index % frame.width as usize) as u16;
+            let y = (index / frame.width as usize) as u16;
+            if cursor != Some((x, y)) {
+                queue!(w, cursor::MoveTo(x, y))?;
+            }
+            cell.queue(w)?;
+            cursor = Some((x + 1, y));
+            *shown = Some(cell);
+        }
+        Ok(())
+    }
+}
+
+impl GameState {
+    fn paint(&self, frame: &mut Frame, theme: &Theme, animator: &Animator) {
+        for &position in &self.obstacles {
+            frame.set(position, Cell::new(theme.glyphs.wall, theme.surface));
+        }
+        for food in &self.foods {
+            food.paint(frame, theme, animator);
+        }
+        if let Some(bonus) = &self.bonus {
+            bonus.paint(frame, theme);
+        }
+        if let Some(pellet) = self.phase_pellet {
+            frame.set(
+                pellet,
+                Cell::new(theme.glyphs.pellet, theme.text).on(theme.background),
+            );
+        }
+        if let Some(rival) = &self.rival {
+            rival
+                .snake
+                .paint(frame, theme, theme.cherry, theme.inactive);
+        }
+        self.snake
+            .paint(frame, theme, theme.primary, theme.secondary);
+        if self.is_phasing() {
+            let head = theme.glyphs.head(self.snake.direction);
+            frame.set(
+                self.snake.head,
+                Cell::new(head, theme.accent).on(theme.background),
+            );
+        }
+    }
+}
+
+impl GameGrid {
+    // Lays down every cell of the frame, the rest is painted over it. Walls
+    // that closed in are drawn like the border.
+    fn paint(&self, frame: &mut Frame, bounds: Bounds, theme: &Theme) {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let cell = if bounds.is_wall(Position::new(x, y)) {
+                    Cell::new(theme.glyphs.wall, theme.surface)
+                } else {
+                    Cell::new(theme.glyphs.floor, theme.background)
+                };
+                frame.cells.push(cell);
+            }
+        }
+    }
+}
+
+impl Snake {
+    // The tail is a pipe from the head to its end, each segment joining the
+    // ones on either side. It fades from its own color next to the head
+    // towards the board's at the end, never all the way so the last cell
+    // still shows, and in bands, so a step only recolors the cells where one
+    // band meets the next.
+    fn paint(&self, frame: &mut Frame, theme: &Theme, head: Color, tail: Color) {
+        let length = self.tail.len();
+        for (index, &position) in self.tail.iter().enumerate() {
+            let previous = index
+                .checked_sub(1)
+                .map_or(self.head, |index| self.tail[index]);
+            let toward_head = toward(position, previous);
+            // The end carries straight on
+            let toward_end = match self.tail.get(index + 1) {
+                Some(&next) => toward(position, next),
+                None => toward_head.map(|direction| direction.reverse()),
+            };
+            let glyph = match (toward_head, toward_end) {
+                (Some(toward_head), Some(toward_end)) => theme.glyphs.pipe(toward_head, toward_end),
+                _ => theme.glyphs.tail,
+            };
+            let band = index * TAIL_BANDS / length;
+            let fade = TAIL_FADE * band as f32 / TAIL_BANDS as f32;
+            let color = theme::lerp_color(tail, theme.background, fade);
+            frame.set(position, Cell::new(glyph, color).on(theme.background));
+        }
+        let glyph = theme.glyphs.head(self.direction);
+        frame.set(self.head, Cell::new(glyph, head).on(theme.background));
+    }
+}
+
+// The way from one cell to the next one along, which is the far side of the
+// board when the snake wrapped around it. None for the same cell.
+fn toward(from: Position, to: Position) -> Option<Direction> {
+    let dx = i32::from(to.x) - i32::from(from.x);
+    let dy = i32::from(to.y) - i32::from(from.y);
+    match (dx, dy) {
+        (0, 0) => None,
+        (dx, 0) if dx == 1 || dx < -1 => Some(Direction::Right),
+        (_, 0) => Some(Direction::Left),
+        (0, dy) if dy == 1 || dy < -1 => Some(Direction::Down),
+        (0, _) => Some(Direction::Up),
+        _ => None,
+    }
+}
+
+impl Food {
+    // Pulsing, so it is easier to spot on a big board
+    fn paint(&self, frame: &mut Frame, theme: &Theme, animator: &Animator) {
+        let mut color = self.kind.color(theme);
+        if animator.is_dimmed() {
+            color = theme::lerp_color(color, theme.background, FOOD_PULSE);
+        }
+        let cell = Cell::new(theme.glyphs.fruit(self.kind), color).on(theme.background);
+        frame.set(self.position, cell);
+    }
+}
+
+impl FruitKind {
+    pub fn color(self, theme: &Theme) -> Color {
+        match self {
+            FruitKind::Apple => theme.accent,
+            FruitKind::Cherry => theme.cherry,
+            FruitKind::Banana => theme.banana,
+            FruitKind::Hourglass => theme.text,
+        }
+    }
+}
+
+impl BonusFood {
+    fn paint(&self, frame: &mut Frame, theme: &Theme) {
+        frame.set(
+            self.position,
+            Cell::new(theme.glyphs.bonus, theme.gold).on(theme.background),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        animation::PULSE_FRAMES,
+        game::{Action, Direction},
+        layout::STATUS_ROWS,
+        menu::StatusBar,
+        screen::{blank_screen, foreground_code, play},
+    };
+
+    const HIDE: &str = "\x1b[?25l";
+
+    #[test]
+    fn test_frame_ends_at_parking_spot() {
+        for capabilities in [
+            Capabilities::default(),
+            Capabilities {
+                cursor_hide_unreliable: true,
+                ..Capabilities::default()
+            },
+        ] {
+            let spot = parking_spot(50, 15, 0, capabilities);
+            let mut frame = Vec::new();
+            queue!(frame, cursor::MoveTo(7, 3)).unwrap();
+            park_cursor(&mut frame, spot, capabilities).unwrap();
+
+            let frame = String::from_utf8(frame).unwrap();
+            // MoveTo is 0-based, the escape sequence is 1-based row;column
+            let expected = format!("\x1b[{};{}H", spot.1 + 1, spot.0 + 1);
+            assert!(frame.ends_with(&expected), "{:?}", frame);
+        }
+    }
+
+    #[test]
+    fn test_unreliable_hide_parks_outside_the_board() {
+        let capabilities = Capabilities {
+            cursor_hide_unreliable: true,
+            ..Capabilities::default()
+        };
+        assert_eq!(parking_spot(50, 15, 0, capabilities), (0, 15));
+        assert_eq!(parking_spot(50, 15, 0, Capabilities::default()), (49, 14));
+    }
+
+    #[test]
+    fn test_unreliable_hide_parks_below_the_hud() {
+        let capabilities = Capabilities {
+            cursor_hide_unreliable: true,
+            ..Capabilities::default()
+        };
+        // Side panel, with the commentary line right under the board
+        assert_eq!(parking_spot(50, 15, 1, capabilities), (0, 16));
+
+        // Status bar, under the commentary line or straight under the board
+        for commentary in [0, 1] {
+            let bar = StatusBar::new(15 + commentary, 30, "somebody".to_string(), 1200);
+            let spot = parking_spot(30, 15, commentary + STATUS_ROWS, capabilities);
+            let mut output = Vec::new();
+            bar.queue(&mut output).unwrap();
+            let mut screen = blank_screen(30, 20);
+            play(&mut screen, &output);
+            let last_hud_row = screen
+                .iter()
+                .rposition(|row| row.iter().any(|&(glyph, _, _)| glyph != ' '))
+                .unwrap();
+            assert_eq!(last_hud_row as u16, bar.y + STATUS_ROWS - 1);
+            assert_eq!(spot, (0, last_hud_row as u16 + 1));
+        }
+
+        // When hiding works the cursor stays on the board's corner either way
+        assert_eq!(parking_spot(30, 15, 3, Capabilities::default()), (29, 14));
+    }
+
+    #[test]
+    fn test_color_support_from_the_environment() {
+        let support = |vars: &[(&str, &str)]| {
+            color_support(|name| {
+                vars.iter()
+                    .find(|(var, _)| *var == name)
+                    .map(|(_, value)| value.to_string())
+            })
+        };
+        assert_eq!(
+            support(&[("COLORTERM", "truecolor")]),
+            ColorSupport::TrueColor
+        );
+        assert_eq!(
+            support(&[("TERM", "xterm-256color")]),
+            ColorSupport::Ansi256
+        );
+        assert_eq!(support(&[("TERM", "xterm")]), ColorSupport::Ansi16);
+        assert_eq!(support(&[]), ColorSupport::Ansi16);
+        assert_eq!(support(&[("TERM", "dumb")]), ColorSupport::Monochrome);
+        // NO_COLOR wins, unless it is empty
+        let no_color = [("NO_COLOR", "1"), ("COLORTERM", "24bit")];
+        assert_eq!(support(&no_color), ColorSupport::Monochrome);
+        let empty = [("NO_COLOR", ""), ("COLORTERM", "24bit")];
+        assert_eq!(support(&empty), ColorSupport::TrueColor);
+    }
+
+    #[test]
+    fn test_only_utf8_locales_get_unicode() {
+        assert!(utf8_locale(Some("en_US.UTF-8")));
+        assert!(utf8_locale(Some("C.utf8")));
+        assert!(!utf8_locale(Some("POSIX")));
+        assert!(!utf8_locale(Some("en_US.ISO-8859-1")));
+        assert!(!utf8_locale(None));
+    }
+
+    #[test]
+    fn test_hide_is_reemitted_after_resize() {
+        let mut frame = Vec::new();
+        assert_eq!(
+            handle_event(&mut frame, Event::Resize(80, 24)).unwrap(),
+            None
+        );
+        assert_eq!(String::from_utf8(frame).unwrap(), HIDE);
+
+        let mut frame = Vec::new();
+        let key = KeyEvent::from(crossterm::event::KeyCode::Up);
+        assert_eq!(
+            handle_event(&mut frame, Event::Key(key)).unwrap(),
+            Some(key)
+        );
+        assert!(frame.is_empty());
+    }
+
+    // A game with something of everything on the board, run for a while
+    fn busy_game() -> GameState {
+        let mut state = GameState::with_seed(30, 15, 11)
+            .with_obstacles([Position::new(5, 3), Position::new(6, 3)])
+            .with_food_count(3)
+            .with_rival(2);
+        state.phase_pellet = Some(Position::new(20, 10));
+        state.bonus = Some(BonusFood {
+            position: Position::new(8, 12),
+            ttl: 50,
+            value: 5,
+        });
+        state
+    }
+
+    // A renderer that has drawn nothing yet, with the food at the same point
+    // of its pulse as `renderer`'s
+    fn fresh(renderer: &Renderer) -> Renderer {
+        let mut fresh = Renderer::new(renderer.grid.width, renderer.grid.height);
+        fresh.animator = renderer.animator;
+        fresh
+    }
+
+    #[test]
+    fn test_changed_cells_match_a_full_redraw() {
+        let theme = Theme::default();
+        let mut state = busy_game();
+        let mut renderer = Renderer::new(30, 15);
+        let mut screen = blank_screen(30, 15);
+
+        let turns = [
+            Direction::Down,
+            Direction::Left,
+            Direction::Up,
+            Direction::Right,
+        ];
+        for tick in 0..24 {
+            let mut fresh = fresh(&renderer);
+            let mut output = Vec::new();
+            renderer.render(&mut output, &state, &theme).unwrap();
+            play(&mut screen, &output);
+
+            let mut full = Vec::new();
+            fresh.render(&mut full, &state, &theme).unwrap();
+            let mut expected = blank_screen(30, 15);
+            play(&mut expected, &full);
+            assert_eq!(screen, expected, "tick {}", tick);
+
+            let turn = (tick % 6 == 0).then(|| turns[tick / 6 % 4]);
+            let action = Action::new(state.snake.head, turn, tick % 3 == 0);
+            state.next(action);
+            assert_eq!(state.outcome(), None);
+        }
+    }
+
+    #[test]
+    fn test_sending_only_the_changed_cells_keeps_up() {
+        let theme = Theme::default();
+        let mut state = busy_game();
+        // Gone part way through
+        state.bonus.as_mut().unwrap().ttl = 10;
+        let mut renderer = Renderer::new(30, 15);
+        let mut screen = blank_screen(30, 15);
+        let mut output = Vec::new();
+        renderer
+            .render_cells(&mut output, &state, &theme, &[])
+            .unwrap();
+        play(&mut screen, &output);
+
+        for tick in 0..24 {
+            let turn = (tick % 5 == 0).then_some(Direction::Down);
+            state.next(Action::new(state.snake.head, turn, tick % 4 == 0));
+            let cells = state.changed_cells().unwrap();
+            let mut fresh = fresh(&renderer);
+            let mut output = Vec::new();
+            renderer
+                .render_cells(&mut output, &state, &theme, &cells)
+                .unwrap();
+            play(&mut screen, &output);
+
+            let mut full = Vec::new();
+            fresh.render(&mut full, &state, &theme).unwrap();
+            let mut expected = blank_screen(30, 15);
+            play(&mut expected, &full);
+            assert_eq!(screen, expected, "tick {}", tick);
+        }
+        assert!(state.bonus.is_none());
+    }
+
+    #[test]
+    fn test_closed_in_walls_are_drawn_like_the_border() {
+        let theme = Theme::default();
+        let mut state = GameState::with_seed(20, 12, 3);
+        let mut renderer = Renderer::new(20, 12);
+        let mut screen = blank_screen(20, 12);
+        let mut output = Vec::new();
+        renderer.render(&mut output, &state, &theme).unwrap();
+        play(&mut screen, &output);
+        let wall = screen[0][0].clone();
+        let floor = screen[1][1].clone();
+
+        for _ in 0..2 {
+            state.shrink();
+        }
+        let mut output = Vec::new();
+        renderer.render(&mut output, &state, &theme).unwrap();
+        play(&mut screen, &output);
+        for (y, row) in screen.iter().enumerate() {
+            for (x, cell) in row.iter().enumerate() {
+                let position = Position::new(x as u16, y as u16);
+                if state.bounds.is_wall(position) {
+                    assert_eq!(*cell, wall, "{:?}", position);
+                } else if !state.snake.contains(position) && state.food_at(position).is_none() {
+                    assert_eq!(*cell, floor, "{:?}", position);
+                }
+            }
+        }
+        assert!(state.bounds.is_wall(Position::new(2, 5)));
+        assert!(!state.bounds.is_wall(Position::new(3, 5)));
+    }
+
+    #[test]
+    fn test_a_step_sends_a_fraction_of_the_frame() {
+        let theme = Theme::default();
+        let mut state = busy_game();
+        let mut renderer = Renderer::new(30, 15);
+        let mut full = Vec::new();
+        renderer.render(&mut full, &state, &theme).unwrap();
+
+        state.next(Action::new(state.snake.head, None, false));
+        let mut step = Vec::new();
+        renderer.render(&mut step, &state, &theme).unwrap();
+        // Around 10 KB for the whole board, well under 100 bytes for a step
+        assert!(
+            step.len() * 50 < full.len(),
+            "{} vs {}",
+            step.len(),
+            full.len()
+        );
+
+        // Nothing changed, nothing sent
+        let mut idle = Vec::new();
+        renderer.render(&mut idle, &state, &theme).unwrap();
+        assert!(idle.is_empty());
+
+        renderer.invalidate_row(0);
+        let mut row = Vec::new();
+        renderer.render(&mut row, &state, &theme).unwrap();
+        let mut screen = blank_screen(30, 15);
+        play(&mut screen, &row);
+        assert!(screen[0].iter().all(|(glyph, _, _)| *glyph == '█'));
+        assert!(screen[1].iter().all(|(glyph, _, _)| *glyph == ' '));
+    }
+
+    #[test]
+    fn test_moved_food_is_drawn_where_it_went() {
+        let theme = Theme::default();
+        let mut state = GameState::with_seed(30, 15, 1);
+        let mut renderer = Renderer::new(30, 15);
+        renderer.render(&mut Vec::new(), &state, &theme).unwrap();
+
+        state.foods[0] = Food::at(Position::new(4, 9));
+        let mut output = Vec::new();
+        renderer.render(&mut output, &state, &theme).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("\x1b[10;5H"), "{:?}", output);
+    }
+
+    #[test]
+    fn test_colorblind_snake_and_food_differ_in_shape() {
+        let mut state = GameState::with_seed(30, 15, 1);
+        state.foods[0] = Food::at(Position::new(3, 3));
+        state.snake.direction = Direction::Down;
+        let mut output = Vec::new();
+        Renderer::new(30, 15)
+            .render(&mut output, &state, &Theme::COLORBLIND)
+            .unwrap();
+        let mut screen = blank_screen(30, 15);
+        play(&mut screen, &output);
+        let head = state.snake.head;
+        assert_eq!(screen[head.y as usize][head.x as usize].0, '▼');
+        assert_eq!(screen[3][3].0, '◆');
+    }
+
+    #[test]
+    fn test_food_pulses_unless_motion_is_reduced() {
+        let theme = Theme::default();
+        let state = GameState::with_seed(30, 15, 1);
+        let food = state.foods[0].position;
+        // The food's color on each of two pulses' worth of frames
+        let colors = |mut renderer: Renderer| {
+            let mut screen = blank_screen(30, 15);
+            let mut colors = Vec::new();
+            for _ in 0..PULSE_FRAMES * 2 {
+                let mut output = Vec::new();
+                renderer
+                    .render_cells(&mut output, &state, &theme, &[])
+                    .unwrap();
+                play(&mut screen, &output);
+                colors.push(screen[food.y as usize][food.x as usize].1.clone());
+            }
+            colors
+        };
+
+        let bright = foreground_code(theme.accent);
+        let dim = foreground_code(theme::lerp_color(
+            theme.accent,
+            theme.background,
+            FOOD_PULSE,
+        ));
+        let pulsing = colors(Renderer::new(30, 15));
+        let (first, second) = pulsing.split_at(PULSE_FRAMES as usize);
+        assert!(first.iter().all(|color| *color == bright));
+        assert!(second.iter().all(|color| *color == dim));
+        let still = colors(Renderer::new(30, 15).with_reduced_motion(true));
+        assert!(still.iter().all(|color| *color == bright));
+    }
+}
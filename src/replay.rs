@@ -0,0 +1,809 @@
+use crate::{
+    game::{
+        Action, Direction, FoodBehavior, FruitKind, FruitWeights, GameMode, GameState, WallMode,
+        MIN_BOARD_SIZE,
+    },
+    level::{self, Level},
+};
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    path::Path,
+};
+
+const MAGIC: &str = "rust-snake-replay";
+const VERSION: u32 = 4;
+// Version 2 added the player line and version 3 the hourglass, the fourth
+// fruit weight. Older replays never had one spawn. Version 4 changed how the
+// rival bot plans, its moves are not stored, so older races can't be re-run.
+const OLDEST_VERSION: u32 = 1;
+const HOURGLASS_VERSION: u32 = 3;
+const RIVAL_VERSION: u32 = 4;
+
+// Everything needed to re-run a game: the board, the food seed and what
+// happened on every tick. Food positions are not stored, they are reproduced
+// by seeding the RNG the same way.
+//
+// On disk it is a small text file, one character per tick:
+//
+//   rust-snake-replay 4
+//   player antoñito
+//   30 15 1234
+//   ..U..L+..D
+//
+// The player line is left out when the name isn't known.
+//
+// The board line ends with `wrap` for games played with wrap-around walls,
+// `food=N` when more than one food was on the board, `fruit=A,C,B,H` when the
+// fruit weights were changed, `moving` when the food wandered and the mode's
+// name for anything but a classic game, with `shrinks=T,T` listing the tick
+// counts after which a shrinking arena closed in, `rival=N` when a rival
+// snake replanning every N ticks took part and `length=N` when the snake
+// started with N tail cells, and `practice` for a practice run where nothing
+// ended the game. Games played on a map add `map`
+// and the map itself follows the board line, one row per line.
+//
+// `.` keeps the direction, `U`/`D`/`L`/`R` turn, and lowercase or `+` mean
+// the snake grew on that tick. A `two-player` game has a second line of
+// ticks for the second player's turns, which never say whether it grew.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+    pub width: u16,
+    pub height: u16,
+    pub seed: u64,
+    pub wall_mode: WallMode,
+    pub food_count: usize,
+    pub fruit_weights: FruitWeights,
+    pub food_behavior: FoodBehavior,
+    pub mode: GameMode,
+    // The tick counts after which a shrinking arena closed in
+    pub shrinks: Vec<u64>,
+    // How often the rival replanned, if there was one
+    pub rival: Option<u32>,
+    // Tail cells the snake was asked to start with
+    pub start_length: usize,
+    pub practice: bool,
+    pub two_player: bool,
+    pub level: Option<Level>,
+    pub player: Option<String>,
+    pub ticks: Vec<Tick>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tick {
+    pub change_direction: Option<Direction>,
+    pub must_grow: bool,
+    pub second_turn: Option<Direction>,
+}
+
+impl Replay {
+    pub fn from_state(state: &GameState) -> Self {
+        Self {
+            width: state.game_width,
+            height: state.game_height,
+            seed: state.seed,
+            wall_mode: state.wall_mode,
+            food_count: state.foods.len(),
+            fruit_weights: state.fruit_weights,
+            food_behavior: state.food_behavior,
+            mode: state.mode,
+            shrinks: state.shrinks.clone(),
+            rival: state
+                .rival
+                .as_ref()
+                .filter(|rival| !rival.second_player)
+                .map(|rival| rival.controller.replan_every),
+            start_length: state.start_length,
+            practice: state.practice,
+            two_player: state.is_two_player(),
+            level: state.level.clone(),
+            player: None,
+            ticks: state
+                .actions
+                .iter()
+                .map(|action| Tick {
+                    change_direction: action.change_direction,
+                    must_grow: action.must_grow,
+                    second_turn: action.second_turn,
+                })
+                .collect(),
+        }
+    }
+
+    // On one line, as the leaderboard keeps it
+    pub fn with_player(mut self, name: &str) -> Self {
+        self.player = Some(name.replace(['\n', '\r'], " "));
+        self
+    }
+
+    pub fn initial_state(&self) -> GameState {
+        let state = match &self.level {
+            Some(level) => GameState::from_level(level.clone(), self.seed),
+            None => GameState::with_seed(self.width, self.height, self.seed),
+        };
+        let mut state = state
+            .with_start_length(self.start_length)
+            .with_fruit_weights(self.fruit_weights)
+            .with_food_count(self.food_count)
+            .with_mode(self.mode)
+            .with_shrinks(&self.shrinks);
+        if let Some(replan_every) = self.rival {
+            state = state.with_rival(replan_every);
+        }
+        if self.two_player {
+            state = state.with_second_player();
+        }
+        state.wall_mode = self.wall_mode;
+        state.food_behavior = self.food_behavior;
+        state.practice = self.practice;
+        state
+    }
+
+    pub fn apply(state: &mut GameState, tick: Tick) {
+        let mut action = Action::new(state.snake.head, tick.change_direction, tick.must_grow);
+        action.second_turn = tick.second_turn;
+        state.next(action);
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = io::BufWriter::new(fs::File::create(path)?);
+        self.write(&mut file)?;
+        file.flush()
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Self::read(BufReader::new(fs::File::open(path)?))
+    }
+
+    pub fn write(&self, w: &mut impl Write) -> io::Result<()> {
+        writeln!(w, "{} {}", MAGIC, VERSION)?;
+        if let Some(player) = &self.player {
+            // A line break would end the name and start the board line
+            writeln!(w, "player {}", player.replace(['\n', '\r'], " "))?;
+        }
+        write!(w, "{} {} {}", self.width, self.height, self.seed)?;
+        if self.wall_mode != WallMode::Solid {
+            write!(w, " {}", self.wall_mode)?;
+        }
+        if self.food_count != 1 {
+            write!(w, " food={}", self.food_count)?;
+        }
+        if self.fruit_weights != FruitWeights::default() {
+            write!(w, " fruit={}", self.fruit_weights)?;
+        }
+        if self.food_behavior == FoodBehavior::Wandering {
+            write!(w, " moving")?;
+        }
+        if self.mode != GameMode::Classic {
+            write!(w, " {}", self.mode.name())?;
+        }
+        if !self.shrinks.is_empty() {
+            let shrinks: Vec<String> = self.shrinks.iter().map(u64::to_string).collect();
+            write!(w, " shrinks={}", shrinks.join(","))?;
+        }
+        if let Some(replan_every) = self.rival {
+            write!(w, " rival={}", replan_every)?;
+        }
+        if self.start_length > 0 {
+            write!(w, " length={}", self.start_length)?;
+        }
+        if self.practice {
+            write!(w, " practice")?;
+        }
+        if self.two_player {
+            write!(w, " two-player")?;
+        }
+        match &self.level {
+            Some(level) => write!(w, " map\n{}", level.to_text())?,
+            None => writeln!(w)?,
+        }
+        let ticks: String = self.ticks.iter().map(|tick| encode_tick(*tick)).collect();
+        writeln!(w, "{}", ticks)?;
+        if self.two_player {
+            let turns: String = self
+                .ticks
+                .iter()
+                .map(|tick| encode_turn(tick.second_turn))
+                .collect();
+            writeln!(w, "{}", turns)?;
+        }
+        Ok(())
+    }
+
+    pub fn read(r: impl BufRead) -> io::Result<Self> {
+        let mut lines = r.lines();
+        let mut next_line = |what: &str| {
+            lines
+                .next()
+                .unwrap_or_else(|| Err(invalid(format!("missing {}", what))))
+        };
+
+        let header = next_line("header")?;
+        let version = match header.split_once(' ') {
+            Some((MAGIC, version)) => version
+                .parse()
+                .ok()
+                .filter(|version| (OLDEST_VERSION..=VERSION).contains(version))
+                .ok_or_else(|| invalid(format!("unsupported replay version {}", version)))?,
+            _ => return Err(invalid("not a rust-snake replay".to_string())),
+        };
+
+        let mut board = next_line("board line")?;
+        let mut player = None;
+        if let Some(name) = board.strip_prefix("player ") {
+            player = Some(name.to_string());
+            board = next_line("board line")?;
+        }
+        let fields: Vec<&str> = board.split_whitespace().collect();
+        let [width, height, seed, ref options @ ..] = fields[..] else {
+            return Err(invalid(format!("malformed board line '{}'", board)));
+        };
+        let mut wall_mode = WallMode::Solid;
+        let mut food_count = 1;
+        let mut fruit_weights = FruitWeights::default();
+        if version < HOURGLASS_VERSION {
+            fruit_weights.0[FruitKind::Hourglass as usize] = 0;
+        }
+        let mut food_behavior = FoodBehavior::Still;
+        let mut mode = GameMode::Classic;
+        let mut shrinks = Vec::new();
+        let mut rival = None;
+        let mut start_length = 0;
+        let mut practice = false;
+        let mut two_player = false;
+        let mut has_map = false;
+        for &option in options {
+            if let Some(count) = option.strip_prefix("food=") {
+                food_count = count
+                    .parse()
+                    .ok()
+                    .filter(|&count| count > 0)
+                    .ok_or_else(|| invalid(format!("invalid food count '{}'", count)))?;
+            } else if let Some(ticks) = option.strip_prefix("shrinks=") {
+                shrinks = ticks
+                    .split(',')
+                    .map(|tick| tick.parse().ok())
+                    .collect::<Option<_>>()
+                    .ok_or_else(|| invalid(format!("invalid shrink ticks '{}'", ticks)))?;
+            } else if let Some(ticks) = option.strip_prefix("rival=") {
+                if version < RIVAL_VERSION {
+                    return Err(invalid(
+                        "the replay raced an older rival and can't be replayed".to_string(),
+                    ));
+                }
+                rival = Some(
+                    ticks
+                        .parse()
+                        .ok()
+                        .filter(|&ticks| ticks > 0)
+                        .ok_or_else(|| invalid(format!("invalid rival replanning '{}'", ticks)))?,
+                );
+            } else if let Some(length) = option.strip_prefix("length=") {
+                start_length = length
+                    .parse()
+                    .map_err(|_| invalid(format!("invalid start length '{}'", length)))?;
+            } else if let Some(weights) = option.strip_prefix("fruit=") {
+                fruit_weights = weights.parse().map_err(invalid)?;
+            } else if option == "map" {
+                has_map = true;
+            } else if option == "practice" {
+                practice = true;
+            } else if option == "two-player" {
+                two_player = true;
+            } else if option == "moving" {
+                food_behavior = FoodBehavior::Wandering;
+            } else if let Ok(game_mode) = option.parse() {
+                mode = game_mode;
+            } else {
+                wall_mode = option.parse().map_err(invalid)?;
+            }
+        }
+        let parse_error = |field: &str| invalid(format!("invalid number '{}'", field));
+        let width = width.parse().map_err(|_| parse_error(width))?;
+        let height = height.parse().map_err(|_| parse_error(height))?;
+        let seed = seed.parse().map_err(|_| parse_error(seed))?;
+        // Smaller boards have no room for the snake and its food
+        if width < MIN_BOARD_SIZE || height < MIN_BOARD_SIZE {
+            return Err(invalid(format!(
+                "board must be at least {}x{}, got {}x{}",
+                MIN_BOARD_SIZE, MIN_BOARD_SIZE, width, height
+            )));
+        }
+
+        let level = if has_map {
+            let rows = (0..height)
+                .map(|_| next_line("map row"))
+                .collect::<io::Result<Vec<_>>>()?;
+            let level = level::parse_map(&rows.join("\n"))?;
+            if (level.width, level.height) != (width, height) {
+                return Err(invalid("map does not match the board size".to_string()));
+            }
+            Some(level)
+        } else {
+            None
+        };
+
+        let mut ticks: Vec<Tick> = next_line("ticks")?
+            .chars()
+            .map(|c| decode_tick(c).ok_or_else(|| invalid(format!("invalid tick '{}'", c))))
+            .collect::<io::Result<_>>()?;
+        if two_player {
+            let turns = next_line("second player's ticks")?;
+            if turns.chars().count() != ticks.len() {
+                return Err(invalid("the players' ticks do not line up".to_string()));
+            }
+            for (tick, c) in ticks.iter_mut().zip(turns.chars()) {
+                let turn = decode_tick(c)
+                    .filter(|turn| !turn.must_grow)
+                    .ok_or_else(|| invalid(format!("invalid turn '{}'", c)))?;
+                tick.second_turn = turn.change_direction;
+            }
+        }
+
+        Ok(Self {
+            width,
+            height,
+            seed,
+            wall_mode,
+            food_count,
+            fruit_weights,
+            food_behavior,
+            mode,
+            shrinks,
+            rival,
+            start_length,
+            practice,
+            two_player,
+            level,
+            player,
+            ticks,
+        })
+    }
+}
+
+fn encode_turn(turn: Option<Direction>) -> char {
+    encode_tick(Tick {
+        change_direction: turn,
+        must_grow: false,
+        second_turn: None,
+    })
+}
+
+fn encode_tick(tick: Tick) -> char {
+    let c = match tick.change_direction {
+        None if tick.must_grow => return '+',
+        None => return '.',
+        Some(Direction::Up) => 'U',
+        Some(Direction::Down) => 'D',
+        Some(Direction::Left) => 'L',
+        Some(Direction::Right) => 'R',
+    };
+    if tick.must_grow {
+        c.to_ascii_lowercase()
+    } else {
+        c
+    }
+}
+
+fn decode_tick(c: char) -> Option<Tick> {
+    let change_direction = match c.to_ascii_uppercase() {
+        '.' | '+' => None,
+        'U' => Some(Direction::Up),
+        'D' => Some(Direction::Down),
+        'L' => Some(Direction::Left),
+        'R' => Some(Direction::Right),
+        _ => return None,
+    };
+    Some(Tick {
+        change_direction,
+        must_grow: c == '+' || c.is_ascii_lowercase(),
+        second_turn: None,
+    })
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play(state: &mut GameState, moves: &[(Option<Direction>, bool)]) {
+        for &(change_direction, must_grow) in moves {
+            state.next(Action::new(state.snake.head, change_direction, must_grow));
+        }
+    }
+
+    #[test]
+    fn test_replay_round_trip() {
+        let mut state = GameState::with_seed(30, 15, 99);
+        play(
+            &mut state,
+            &[
+                (None, false),
+                (Some(Direction::Up), true),
+                (None, true),
+                (Some(Direction::Left), false),
+            ],
+        );
+
+        let replay = Replay::from_state(&state);
+        let mut bytes = Vec::new();
+        replay.write(&mut bytes).unwrap();
+        assert_eq!(
+            String::from_utf8(bytes.clone()).unwrap(),
+            "rust-snake-replay 4\n30 15 99\n.u+L\n"
+        );
+        assert_eq!(Replay::read(bytes.as_slice()).unwrap(), replay);
+    }
+
+    #[test]
+    fn test_replay_keeps_the_player() {
+        let mut state = GameState::with_seed(30, 15, 7);
+        play(&mut state, &[(None, false), (Some(Direction::Down), true)]);
+
+        let replay = Replay::from_state(&state).with_player("Ana María");
+        let mut bytes = Vec::new();
+        replay.write(&mut bytes).unwrap();
+        assert_eq!(
+            String::from_utf8(bytes.clone()).unwrap(),
+            "rust-snake-replay 4\nplayer Ana María\n30 15 7\n.d\n"
+        );
+        let read = Replay::read(bytes.as_slice()).unwrap();
+        assert_eq!(read.player.as_deref(), Some("Ana María"));
+        assert_eq!(read, replay);
+
+        // Neither way in can split the name over lines
+        let replay = Replay::from_state(&state).with_player("Ana\nMaría");
+        assert_eq!(replay.player.as_deref(), Some("Ana María"));
+        let mut replay = Replay::from_state(&state);
+        replay.player = Some("Ana\r\nMaría".to_string());
+        let mut bytes = Vec::new();
+        replay.write(&mut bytes).unwrap();
+        let read = Replay::read(bytes.as_slice()).unwrap();
+        assert_eq!(read.player.as_deref(), Some("Ana  María"));
+        assert_eq!(read.ticks, replay.ticks);
+
+        // Replays from before names were kept still load
+        let old = Replay::read("rust-snake-replay 2\n30 15 7\n.d\n".as_bytes()).unwrap();
+        assert_eq!(old.player, None);
+        assert_eq!(old.ticks, replay.ticks);
+    }
+
+    #[test]
+    fn test_replay_reproduces_the_game() {
+        let mut state = GameState::with_seed(30, 15, 5);
+        play(
+            &mut state,
+            &[
+                (Some(Direction::Up), true),
+                (Some(Direction::Right), true),
+                (None, false),
+            ],
+        );
+
+        let replay = Replay::from_state(&state);
+        let mut replayed = replay.initial_state();
+        for tick in &replay.ticks {
+            Replay::apply(&mut replayed, *tick);
+        }
+
+        assert_eq!(replayed.snake.head, state.snake.head);
+        assert_eq!(replayed.snake.tail, state.snake.tail);
+        assert_eq!(replayed.foods[0].position, state.foods[0].position);
+        assert_eq!(replayed.score, state.score);
+    }
+
+    #[test]
+    fn test_replay_keeps_the_fruit_weights() {
+        let mut state =
+            GameState::with_seed(10, 8, 3).with_fruit_weights(FruitWeights([0, 1, 1, 1]));
+        play(&mut state, &[(None, true); 5]);
+
+        let replay = Replay::from_state(&state);
+        let mut bytes = Vec::new();
+        replay.write(&mut bytes).unwrap();
+        assert!(String::from_utf8(bytes.clone())
+            .unwrap()
+            .contains("\n10 8 3 fruit=0,1,1,1\n"));
+
+        let read = Replay::read(bytes.as_slice()).unwrap();
+        let mut replayed = read.initial_state();
+        for tick in &read.ticks {
+            Replay::apply(&mut replayed, *tick);
+        }
+        assert_eq!(replayed.foods, state.foods);
+        assert_eq!(replayed.score, state.score);
+    }
+
+    #[test]
+    fn test_replays_from_before_the_hourglass_never_spawn_one() {
+        let old = Replay::read("rust-snake-replay 2\n10 8 3\n.\n".as_bytes()).unwrap();
+        assert_eq!(old.fruit_weights, FruitWeights([12, 6, 2, 0]));
+        let old = Replay::read("rust-snake-replay 1\n10 8 3 fruit=0,1,1\n.\n".as_bytes()).unwrap();
+        assert_eq!(old.fruit_weights, FruitWeights([0, 1, 1, 0]));
+
+        // Written again they say so, rather than relying on the version
+        let mut bytes = Vec::new();
+        old.write(&mut bytes).unwrap();
+        let text = String::from_utf8(bytes.clone()).unwrap();
+        assert_eq!(text, "rust-snake-replay 4\n10 8 3 fruit=0,1,1,0\n.\n");
+        assert_eq!(Replay::read(bytes.as_slice()).unwrap(), old);
+    }
+
+    #[test]
+    fn test_replay_keeps_moving_food() {
+        let mut state = GameState::with_seed(10, 8, 4);
+        state.food_behavior = FoodBehavior::Wandering;
+        play(&mut state, &[(None, false); 4]);
+
+        let mut bytes = Vec::new();
+        Replay::from_state(&state).write(&mut bytes).unwrap();
+        assert!(String::from_utf8(bytes.clone())
+            .unwrap()
+            .contains("\n10 8 4 moving\n"));
+
+        let read = Replay::read(bytes.as_slice()).unwrap();
+        let mut replayed = read.initial_state();
+        for tick in &read.ticks {
+            Replay::apply(&mut replayed, *tick);
+        }
+        assert_eq!(replayed.foods, state.foods);
+    }
+
+    #[test]
+    fn test_replay_keeps_the_lives() {
+        let mut state = GameState::with_seed(10, 8, 3).with_mode(GameMode::Lives);
+        // Into the right wall on the fourth tick, then on from the start
+        play(&mut state, &[(None, false); 6]);
+        assert_eq!(state.lives, 2);
+
+        let mut bytes = Vec::new();
+        Replay::from_state(&state).write(&mut bytes).unwrap();
+        assert!(String::from_utf8(bytes.clone())
+            .unwrap()
+            .contains("\n10 8 3 lives\n"));
+
+        let read = Replay::read(bytes.as_slice()).unwrap();
+        let mut replayed = read.initial_state();
+        for tick in &read.ticks {
+            Replay::apply(&mut replayed, *tick);
+        }
+        assert_eq!(replayed.lives, 2);
+        assert_eq!(replayed.snake.head, state.snake.head);
+        assert_eq!(replayed.outcome(), None);
+    }
+
+    #[test]
+    fn test_replay_closes_the_arena_in_on_the_same_ticks() {
+        let mut state = GameState::with_seed(20, 12, 8).with_mode(GameMode::Shrinking);
+        play(&mut state, &[(Some(Direction::Up), false); 2]);
+        state.shrink();
+        play(&mut state, &[(Some(Direction::Right), false); 3]);
+        state.shrink();
+        play(&mut state, &[(None, false); 2]);
+        assert_eq!(state.shrinks, [2, 5]);
+
+        let mut bytes = Vec::new();
+        Replay::from_state(&state).write(&mut bytes).unwrap();
+        assert!(String::from_utf8(bytes.clone())
+            .unwrap()
+            .contains("\n20 12 8 shrinking shrinks=2,5\n"));
+
+        let read = Replay::read(bytes.as_slice()).unwrap();
+        let mut replayed = read.initial_state();
+        for tick in &read.ticks {
+            Replay::apply(&mut replayed, *tick);
+        }
+        assert_eq!(replayed.bounds, state.bounds);
+        assert_eq!(replayed.shrinks, state.shrinks);
+        assert_eq!(replayed.foods, state.foods);
+        assert_eq!(replayed.snake.head, state.snake.head);
+        assert_eq!(replayed.next_shrink, None);
+    }
+
+    #[test]
+    fn test_replay_races_the_same_rival() {
+        let mut state = GameState::with_seed(20, 12, 5).with_rival(3);
+        let turns = [
+            Direction::Down,
+            Direction::Left,
+            Direction::Up,
+            Direction::Right,
+        ];
+        for tick in 0..80 {
+            let turn = (tick % 4 == 0).then(|| turns[tick / 4 % 4]);
+            play(&mut state, &[(turn, false)]);
+        }
+        let rival = state.rival.as_ref().unwrap();
+        assert!(rival.score > 0);
+
+        let mut bytes = Vec::new();
+        Replay::from_state(&state).write(&mut bytes).unwrap();
+        assert!(String::from_utf8(bytes.clone())
+            .unwrap()
+            .contains("\n20 12 5 rival=3\n"));
+        let read = Replay::read(bytes.as_slice()).unwrap();
+        assert_eq!(read.rival, Some(3));
+        let mut replayed = read.initial_state();
+        for tick in &read.ticks {
+            Replay::apply(&mut replayed, *tick);
+        }
+        let replayed_rival = replayed.rival.as_ref().unwrap();
+        assert_eq!(replayed_rival.snake.head, rival.snake.head);
+        assert_eq!(replayed_rival.snake.tail, rival.snake.tail);
+        assert_eq!(replayed_rival.score, rival.score);
+        assert_eq!(replayed.foods, state.foods);
+
+        assert!(Replay::read("rust-snake-replay 4\n20 12 5 rival=0\n..\n".as_bytes()).is_err());
+        // The rival planned differently before, so it would not race the same
+        let old = Replay::read("rust-snake-replay 3\n20 12 5 rival=3\n..\n".as_bytes());
+        assert!(old.unwrap_err().to_string().contains("older rival"));
+    }
+
+    #[test]
+    fn test_replay_keeps_the_start_length() {
+        let mut state = GameState::with_seed(20, 12, 6).with_start_length(4);
+        play(&mut state, &[(Some(Direction::Up), false), (None, true)]);
+
+        let mut bytes = Vec::new();
+        Replay::from_state(&state).write(&mut bytes).unwrap();
+        assert!(String::from_utf8(bytes.clone())
+            .unwrap()
+            .contains("\n20 12 6 length=4\n"));
+
+        let read = Replay::read(bytes.as_slice()).unwrap();
+        let mut replayed = read.initial_state();
+        for tick in &read.ticks {
+            Replay::apply(&mut replayed, *tick);
+        }
+        assert_eq!(replayed.snake.tail, state.snake.tail);
+        assert_eq!(replayed.foods, state.foods);
+        assert!(Replay::read("rust-snake-replay 1\n20 12 6 length=x\n.\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_replay_keeps_practice() {
+        let mut state = GameState::with_seed(10, 8, 2);
+        state.practice = true;
+        play(&mut state, &[(None, false); 6]);
+        assert_eq!(state.outcome(), None);
+
+        let mut bytes = Vec::new();
+        Replay::from_state(&state).write(&mut bytes).unwrap();
+        assert!(String::from_utf8(bytes.clone())
+            .unwrap()
+            .contains("\n10 8 2 practice\n"));
+
+        let read = Replay::read(bytes.as_slice()).unwrap();
+        let mut replayed = read.initial_state();
+        for tick in &read.ticks {
+            Replay::apply(&mut replayed, *tick);
+        }
+        assert_eq!(replayed.snake.head, state.snake.head);
+        assert_eq!(replayed.outcome(), None);
+    }
+
+    #[test]
+    fn test_replay_keeps_both_players_turns() {
+        let mut state = GameState::with_seed(20, 12, 5).with_second_player();
+        let turns = [
+            None,
+            Some(Direction::Down),
+            None,
+            Some(Direction::Right),
+            None,
+        ];
+        for (tick, turn) in turns.into_iter().enumerate() {
+            let player_turn = (tick == 2).then_some(Direction::Up);
+            let mut action = state.player_action(player_turn);
+            action.second_turn = turn;
+            state.next(action);
+        }
+
+        let mut bytes = Vec::new();
+        Replay::from_state(&state).write(&mut bytes).unwrap();
+        let text = String::from_utf8(bytes.clone()).unwrap();
+        assert!(text.contains("\n20 12 5 two-player\n"));
+        assert!(text.ends_with("\n..U..\n.D.R.\n"), "{}", text);
+
+        let read = Replay::read(bytes.as_slice()).unwrap();
+        let mut replayed = read.initial_state();
+        for tick in &read.ticks {
+            Replay::apply(&mut replayed, *tick);
+        }
+        let (second, replayed_second) = (state.rival.unwrap(), replayed.rival.unwrap());
+        assert_eq!(replayed_second.snake.head, second.snake.head);
+        assert_eq!(replayed.snake.head, state.snake.head);
+
+        let short = "rust-snake-replay 1\n20 12 5 two-player\n...\n..\n";
+        assert!(Replay::read(short.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_replay_keeps_the_wall_mode() {
+        let mut state = GameState::with_seed(10, 8, 3);
+        state.wall_mode = WallMode::Wrap;
+        play(&mut state, &[(None, false); 6]);
+
+        let replay = Replay::from_state(&state);
+        let mut bytes = Vec::new();
+        replay.write(&mut bytes).unwrap();
+        assert!(String::from_utf8(bytes.clone())
+            .unwrap()
+            .contains("\n10 8 3 wrap\n"));
+
+        let read = Replay::read(bytes.as_slice()).unwrap();
+        let mut replayed = read.initial_state();
+        for tick in &read.ticks {
+            Replay::apply(&mut replayed, *tick);
+        }
+        assert_eq!(replayed.snake.head, state.snake.head);
+        assert_eq!(replayed.outcome(), None);
+    }
+
+    #[test]
+    fn test_replay_keeps_the_food_count() {
+        let mut state = GameState::with_seed(30, 15, 8).with_food_count(4);
+        play(&mut state, &[(None, true), (Some(Direction::Up), true)]);
+
+        let replay = Replay::from_state(&state);
+        let mut bytes = Vec::new();
+        replay.write(&mut bytes).unwrap();
+        let read = Replay::read(bytes.as_slice()).unwrap();
+        assert_eq!(read.food_count, 4);
+
+        let mut replayed = read.initial_state();
+        for tick in &read.ticks {
+            Replay::apply(&mut replayed, *tick);
+        }
+        assert_eq!(replayed.foods, state.foods);
+    }
+
+    #[test]
+    fn test_reject_malformed_replays() {
+        assert!(Replay::read("not a replay\n".as_bytes()).is_err());
+        assert!(Replay::read("rust-snake-replay 5\n30 15 1\n.\n".as_bytes()).is_err());
+        assert!(Replay::read("rust-snake-replay 0\n30 15 1\n.\n".as_bytes()).is_err());
+        assert!(Replay::read("rust-snake-replay 2\nplayer x\n".as_bytes()).is_err());
+        assert!(Replay::read("rust-snake-replay 1\n30 15\n.\n".as_bytes()).is_err());
+        assert!(Replay::read("rust-snake-replay 1\n30 15 1\n.x\n".as_bytes()).is_err());
+        assert!(Replay::read("rust-snake-replay 1\n".as_bytes()).is_err());
+        assert!(Replay::read("rust-snake-replay 1\n30 15 1 lava\n.\n".as_bytes()).is_err());
+        assert!(Replay::read("rust-snake-replay 1\n30 15 1 food=0\n.\n".as_bytes()).is_err());
+        let tiny = Replay::read("rust-snake-replay 1\n2 2 1\n.\n".as_bytes()).unwrap_err();
+        assert_eq!(tiny.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_replay_keeps_the_map() {
+        let map = "#######\n#.....#\n#S.#..#\n#.....#\n#######\n";
+        let mut state = GameState::from_level(level::parse_map(map).unwrap(), 4);
+        play(
+            &mut state,
+            &[
+                (Some(Direction::Down), false),
+                (Some(Direction::Right), false),
+            ],
+        );
+
+        let replay = Replay::from_state(&state);
+        let mut bytes = Vec::new();
+        replay.write(&mut bytes).unwrap();
+        let text = String::from_utf8(bytes.clone()).unwrap();
+        assert!(text.starts_with("rust-snake-replay 4\n7 5 4 map\n#######\n"));
+
+        let read = Replay::read(bytes.as_slice()).unwrap();
+        assert_eq!(read, replay);
+        let mut replayed = read.initial_state();
+        for tick in &read.ticks {
+            Replay::apply(&mut replayed, *tick);
+        }
+        assert_eq!(replayed.obstacles, state.obstacles);
+        assert_eq!(replayed.snake.head, state.snake.head);
+    }
+}
@@ -0,0 +1,196 @@
+use crate::{
+    controller::{AutoController, Controller},
+    game::{Position, Snake},
+};
+
+// A computer-controlled snake racing the player for the same food, steered
+// by an `AutoController`, or in a two-player game the second player's snake
+#[derive(Debug, Clone)]
+pub struct Rival {
+    pub snake: Snake,
+    pub score: u32,
+    // Times it crashed and started over
+    pub crashes: u32,
+    pub controller: AutoController,
+    // Steered by the turns in each `Action`, and a crash ends the game
+    pub second_player: bool,
+}
+
+impl Rival {
+    pub fn new(start: Position, replan_every: u32) -> Self {
+        Self {
+            snake: Snake::new(start.x, start.y),
+            score: 0,
+            crashes: 0,
+            controller: AutoController::new(replan_every),
+            second_player: false,
+        }
+    }
+
+    pub fn second_player(start: Position) -> Self {
+        Self {
+            second_player: true,
+            ..Self::new(start, 1)
+        }
+    }
+
+    // Starts over from `start` with just a head, keeping the score
+    pub fn restart(&mut self, start: Position) {
+        self.snake = Snake::new(start.x, start.y);
+        self.crashes += 1;
+        self.controller.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{Action, Direction, Food, GameOutcome, GameState};
+
+    // The player goes round a 2x2 square in the middle, out of the way
+    fn circle(state: &mut GameState) {
+        let turns = [
+            Direction::Down,
+            Direction::Left,
+            Direction::Up,
+            Direction::Right,
+        ];
+        let turn = turns[state.ticks as usize % turns.len()];
+        state.next(Action::new(state.snake.head, Some(turn), false));
+    }
+
+    #[test]
+    fn test_rival_heads_straight_for_the_food() {
+        let mut state = GameState::with_seed(20, 12, 1).with_rival(1);
+        assert_eq!(
+            state.rival.as_ref().unwrap().snake.head,
+            Position::new(2, 2)
+        );
+        state.foods[0] = Food::at(Position::new(4, 5));
+
+        for _ in 0..4 {
+            circle(&mut state);
+        }
+        let rival = state.rival.as_ref().unwrap();
+        assert_eq!(rival.snake.len(), 1);
+        circle(&mut state);
+        let rival = state.rival.as_ref().unwrap();
+        assert_eq!(rival.snake.head, Position::new(4, 5));
+        assert_eq!(rival.snake.len(), 2);
+        assert_eq!(rival.score, 1);
+        assert_eq!(state.score, 0);
+        assert_ne!(state.foods[0].position, Position::new(4, 5));
+    }
+
+    #[test]
+    fn test_rival_keeps_moving_when_no_food_is_in_reach() {
+        // Walled into the top left corner, the food on the other side
+        let mut state = GameState::with_seed(12, 10, 1)
+            .with_obstacles((1..5).map(|y| Position::new(5, y)))
+            .with_obstacles((1..5).map(|x| Position::new(x, 5)))
+            .with_rival(1);
+        state.foods[0] = Food::at(Position::new(9, 8));
+        for _ in 0..20 {
+            circle(&mut state);
+            let rival = state.rival.as_ref().unwrap();
+            assert_eq!(rival.crashes, 0);
+            assert!(rival.snake.head.x < 5 && rival.snake.head.y < 5);
+        }
+    }
+
+    #[test]
+    fn test_rival_eats_for_hundreds_of_ticks_without_crashing() {
+        for seed in 0..5 {
+            let mut state = GameState::with_seed(16, 12, seed).with_rival(1);
+            for _ in 0..300 {
+                circle(&mut state);
+            }
+            let rival = state.rival.as_ref().unwrap();
+            assert_eq!(rival.crashes, 0, "seed {}", seed);
+            assert!(rival.score >= 20, "seed {}: {}", seed, rival.score);
+        }
+    }
+
+    #[test]
+    fn test_undo_takes_the_rival_back_too() {
+        let mut state = GameState::with_seed(16, 12, 2).with_rival(1);
+        let mut history = Vec::new();
+        for _ in 0..60 {
+            let rival = state.rival.as_ref().unwrap();
+            history.push((rival.snake.clone(), rival.score, state.foods.clone()));
+            circle(&mut state);
+        }
+        assert!(state.rival.as_ref().unwrap().score > 0);
+        while let Some((snake, score, foods)) = history.pop() {
+            state.undo().unwrap();
+            let rival = state.rival.as_ref().unwrap();
+            assert_eq!(rival.snake.head, snake.head);
+            assert_eq!(rival.snake.tail, snake.tail);
+            assert_eq!(rival.score, score);
+            assert_eq!(state.foods, foods);
+        }
+    }
+
+    #[test]
+    fn test_running_into_the_rival_ends_the_game() {
+        let mut state = GameState::with_seed(20, 12, 1).with_rival(4);
+        state.foods[0] = Food::at(Position::new(17, 10));
+        // Going up across the player's way, which it only leaves next tick
+        let rival = state.rival.as_mut().unwrap();
+        rival.snake.head = Position::new(11, 4);
+        rival.snake.direction = Direction::Up;
+        rival.snake.tail = (5..8).map(|y| Position::new(11, y)).collect();
+        state.next(Action::new(state.snake.head, None, false));
+        assert_eq!(state.snake.head, Position::new(11, 6));
+        assert_eq!(state.outcome(), Some(GameOutcome::RivalCollision));
+    }
+
+    fn two_player_tick(state: &mut GameState, second_turn: Option<Direction>) {
+        let mut action = state.player_action(None);
+        action.second_turn = second_turn;
+        state.next(action);
+    }
+
+    #[test]
+    fn test_second_player_steers_and_scores_on_their_own() {
+        let mut state = GameState::with_seed(20, 12, 1).with_second_player();
+        assert!(state.is_two_player());
+        assert!(!state.is_ranked());
+        state.foods[0] = Food::at(Position::new(2, 4));
+
+        two_player_tick(&mut state, Some(Direction::Down));
+        two_player_tick(&mut state, None);
+        let second = state.rival.as_ref().unwrap();
+        assert_eq!(second.snake.head, Position::new(2, 4));
+        assert_eq!((state.score, second.score), (0, 1));
+
+        // Into the left wall, where it stays for the game to end
+        two_player_tick(&mut state, Some(Direction::Left));
+        assert_eq!(state.outcome(), None);
+        two_player_tick(&mut state, None);
+        let second = state.rival.as_ref().unwrap();
+        assert_eq!(second.snake.head, Position::new(0, 4));
+        assert_eq!(second.crashes, 0);
+        assert_eq!(state.outcome(), Some(GameOutcome::SecondPlayerCrashed));
+        assert!(!GameOutcome::SecondPlayerCrashed.is_collision());
+    }
+
+    #[test]
+    fn test_heads_meeting_on_the_food_is_a_draw() {
+        let mut state = GameState::with_seed(20, 12, 1).with_second_player();
+        state.foods[0] = Food::at(Position::new(11, 6));
+        let second = state.rival.as_mut().unwrap();
+        second.snake.head = Position::new(12, 6);
+        second.snake.direction = Direction::Left;
+
+        two_player_tick(&mut state, None);
+        assert_eq!(state.snake.head, Position::new(11, 6));
+        assert_eq!(
+            state.rival.as_ref().unwrap().snake.head,
+            Position::new(11, 6)
+        );
+        // The first player moved first and got to the food
+        assert_eq!((state.score, state.rival.as_ref().unwrap().score), (1, 0));
+        assert_eq!(state.outcome(), Some(GameOutcome::BothCrashed));
+    }
+}
@@ -0,0 +1,378 @@
+use crate::{game::GameOutcome, replay::Replay};
+use std::{
+    fs,
+    io::{self, Read, Write},
+    path::Path,
+    time::Duration,
+};
+
+// A run card bundles everything about a finished game into one file that can
+// be passed around:
+//
+//   b"SNAKECARD" | version: u16 | sections...
+//
+// Every section is a four byte tag, a u32 length and that many bytes, all
+// little endian. Unknown tags are skipped so newer cards stay readable.
+//
+//   SUMM  the summary as `key=value` lines. Version 1 cards only had the wall
+//         mode, as `mode`, and neither the game mode nor the duration, which
+//         their replay is played through for.
+//   RPLY  FNV-1a checksum of the replay (u64), then the run-length encoded replay
+//   BORD  the final board as text
+//   EVNT  one line per notable tick
+const MAGIC: &[u8] = b"SNAKECARD";
+const VERSION: u16 = 2;
+const GAME_MODE_VERSION: u16 = 2;
+
+const SUMMARY: [u8; 4] = *b"SUMM";
+const REPLAY: [u8; 4] = *b"RPLY";
+const BOARD: [u8; 4] = *b"BORD";
+const EVENTS: [u8; 4] = *b"EVNT";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Summary {
+    pub score: u32,
+    pub length: usize,
+    pub ticks: u64,
+    // Game time, the pauses left out
+    pub duration: Duration,
+    // The game mode, classic, lives and so on, and the wall mode apart
+    pub mode: String,
+    pub walls: String,
+    pub seed: u64,
+    pub outcome: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunCard {
+    pub summary: Summary,
+    pub replay: Replay,
+    pub board: String,
+    pub events: String,
+}
+
+impl RunCard {
+    // Plays the replay through to work out the summary, board and events
+    pub fn from_replay(replay: Replay) -> Self {
+        let mut state = replay.initial_state();
+        let mut events = String::new();
+        for tick in &replay.ticks {
+            Replay::apply(&mut state, *tick);
+            if let Some(direction) = tick.change_direction {
+                events.push_str(&format!("{} turn {:?}\n", state.ticks, direction));
+            }
+            if tick.must_grow {
+                events.push_str(&format!("{} food {}\n", state.ticks, state.score));
+            }
+        }
+        let outcome = outcome_name(state.outcome());
+        events.push_str(&format!("{} end {}\n", state.ticks, outcome));
+
+        Self {
+            summary: Summary {
+                score: state.score,
+                length: state.snake.len(),
+                ticks: state.ticks,
+                duration: state.game_time,
+                mode: replay.mode.name().to_string(),
+                walls: replay.wall_mode.to_string(),
+                seed: replay.seed,
+                outcome: outcome.to_string(),
+            },
+            board: state.to_ascii(),
+            events,
+            replay,
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut file = io::BufWriter::new(fs::File::create(path)?);
+        self.write(&mut file)?;
+        file.flush()
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Self::read(io::BufReader::new(fs::File::open(path)?))
+    }
+
+    pub fn write(&self, w: &mut impl Write) -> io::Result<()> {
+        w.write_all(MAGIC)?;
+        w.write_all(&VERSION.to_le_bytes())?;
+
+        let mut replay = Vec::new();
+        self.replay.write(&mut replay)?;
+        let mut replay_section = checksum(&replay).to_le_bytes().to_vec();
+        replay_section.extend(compress(&replay));
+
+        write_section(w, SUMMARY, self.summary.to_text().as_bytes())?;
+        write_section(w, REPLAY, &replay_section)?;
+        write_section(w, BOARD, self.board.as_bytes())?;
+        write_section(w, EVENTS, self.events.as_bytes())
+    }
+
+    pub fn read(mut r: impl Read) -> io::Result<Self> {
+        let mut bytes = Vec::new();
+        r.read_to_end(&mut bytes)?;
+
+        let rest = bytes
+            .strip_prefix(MAGIC)
+            .ok_or_else(|| invalid("not a rust-snake run card".to_string()))?;
+        let (version, mut rest) = split(rest, 2)?;
+        let version = u16::from_le_bytes([version[0], version[1]]);
+        if version == 0 || version > VERSION {
+            return Err(invalid(format!("unsupported run card version {}", version)));
+        }
+
+        let (mut summary, mut replay, mut board, mut events) = (None, None, None, None);
+        while !rest.is_empty() {
+            let (tag, after_tag) = split(rest, 4)?;
+            let (length, after_length) = split(after_tag, 4)?;
+            let length = u32::from_le_bytes(length.try_into().unwrap()) as usize;
+            let (data, after_data) = split(after_length, length)?;
+            rest = after_data;
+
+            match <[u8; 4]>::try_from(tag).unwrap() {
+                SUMMARY => summary = Some(text(data, "summary")?),
+                REPLAY => replay = Some(read_replay(data)?),
+                BOARD => board = Some(text(data, "board")?),
+                EVENTS => events = Some(text(data, "event log")?),
+                _ => {}
+            }
+        }
+
+        let missing = |what: &str| invalid(format!("run card has no {} section", what));
+        let summary = summary.ok_or_else(|| missing("summary"))?;
+        let replay = replay.ok_or_else(|| missing("replay"))?;
+        let summary = if version < GAME_MODE_VERSION {
+            Summary::from_old_text(&summary, &replay)?
+        } else {
+            Summary::from_text(&summary)?
+        };
+        Ok(Self {
+            summary,
+            replay,
+            board: board.ok_or_else(|| missing("board"))?,
+            events: events.ok_or_else(|| missing("event log"))?,
+        })
+    }
+}
+
+impl Summary {
+    // The duration in milliseconds
+    fn to_text(&self) -> String {
+        format!(
+            "score={}\nlength={}\nticks={}\nduration={}\nmode={}\nwalls={}\nseed={}\noutcome={}\n",
+            self.score,
+            self.length,
+            self.ticks,
+            self.duration.as_millis(),
+            self.mode,
+            self.walls,
+            self.seed,
+            self.outcome
+        )
+    }
+
+    fn from_text(text: &str) -> io::Result<Self> {
+        let field = |key: &str| {
+            text.lines()
+                .find_map(|line| line.strip_prefix(key)?.strip_prefix('='))
+                .ok_or_else(|| invalid(format!("summary is missing '{}'", key)))
+        };
+        let number = |key: &str| {
+            field(key)?
+                .parse::<u64>()
+                .map_err(|_| invalid(format!("invalid summary value for '{}'", key)))
+        };
+        let out_of_range = |key: &str| invalid(format!("summary value for '{}' is too big", key));
+        Ok(Self {
+            score: u32::try_from(number("score")?).map_err(|_| out_of_range("score"))?,
+            length: usize::try_from(number("length")?).map_err(|_| out_of_range("length"))?,
+            ticks: number("ticks")?,
+            duration: Duration::from_millis(number("duration")?),
+            mode: field("mode")?.to_string(),
+            walls: field("walls")?.to_string(),
+            seed: number("seed")?,
+            outcome: field("outcome")?.to_string(),
+        })
+    }
+
+    // A version 1 summary, where `mode` was the wall mode, given the game mode
+    // and duration it didn't keep by playing the replay through
+    fn from_old_text(text: &str, replay: &Replay) -> io::Result<Self> {
+        let played = RunCard::from_replay(replay.clone()).summary;
+        let text = format!(
+            "{}duration={}\nmode={}\n",
+            text.replace("\nmode=", "\nwalls="),
+            played.duration.as_millis(),
+            played.mode
+        );
+        Self::from_text(&text)
+    }
+}
+
+fn outcome_name(outcome: Option<GameOutcome>) -> &'static str {
+    outcome.map_or("quit", GameOutcome::name)
+}
+
+fn write_section(w: &mut impl Write, tag: [u8; 4], data: &[u8]) -> io::Result<()> {
+    w.write_all(&tag)?;
+    w.write_all(&(data.len() as u32).to_le_bytes())?;
+    w.write_all(data)
+}
+
+fn read_replay(data: &[u8]) -> io::Result<Replay> {
+    let (expected, compressed) = split(data, 8)?;
+    let expected = u64::from_le_bytes(expected.try_into().unwrap());
+    let replay = decompress(compressed)?;
+    if checksum(&replay) != expected {
+        return Err(invalid("replay checksum mismatch".to_string()));
+    }
+    Replay::read(replay.as_slice())
+}
+
+fn split(bytes: &[u8], at: usize) -> io::Result<(&[u8], &[u8])> {
+    if bytes.len() < at {
+        return Err(invalid("run card is truncated".to_string()));
+    }
+    Ok(bytes.split_at(at))
+}
+
+fn text(data: &[u8], what: &str) -> io::Result<String> {
+    String::from_utf8(data.to_vec()).map_err(|_| invalid(format!("{} is not valid UTF-8", what)))
+}
+
+// FNV-1a, enough to catch a damaged or edited replay
+fn checksum(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+// Replays are mostly long runs of `.`, so (count, byte) pairs shrink them a lot
+fn compress(bytes: &[u8]) -> Vec<u8> {
+    let mut compressed = Vec::new();
+    for chunk in bytes.chunk_by(|a, b| a == b) {
+        for run in chunk.chunks(u8::MAX as usize) {
+            compressed.push(run.len() as u8);
+            compressed.push(run[0]);
+        }
+    }
+    compressed
+}
+
+fn decompress(compressed: &[u8]) -> io::Result<Vec<u8>> {
+    if !compressed.len().is_multiple_of(2) {
+        return Err(invalid("replay data is truncated".to_string()));
+    }
+    let mut bytes = Vec::new();
+    for pair in compressed.chunks(2) {
+        bytes.extend(std::iter::repeat_n(pair[1], pair[0] as usize));
+    }
+    Ok(bytes)
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::DEFAULT_STEP_INTERVAL;
+
+    const FIXTURE: &[u8] = include_bytes!("../fixtures/example.runcard");
+    const V1_FIXTURE: &[u8] = include_bytes!("../fixtures/example-v1.runcard");
+
+    // Two steps right, up to grow, then left along the top row into the wall
+    fn example_replay() -> Replay {
+        Replay::read("rust-snake-replay 1\n10 8 42\n..U+.L......\n".as_bytes()).unwrap()
+    }
+
+    fn example_card() -> RunCard {
+        RunCard::from_replay(example_replay())
+    }
+
+    #[test]
+    fn test_run_card_round_trip() {
+        let card = example_card();
+        assert_eq!(card.summary.score, 1);
+        assert_eq!(card.summary.length, 2);
+        assert_eq!(card.summary.outcome, "wall");
+        assert_eq!(card.summary.duration, DEFAULT_STEP_INTERVAL * 12);
+        assert_eq!(card.summary.mode, "classic");
+        assert_eq!(card.summary.walls, "solid");
+        assert!(card.events.contains("4 food 1\n"));
+
+        let mut bytes = Vec::new();
+        card.write(&mut bytes).unwrap();
+        assert_eq!(RunCard::read(bytes.as_slice()).unwrap(), card);
+
+        let replay = b"rust-snake-replay 1\n30 15 1\n..........+\n";
+        assert_eq!(decompress(&compress(replay)).unwrap(), replay);
+    }
+
+    #[test]
+    fn test_corrupted_run_cards_are_rejected() {
+        let mut bytes = Vec::new();
+        example_card().write(&mut bytes).unwrap();
+
+        // Flip a byte inside the compressed replay
+        let replay_start = bytes.windows(4).position(|tag| tag == REPLAY).unwrap();
+        let mut damaged = bytes.clone();
+        damaged[replay_start + 4 + 4 + 8 + 1] ^= 0x01;
+        let err = RunCard::read(damaged.as_slice()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        assert!(RunCard::read(&bytes[..bytes.len() - 1]).is_err());
+        assert!(RunCard::read(&b"not a card"[..]).is_err());
+
+        let mut future = bytes.clone();
+        future[MAGIC.len()] = 3;
+        assert!(RunCard::read(future.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_only_the_replay_of_a_card_is_trusted() {
+        let mut forged = example_card();
+        forged.summary.score = 9_999;
+        forged.board = "\x1b[2J".to_string();
+        let mut bytes = Vec::new();
+        forged.write(&mut bytes).unwrap();
+
+        // The checksum covers the replay alone, playing it through again
+        // brings back what really happened
+        let card = RunCard::read(bytes.as_slice()).unwrap();
+        assert_eq!(card.summary.score, 9_999);
+        assert_eq!(RunCard::from_replay(card.replay), example_card());
+
+        let summary = "score=4294967296\nlength=1\nticks=1\nduration=75\nmode=classic\n\
+                       walls=solid\nseed=1\noutcome=wall\n";
+        let err = Summary::from_text(summary).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_fixture_format_is_stable() {
+        let card = RunCard::read(FIXTURE).unwrap();
+        assert_eq!(card, example_card());
+
+        let mut bytes = Vec::new();
+        card.write(&mut bytes).unwrap();
+        assert_eq!(bytes, FIXTURE);
+    }
+
+    #[test]
+    fn test_version_1_cards_get_the_game_mode_and_duration_from_the_replay() {
+        // Its summary's `mode` is the wall mode, and it has no duration
+        let text = String::from_utf8_lossy(V1_FIXTURE);
+        assert!(text.contains("ticks=12\nmode=solid\nseed=42"));
+        assert!(!text.contains("duration="));
+
+        let card = RunCard::read(V1_FIXTURE).unwrap();
+        assert_eq!(card.summary.mode, "classic");
+        assert_eq!(card.summary.walls, "solid");
+        assert_eq!(card.summary.duration, DEFAULT_STEP_INTERVAL * 12);
+        assert_eq!(card, example_card());
+    }
+}
@@ -0,0 +1,145 @@
+use crate::{difficulty::Difficulty, game::GameMode};
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+const HIGH_SCORE_FILE: &str = "highscore";
+const LAST_NAME_FILE: &str = "last-name";
+
+// $XDG_DATA_HOME/rust-snake, falling back to ~/.local/share/rust-snake and
+// finally the current directory
+pub fn data_dir() -> PathBuf {
+    if let Some(dir) = env::var_os("XDG_DATA_HOME").filter(|dir| !dir.is_empty()) {
+        return PathBuf::from(dir).join("rust-snake");
+    }
+    if let Some(home) = env::var_os("HOME").filter(|home| !home.is_empty()) {
+        return PathBuf::from(home).join(".local/share/rust-snake");
+    }
+    PathBuf::from(".")
+}
+
+// Normal classic games keep the file from before there were difficulties and
+// modes, the other modes add their name after the difficulty
+pub fn high_score_file(difficulty: Difficulty, mode: GameMode) -> String {
+    data_file(HIGH_SCORE_FILE, difficulty, mode)
+}
+
+// `base` with the difficulty and the mode after it, unless they are the
+// normal and classic ones
+pub fn data_file(base: &str, difficulty: Difficulty, mode: GameMode) -> String {
+    let mut file = match difficulty {
+        Difficulty::Normal => base.to_string(),
+        _ => format!("{}-{}", base, difficulty.name()),
+    };
+    if mode != GameMode::Classic {
+        file = format!("{}-{}", file, mode.name());
+    }
+    file
+}
+
+pub fn load_high_score(difficulty: Difficulty, mode: GameMode) -> u32 {
+    load_high_score_from(&data_dir().join(high_score_file(difficulty, mode)))
+}
+
+pub fn save_high_score(difficulty: Difficulty, mode: GameMode, score: u32) -> io::Result<()> {
+    save_high_score_to(&data_dir().join(high_score_file(difficulty, mode)), score)
+}
+
+// Missing or corrupt files count as no high score at all
+pub fn load_high_score_from(path: &Path) -> u32 {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|contents| contents.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+pub fn save_high_score_to(path: &Path, score: u32) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, format!("{}\n", score))
+}
+
+// The name last played under, to start the menu with
+pub fn load_last_name() -> Option<String> {
+    load_last_name_from(&data_dir().join(LAST_NAME_FILE))
+}
+
+pub fn save_last_name(name: &str) -> io::Result<()> {
+    save_last_name_to(&data_dir().join(LAST_NAME_FILE), name)
+}
+
+// A blank name is as good as none
+pub fn load_last_name_from(path: &Path) -> Option<String> {
+    let contents = fs::read_to_string(path).ok()?;
+    let name = contents.lines().next()?.trim();
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+pub fn save_last_name_to(path: &Path, name: &str) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, format!("{}\n", name.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir()
+            .join(format!("rust-snake-test-{}", std::process::id()))
+            .join(name)
+    }
+
+    #[test]
+    fn test_high_score_round_trip() {
+        let path = temp_path("round_trip");
+        save_high_score_to(&path, 42).unwrap();
+        assert_eq!(load_high_score_from(&path), 42);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_high_scores_are_kept_per_difficulty_and_mode() {
+        let file = |difficulty| high_score_file(difficulty, GameMode::Classic);
+        assert_eq!(file(Difficulty::Normal), "highscore");
+        assert_eq!(file(Difficulty::Easy), "highscore-easy");
+        assert_eq!(file(Difficulty::Hard), "highscore-hard");
+
+        let file = |difficulty| high_score_file(difficulty, GameMode::Lives);
+        assert_eq!(file(Difficulty::Normal), "highscore-lives");
+        assert_eq!(file(Difficulty::Hard), "highscore-hard-lives");
+        assert_eq!(
+            high_score_file(Difficulty::Easy, GameMode::Timed),
+            "highscore-easy-timed"
+        );
+    }
+
+    #[test]
+    fn test_last_name_round_trip() {
+        let path = temp_path("last_name");
+        save_last_name_to(&path, "antoñito  ").unwrap();
+        assert_eq!(load_last_name_from(&path), Some("antoñito".to_string()));
+        save_last_name_to(&path, "").unwrap();
+        assert_eq!(load_last_name_from(&path), None);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(load_last_name_from(&path), None);
+    }
+
+    #[test]
+    fn test_missing_high_score_is_zero() {
+        assert_eq!(load_high_score_from(&temp_path("missing")), 0);
+    }
+
+    #[test]
+    fn test_corrupt_high_score_is_zero() {
+        let path = temp_path("corrupt");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "not a number").unwrap();
+        assert_eq!(load_high_score_from(&path), 0);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,250 @@
+// A stand-in terminal for tests: frames rendered into a `Vec<u8>` are played
+// onto a grid of cells that can be checked without a real terminal
+use crate::{
+    game::GameState,
+    menu::{SidePanel, StatusBar},
+    render::Renderer,
+    theme::Theme,
+};
+use crossterm::style::{Color, SetForegroundColor};
+use std::{env, fs, path::PathBuf};
+
+pub type Screen = Vec<Vec<(char, String, String)>>;
+
+// Plays terminal output onto a screen of cells, each holding its glyph and
+// the foreground and background codes it was printed with
+pub fn play(screen: &mut Screen, output: &[u8]) {
+    let output = String::from_utf8(output.to_vec()).unwrap();
+    let (mut x, mut y) = (0, 0);
+    let (mut foreground, mut background) = (String::new(), String::new());
+    let mut chars = output.chars();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            screen[y][x] = (c, foreground.clone(), background.clone());
+            x += 1;
+            continue;
+        }
+        assert_eq!(chars.next(), Some('['));
+        let mut parameters = String::new();
+        let command = loop {
+            match chars.next().unwrap() {
+                c if c.is_ascii_alphabetic() => break c,
+                c => parameters.push(c),
+            }
+        };
+        match (command, parameters.as_str()) {
+            ('H', _) => {
+                let (row, column) = parameters.split_once(';').unwrap();
+                y = row.parse::<usize>().unwrap() - 1;
+                x = column.parse::<usize>().unwrap() - 1;
+            }
+            ('m', "39") => foreground.clear(),
+            ('m', "49") => background.clear(),
+            ('m', "0") => {
+                foreground.clear();
+                background.clear();
+            }
+            ('m', _) if parameters.starts_with("38;") => foreground = parameters,
+            ('m', _) if parameters.starts_with("48;") => background = parameters,
+            // Bold on and off
+            ('m', "1") | ('m', "22") => {}
+            // The whole line the cursor is on
+            ('K', "2") => screen[y].fill((' ', String::new(), String::new())),
+            _ => panic!("unexpected sequence {:?}", parameters),
+        }
+    }
+}
+
+pub fn blank_screen(width: u16, height: u16) -> Screen {
+    vec![vec![(' ', String::new(), String::new()); width as usize]; height as usize]
+}
+
+// A frame as plain text, one string per row: the panel, snakes and food as
+// printed and the board's blocks by what they stand for, `#` wall and blank
+// for the floor. With the panel's ASCII glyphs the board is left as drawn.
+pub fn render_to_grid(state: &GameState, panel: &SidePanel) -> Vec<String> {
+    let theme = Theme {
+        glyphs: panel.glyphs,
+        ..Theme::default()
+    };
+    let mut output = Vec::new();
+    Renderer::new(state.game_width, state.game_height)
+        .render(&mut output, state, &theme)
+        .unwrap();
+    panel.queue(&mut output, &theme).unwrap();
+    let mut screen = blank_screen(panel.x + panel.width + 1, panel.height);
+    play(&mut screen, &output);
+    to_text(&screen, &theme)
+}
+
+// The same for a frame with the status bar under the board
+pub fn render_with_status_bar(state: &GameState, bar: &StatusBar) -> Vec<String> {
+    let theme = Theme::default();
+    let mut output = Vec::new();
+    Renderer::new(state.game_width, state.game_height)
+        .render(&mut output, state, &theme)
+        .unwrap();
+    bar.queue(&mut output).unwrap();
+    let mut screen = blank_screen(bar.width, bar.y + bar.lines().len() as u16);
+    play(&mut screen, &output);
+    to_text(&screen, &theme)
+}
+
+fn to_text(screen: &Screen, theme: &Theme) -> Vec<String> {
+    let blocks = [(theme.surface, '#'), (theme.background, ' ')]
+        .map(|(color, symbol)| (foreground_code(color), symbol));
+    screen
+        .iter()
+        .map(|row| {
+            let row: String = row
+                .iter()
+                .map(|(glyph, foreground, _)| match glyph {
+                    '█' => blocks
+                        .iter()
+                        .find(|(code, _)| code == foreground)
+                        .map_or('?', |&(_, symbol)| symbol),
+                    _ => *glyph,
+                })
+                .collect();
+            row.trim_end().to_string()
+        })
+        .collect()
+}
+
+// The parameters `play` records for text printed in `color`
+pub fn foreground_code(color: Color) -> String {
+    let code = SetForegroundColor(color).to_string();
+    code.trim_start_matches("\x1b[")
+        .trim_end_matches('m')
+        .to_string()
+}
+
+// Compares a grid with its fixture in `fixtures/snapshots`, or writes the
+// fixture with UPDATE_SNAPSHOTS set
+pub fn assert_snapshot(name: &str, grid: &[String]) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("fixtures/snapshots")
+        .join(format!("{}.txt", name));
+    let actual = grid.join("\n") + "\n";
+    if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, &actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path)
+        .unwrap_or_else(|err| panic!("no snapshot at {}: {}", path.display(), err));
+    assert!(
+        actual == expected,
+        "{} does not match its snapshot, expected:\n{}\ngot:\n{}",
+        name,
+        expected,
+        actual
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        game::{Food, Position, BONUS_LIFETIME},
+        keyboard::KeyBindings,
+        theme::GlyphSet,
+    };
+    use std::time::Duration;
+
+    fn panel(state: &GameState, name: &str) -> SidePanel {
+        panel_with(state, name, &GlyphSet::UNICODE)
+    }
+
+    fn panel_with(state: &GameState, name: &str, glyphs: &'static GlyphSet) -> SidePanel {
+        let bindings = KeyBindings::default();
+        let mut panel = SidePanel::new(
+            state.game_width,
+            state.game_height,
+            20,
+            name.to_string(),
+            42,
+            &bindings,
+            glyphs,
+        );
+        panel.update_player(name);
+        panel.update_speed(&state.speed.label(state.score));
+        panel
+    }
+
+    #[test]
+    fn test_snapshot_empty_board() {
+        let state = GameState::with_seed(30, 15, 1);
+        assert_snapshot("empty_board", &render_to_grid(&state, &panel(&state, "Al")));
+    }
+
+    #[test]
+    fn test_snapshot_snake_with_a_tail() {
+        let mut state = GameState::with_seed(30, 15, 1);
+        state.snake.head = Position::new(10, 5);
+        state.snake.tail = (7..10).rev().map(|x| Position::new(x, 5)).collect();
+        let mut panel = panel(&state, "Al");
+        panel.update_score(3);
+        assert_snapshot("snake_with_a_tail", &render_to_grid(&state, &panel));
+    }
+
+    #[test]
+    fn test_snapshot_food_next_to_a_corner() {
+        let mut state = GameState::with_seed(30, 15, 1);
+        state.foods[0] = Food::at(Position::new(1, 1));
+        let grid = render_to_grid(&state, &panel(&state, "Al"));
+        assert_snapshot("food_next_to_a_corner", &grid);
+    }
+
+    #[test]
+    fn test_snapshot_long_player_name() {
+        let state = GameState::with_seed(30, 15, 1);
+        let panel = panel(&state, "Bartholomew the Magnificent");
+        assert_snapshot("long_player_name", &render_to_grid(&state, &panel));
+    }
+
+    #[test]
+    fn test_snapshot_status_bar_under_the_board() {
+        let mut state = GameState::with_seed(30, 15, 1);
+        state.snake.head = Position::new(10, 5);
+        state.snake.tail = (7..10).rev().map(|x| Position::new(x, 5)).collect();
+        let mut bar = StatusBar::new(15, 30, "Bartholomew the Magnificent".to_string(), 42);
+        bar.update_score(3);
+        bar.update_elapsed(Duration::from_millis(83_900));
+        let grid = render_with_status_bar(&state, &bar);
+        assert_eq!(grid.len(), 17);
+        assert_snapshot("status_bar_under_the_board", &grid);
+
+        // Nothing of a longer score is left behind
+        bar.update_score(0);
+        let mut output = Vec::new();
+        bar.queue(&mut output).unwrap();
+        let mut screen = blank_screen(30, 17);
+        screen[15].fill(('x', String::new(), String::new()));
+        play(&mut screen, &output);
+        let line: String = screen[15].iter().map(|(glyph, _, _)| glyph).collect();
+        assert_eq!(line.trim_end(), "SCORE 0  MAX 42");
+    }
+
+    #[test]
+    fn test_snapshot_ascii_snake_with_a_tail() {
+        let mut state = GameState::with_seed(30, 15, 1);
+        state.snake.head = Position::new(10, 5);
+        state.snake.tail = (7..10).rev().map(|x| Position::new(x, 5)).collect();
+        let mut panel = panel_with(&state, "Al", &GlyphSet::ASCII);
+        panel.update_score(3);
+        panel.update_lives(2, 3);
+        panel.update_bonus(Some(BONUS_LIFETIME / 2));
+        assert_snapshot("ascii_snake_with_a_tail", &render_to_grid(&state, &panel));
+    }
+
+    #[test]
+    fn test_snapshot_ascii_food_next_to_a_corner() {
+        let mut state = GameState::with_seed(30, 15, 1);
+        state.foods[0] = Food::at(Position::new(1, 1));
+        let panel = panel_with(&state, "Al", &GlyphSet::ASCII);
+        let grid = render_to_grid(&state, &panel);
+        assert!(grid.iter().all(|row| row.is_ascii()));
+        assert_snapshot("ascii_food_next_to_a_corner", &grid);
+    }
+}
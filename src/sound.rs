@@ -0,0 +1,47 @@
+use std::{
+    io::{self, Write},
+    thread,
+    time::Duration,
+};
+
+const BELL: &[u8] = b"\x07";
+// Terminals fold bells that come too close together into one, so the two of
+// the game over cue are spaced out
+pub const DOUBLE_BELL_GAP: Duration = Duration::from_millis(150);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Cue {
+    Eat,
+    GameOver,
+}
+
+// Rings the terminal bell, written on its own after a frame was flushed so it
+// never lands in the middle of one
+pub fn play(w: &mut impl Write, cue: Cue) -> io::Result<()> {
+    w.write_all(BELL)?;
+    if cue == Cue::GameOver {
+        w.flush()?;
+        thread::sleep(DOUBLE_BELL_GAP);
+        w.write_all(BELL)?;
+    }
+    w.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn test_game_over_rings_twice() {
+        let mut output = Vec::new();
+        play(&mut output, Cue::Eat).unwrap();
+        assert_eq!(output, b"\x07");
+
+        let mut output = Vec::new();
+        let start = Instant::now();
+        play(&mut output, Cue::GameOver).unwrap();
+        assert_eq!(output, b"\x07\x07");
+        assert!(start.elapsed() >= DOUBLE_BELL_GAP);
+    }
+}
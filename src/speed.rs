@@ -0,0 +1,158 @@
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_SPEED_STEP: Duration = Duration::from_millis(5);
+pub const DEFAULT_SPEED_FLOOR: Duration = Duration::from_millis(40);
+pub const DEFAULT_POINTS_PER_STEP: u32 = 5;
+// How long a press of the boost key lasts. Terminals don't say when a key is
+// let go, so a boost is a burst rather than held for as long as the key is.
+pub const BOOST_LENGTH: Duration = Duration::from_secs(1);
+
+// How the time between two snake steps shrinks as the score goes up: `step`
+// shorter every `points_per_step` points, never below `floor`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Speed {
+    pub start: Duration,
+    pub step: Duration,
+    pub floor: Duration,
+    pub points_per_step: u32,
+}
+
+impl Speed {
+    pub fn new(start: Duration) -> Self {
+        Self {
+            start,
+            step: DEFAULT_SPEED_STEP,
+            floor: DEFAULT_SPEED_FLOOR,
+            points_per_step: DEFAULT_POINTS_PER_STEP,
+        }
+    }
+
+    pub fn constant(interval: Duration) -> Self {
+        Self {
+            start: interval,
+            step: Duration::ZERO,
+            floor: interval,
+            points_per_step: 1,
+        }
+    }
+
+    // How many steps it takes to reach the floor, 0 for a constant speed
+    pub fn max_level(&self) -> u32 {
+        let range = self.start.saturating_sub(self.floor);
+        if self.step.is_zero() || range.is_zero() {
+            return 0;
+        }
+        // Worked out in u64, a u128 division is slow and this runs a few
+        // times every tick
+        let nanos = |duration: Duration| u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX);
+        nanos(range).div_ceil(nanos(self.step)) as u32
+    }
+
+    pub fn level(&self, score: u32) -> u32 {
+        (score / self.points_per_step.max(1)).min(self.max_level())
+    }
+
+    pub fn interval(&self, score: u32) -> Duration {
+        let faster_by = self.step * self.level(score);
+        self.start
+            .saturating_sub(faster_by)
+            .max(self.floor.min(self.start))
+    }
+
+    // 1-based, for display
+    pub fn label(&self, score: u32) -> String {
+        format!("{}/{}", self.level(score) + 1, self.max_level() + 1)
+    }
+}
+
+// Steps taken at double speed for a moment. Only the wall clock is sped up,
+// each step still counts as a whole one of game time, so replays and combos
+// come out the same with or without it.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Boost {
+    until: Option<Instant>,
+}
+
+impl Boost {
+    pub fn start(&mut self, now: Instant) {
+        self.until = Some(now + BOOST_LENGTH);
+    }
+
+    pub fn stop(&mut self) {
+        self.until = None;
+    }
+
+    pub fn active(&self, now: Instant) -> bool {
+        self.until.is_some_and(|until| now < until)
+    }
+
+    // How long a step of `step` game time takes on the wall clock
+    pub fn interval(&self, step: Duration, now: Instant) -> Duration {
+        if self.active(now) {
+            step / 2
+        } else {
+            step
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: Duration = Duration::from_millis(1);
+
+    #[test]
+    fn test_speed_curve() {
+        let speed = Speed {
+            start: 100 * MS,
+            step: 10 * MS,
+            floor: 55 * MS,
+            points_per_step: 3,
+        };
+        assert_eq!(speed.max_level(), 5);
+        assert_eq!(speed.interval(0), 100 * MS);
+        assert_eq!(speed.interval(2), 100 * MS);
+        assert_eq!(speed.interval(3), 90 * MS);
+        assert_eq!(speed.interval(12), 60 * MS);
+        // The last step stops at the floor
+        assert_eq!(speed.interval(15), 55 * MS);
+        assert_eq!(speed.interval(u32::MAX), 55 * MS);
+
+        assert_eq!(speed.label(0), "1/6");
+        assert_eq!(speed.label(1000), "6/6");
+    }
+
+    #[test]
+    fn test_constant_speed() {
+        let speed = Speed::constant(75 * MS);
+        assert_eq!(speed.max_level(), 0);
+        assert_eq!(speed.interval(1000), 75 * MS);
+        assert_eq!(speed.label(1000), "1/1");
+    }
+
+    #[test]
+    fn test_floor_above_start_keeps_the_start() {
+        // Already faster than the default floor, so it never speeds up
+        let speed = Speed::new(20 * MS);
+        assert_eq!(speed.max_level(), 0);
+        assert_eq!(speed.interval(100), 20 * MS);
+    }
+
+    #[test]
+    fn test_a_boost_halves_steps_for_a_moment() {
+        let start = Instant::now();
+        let step = 80 * MS;
+        let mut boost = Boost::default();
+        assert_eq!(boost.interval(step, start), step);
+
+        boost.start(start);
+        assert_eq!(boost.interval(step, start), 40 * MS);
+        assert_eq!(boost.interval(step, start + BOOST_LENGTH - MS), 40 * MS);
+        assert_eq!(boost.interval(step, start + BOOST_LENGTH), step);
+
+        boost.start(start);
+        boost.stop();
+        assert_eq!(boost.interval(step, start), step);
+    }
+}
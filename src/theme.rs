@@ -1,45 +1,818 @@
+use crate::game::{Direction, EffectKind, FruitKind};
 use crossterm::style::Color;
+use std::{fmt, fs, io, path::Path};
 
-// Core monochrome palette
-pub const BACKGROUND: Color = Color::Rgb {
-    r: 18,
-    g: 18,
-    b: 18,
-}; // Almost black
-pub const SURFACE: Color = Color::Rgb {
-    r: 28,
-    g: 28,
-    b: 28,
-}; // Dark grey
-pub const TEXT: Color = Color::Rgb {
-    r: 255,
-    g: 255,
-    b: 255,
-}; // Pure white
-pub const INACTIVE: Color = Color::Rgb {
-    r: 128,
-    g: 128,
-    b: 128,
-}; // Medium grey
-
-// High contrast accents
-pub const SECONDARY: Color = Color::Rgb {
-    r: 255,
-    g: 88,
-    b: 88,
-}; // Vibrant red
-pub const PRIMARY: Color = Color::Rgb {
-    r: 88,
-    g: 255,
-    b: 158,
-}; // Bright mint green
-pub const ACCENT: Color = Color::Rgb {
-    r: 88,
-    g: 198,
-    b: 255,
-}; // Bright cyan
-pub const ACTIVE: Color = Color::Rgb {
-    r: 255,
-    g: 255,
-    b: 255,
-}; // Pure white
+// Every color the game draws with, by role. The snake head is `primary`, its
+// tail `secondary`, the border and obstacles `surface`, and apples `accent`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Theme {
+    pub name: &'static str,
+    pub background: Color,
+    pub surface: Color,
+    pub text: Color,
+    pub inactive: Color,
+    pub primary: Color,
+    pub secondary: Color,
+    pub accent: Color,
+    pub gold: Color,
+    pub cherry: Color,
+    pub banana: Color,
+    pub glyphs: &'static GlyphSet,
+    // What the colors above were brought down to for the terminal
+    pub colors: ColorSupport,
+}
+
+const fn rgb(r: u8, g: u8, b: u8) -> Color {
+    Color::Rgb { r, g, b }
+}
+
+// A share `t` of the way from `a` to `b`. Only RGB colors mix, any other
+// pair stays `a`.
+pub fn lerp_color(a: Color, b: Color, t: f32) -> Color {
+    let (
+        Color::Rgb { r, g, b: blue },
+        Color::Rgb {
+            r: r2,
+            g: g2,
+            b: b2,
+        },
+    ) = (a, b)
+    else {
+        return a;
+    };
+    let t = t.clamp(0.0, 1.0);
+    let mix =
+        |from: u8, to: u8| (f32::from(from) + (f32::from(to) - f32::from(from)) * t).round() as u8;
+    rgb(mix(r, r2), mix(g, g2), mix(blue, b2))
+}
+
+impl Theme {
+    // Monochrome surfaces with high contrast accents
+    pub const MINT: Theme = Theme {
+        name: "mint",
+        background: rgb(18, 18, 18),  // Almost black
+        surface: rgb(28, 28, 28),     // Dark grey
+        text: rgb(255, 255, 255),     // Pure white
+        inactive: rgb(128, 128, 128), // Medium grey
+        primary: rgb(88, 255, 158),   // Bright mint green
+        secondary: rgb(255, 88, 88),  // Vibrant red
+        accent: rgb(88, 198, 255),    // Bright cyan
+        gold: rgb(255, 200, 60),      // Warm gold
+        cherry: rgb(255, 120, 200),   // Pink
+        banana: rgb(240, 230, 140),   // Pale yellow
+        glyphs: &GlyphSet::UNICODE,
+        colors: ColorSupport::TrueColor,
+    };
+
+    pub const CLASSIC: Theme = Theme {
+        name: "classic",
+        background: rgb(10, 24, 10),
+        surface: rgb(40, 70, 40),
+        text: rgb(220, 255, 220),
+        inactive: rgb(110, 140, 110),
+        primary: rgb(170, 255, 60),
+        secondary: rgb(60, 180, 40),
+        accent: rgb(255, 70, 70),
+        gold: rgb(255, 210, 70),
+        cherry: rgb(200, 40, 120),
+        banana: rgb(250, 240, 120),
+        glyphs: &GlyphSet::UNICODE,
+        colors: ColorSupport::TrueColor,
+    };
+
+    pub const HIGH_CONTRAST: Theme = Theme {
+        name: "high-contrast",
+        background: rgb(0, 0, 0),
+        surface: rgb(255, 255, 255),
+        text: rgb(255, 255, 255),
+        inactive: rgb(190, 190, 190),
+        primary: rgb(255, 255, 0),
+        secondary: rgb(0, 200, 255),
+        accent: rgb(255, 0, 255),
+        gold: rgb(255, 140, 0),
+        cherry: rgb(255, 60, 60),
+        banana: rgb(0, 255, 0),
+        glyphs: &GlyphSet::UNICODE,
+        colors: ColorSupport::TrueColor,
+    };
+
+    // Shades of one color, like an old amber terminal
+    pub const AMBER: Theme = Theme {
+        name: "amber",
+        background: rgb(20, 12, 0),
+        surface: rgb(70, 42, 0),
+        text: rgb(255, 190, 60),
+        inactive: rgb(150, 95, 20),
+        primary: rgb(255, 210, 90),
+        secondary: rgb(200, 120, 0),
+        accent: rgb(255, 176, 0),
+        gold: rgb(255, 235, 170),
+        cherry: rgb(255, 140, 40),
+        banana: rgb(230, 160, 40),
+        glyphs: &GlyphSet::UNICODE,
+        colors: ColorSupport::TrueColor,
+    };
+
+    // Yellow, blue and purple from the Okabe-Ito set, which stay apart for
+    // red-green colorblind players where the mint red and green do not. The
+    // tail is a shade of the head rather than a hue of its own, and the glyphs
+    // tell the snake from the food by shape as well.
+    pub const COLORBLIND: Theme = Theme {
+        name: "colorblind",
+        background: rgb(16, 16, 24),
+        surface: rgb(44, 44, 60),
+        text: rgb(255, 255, 255),
+        inactive: rgb(140, 140, 150),
+        primary: rgb(240, 228, 66),   // Yellow
+        secondary: rgb(150, 140, 30), // Dim yellow, a shade of the head
+        accent: rgb(0, 114, 178),     // Blue
+        gold: rgb(255, 255, 255),     // White
+        cherry: rgb(204, 121, 167),   // Reddish purple
+        banana: rgb(86, 180, 233),    // Sky blue
+        glyphs: &GlyphSet::COLORBLIND,
+        colors: ColorSupport::TrueColor,
+    };
+
+    // Dark on a pale board, for terminals with a light background
+    pub const LIGHT: Theme = Theme {
+        name: "light",
+        background: rgb(245, 243, 235),
+        surface: rgb(190, 186, 175),
+        text: rgb(30, 30, 30),
+        inactive: rgb(120, 120, 115),
+        primary: rgb(0, 140, 70),
+        secondary: rgb(200, 50, 50),
+        accent: rgb(0, 100, 200),
+        gold: rgb(190, 130, 0),
+        cherry: rgb(180, 0, 110),
+        banana: rgb(130, 110, 0),
+        glyphs: &GlyphSet::UNICODE,
+        colors: ColorSupport::TrueColor,
+    };
+
+    pub const ALL: [Theme; 6] = [
+        Theme::MINT,
+        Theme::CLASSIC,
+        Theme::HIGH_CONTRAST,
+        Theme::AMBER,
+        Theme::COLORBLIND,
+        Theme::LIGHT,
+    ];
+
+    // A built-in theme by its name, as given to --theme
+    pub fn named(name: &str) -> Option<Theme> {
+        Theme::ALL.into_iter().find(|theme| theme.name == name)
+    }
+
+    // Named colors only, for terminals without 24-bit color. The snake, the
+    // fruits and the border each get their own of the 16 ANSI colors.
+    pub fn ansi_fallback() -> Theme {
+        Theme {
+            name: "ansi",
+            background: Color::Black,
+            surface: Color::DarkGrey,
+            text: Color::White,
+            inactive: Color::Grey,
+            primary: Color::Green,
+            secondary: Color::DarkGreen,
+            accent: Color::Cyan,
+            gold: Color::Yellow,
+            cherry: Color::Red,
+            banana: Color::DarkYellow,
+            glyphs: &GlyphSet::UNICODE,
+            colors: ColorSupport::Ansi16,
+        }
+    }
+
+    // Every built-in theme to pick from, led by `current` itself when it is
+    // a custom or fallback one
+    pub fn choices(current: Theme) -> Vec<Theme> {
+        let built_in = Theme::ALL.map(|theme| theme.drawn_like(&current));
+        let custom = (!built_in.contains(&current)).then_some(current);
+        custom.into_iter().chain(built_in).collect()
+    }
+
+    fn color_mut(&mut self, key: &str) -> Option<&mut Color> {
+        let color = match key {
+            "background" => &mut self.background,
+            "surface" => &mut self.surface,
+            "text" => &mut self.text,
+            "inactive" => &mut self.inactive,
+            "primary" => &mut self.primary,
+            "secondary" => &mut self.secondary,
+            "accent" => &mut self.accent,
+            "gold" => &mut self.gold,
+            "cherry" => &mut self.cherry,
+            "banana" => &mut self.banana,
+            _ => return None,
+        };
+        Some(color)
+    }
+
+    // The built-in theme after this one, wrapping around. The ANSI fallback is
+    // all a terminal without 24-bit color can show, so it stays.
+    pub fn next(&self) -> Theme {
+        if self.name == Theme::ansi_fallback().name || self.colors == ColorSupport::Monochrome {
+            return *self;
+        }
+        let index = Theme::ALL
+            .iter()
+            .position(|theme| theme.name == self.name)
+            .map_or(0, |index| (index + 1) % Theme::ALL.len());
+        Theme::ALL[index].drawn_like(self)
+    }
+
+    // Every color brought down to what the terminal can show. Without any
+    // colors only the glyphs tell things apart.
+    pub fn with_colors(self, colors: ColorSupport) -> Theme {
+        let glyphs = match colors {
+            ColorSupport::Monochrome if *self.glyphs != GlyphSet::ASCII => &GlyphSet::MONOCHROME,
+            _ => self.glyphs,
+        };
+        Theme {
+            background: colors.nearest(self.background),
+            surface: colors.nearest(self.surface),
+            text: colors.nearest(self.text),
+            inactive: colors.nearest(self.inactive),
+            primary: colors.nearest(self.primary),
+            secondary: colors.nearest(self.secondary),
+            accent: colors.nearest(self.accent),
+            gold: colors.nearest(self.gold),
+            cherry: colors.nearest(self.cherry),
+            banana: colors.nearest(self.banana),
+            glyphs,
+            colors,
+            ..self
+        }
+    }
+
+    // Themes bring their own glyphs, but ASCII and the number of colors are
+    // down to the terminal and stay whichever theme is picked
+    fn drawn_like(self, current: &Theme) -> Theme {
+        let theme = self.with_colors(current.colors);
+        if *current.glyphs == GlyphSet::ASCII {
+            Theme {
+                glyphs: current.glyphs,
+                ..theme
+            }
+        } else {
+            theme
+        }
+    }
+}
+
+// How many colors a terminal can show
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ColorSupport {
+    #[default]
+    TrueColor,
+    Ansi256,
+    Ansi16,
+    // For NO_COLOR and dumb terminals, everything in the default color
+    Monochrome,
+}
+
+// The 16 ANSI colors as xterm draws them by default
+const ANSI16: [(Color, [u8; 3]); 16] = [
+    (Color::Black, [0, 0, 0]),
+    (Color::DarkRed, [128, 0, 0]),
+    (Color::DarkGreen, [0, 128, 0]),
+    (Color::DarkYellow, [128, 128, 0]),
+    (Color::DarkBlue, [0, 0, 128]),
+    (Color::DarkMagenta, [128, 0, 128]),
+    (Color::DarkCyan, [0, 128, 128]),
+    (Color::Grey, [192, 192, 192]),
+    (Color::DarkGrey, [128, 128, 128]),
+    (Color::Red, [255, 0, 0]),
+    (Color::Green, [0, 255, 0]),
+    (Color::Yellow, [255, 255, 0]),
+    (Color::Blue, [0, 0, 255]),
+    (Color::Magenta, [255, 0, 255]),
+    (Color::Cyan, [0, 255, 255]),
+    (Color::White, [255, 255, 255]),
+];
+
+// The levels each channel takes in the 6x6x6 cube of the 256 colors
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+impl ColorSupport {
+    // The closest color this many colors have to `color`. Only RGB colors
+    // change, a named one is already as plain as it gets.
+    pub fn nearest(self, color: Color) -> Color {
+        match (self, color) {
+            (ColorSupport::Monochrome, _) => Color::Reset,
+            (ColorSupport::Ansi256, Color::Rgb { r, g, b }) => {
+                Color::AnsiValue(nearest_ansi256([r, g, b]))
+            }
+            (ColorSupport::Ansi16, Color::Rgb { r, g, b }) => nearest_ansi16([r, g, b]),
+            _ => color,
+        }
+    }
+}
+
+fn distance(a: [u8; 3], b: [u8; 3]) -> u32 {
+    a.iter()
+        .zip(b)
+        .map(|(&a, b)| u32::from(a.abs_diff(b)).pow(2))
+        .sum()
+}
+
+fn nearest_ansi16(rgb: [u8; 3]) -> Color {
+    ANSI16
+        .iter()
+        .min_by_key(|(_, ansi)| distance(rgb, *ansi))
+        .map_or(Color::Reset, |&(color, _)| color)
+}
+
+// The nearer of the closest cube color and the closest of the 24 greys
+fn nearest_ansi256(rgb: [u8; 3]) -> u8 {
+    let level = |channel: u8| {
+        (0..CUBE_LEVELS.len())
+            .min_by_key(|&index| CUBE_LEVELS[index].abs_diff(channel))
+            .unwrap_or_default()
+    };
+    let [r, g, b] = rgb.map(level);
+    let cube = [r, g, b].map(|index| CUBE_LEVELS[index]);
+    let cube_index = 16 + 36 * r + 6 * g + b;
+
+    let average = rgb.iter().map(|&channel| u32::from(channel)).sum::<u32>() / 3;
+    let grey = (average.saturating_sub(8) as usize + 5) / 10;
+    let grey = grey.min(23);
+    let grey_level = (8 + 10 * grey) as u8;
+
+    if distance(rgb, [grey_level; 3]) < distance(rgb, cube) {
+        (232 + grey) as u8
+    } else {
+        cube_index as u8
+    }
+}
+
+// Every character drawn that is not plain text, so a terminal or font that
+// garbles block and box-drawing characters can get by with ASCII alone
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GlyphSet {
+    pub wall: &'static str,
+    pub floor: &'static str,
+    // By the way the snake is going, in the order of `Direction::ALL`
+    pub heads: [&'static str; Direction::ALL.len()],
+    // The tail as a pipe: straight across, straight down, then the corners
+    // joining down and right, down and left, up and right, up and left
+    pub pipes: [&'static str; 6],
+    pub tail: &'static str,
+    // By `FruitKind`, in the order of `FruitKind::ALL`
+    pub fruits: [&'static str; FruitKind::ALL.len()],
+    pub bonus: &'static str,
+    pub pellet: &'static str,
+    pub life: &'static str,
+    pub lost_life: &'static str,
+    pub bar_full: &'static str,
+    pub bar_empty: &'static str,
+    pub vertical: &'static str,
+    pub horizontal: &'static str,
+    pub top_left: &'static str,
+    pub top_right: &'static str,
+    pub bottom_left: &'static str,
+    pub bottom_right: &'static str,
+    pub cursor: &'static str,
+    pub previous: &'static str,
+    pub next: &'static str,
+    pub ellipsis: &'static str,
+}
+
+impl GlyphSet {
+    pub const UNICODE: GlyphSet = GlyphSet {
+        wall: "█",
+        floor: "█",
+        heads: ["▲", "▼", "◀", "▶"],
+        pipes: ["━", "┃", "┏", "┓", "┗", "┛"],
+        tail: "█",
+        fruits: ["●", "♦", "◗", "⧗"],
+        bonus: "★",
+        pellet: "✦",
+        life: "♥",
+        lost_life: "♡",
+        bar_full: "▮",
+        bar_empty: "▯",
+        vertical: "│",
+        horizontal: "─",
+        top_left: "┌",
+        top_right: "┐",
+        bottom_left: "└",
+        bottom_right: "┘",
+        cursor: "▎",
+        previous: "◀",
+        next: "▶",
+        ellipsis: "…",
+    };
+
+    // The board loses its solid fill, the floor is left blank and the snake
+    // is told apart by its characters as well as its colors
+    pub const ASCII: GlyphSet = GlyphSet {
+        wall: "#",
+        floor: " ",
+        heads: ["^", "v", "<", ">"],
+        pipes: ["-", "|", "+", "+", "+", "+"],
+        tail: "o",
+        fruits: ["*", "%", ")", "X"],
+        bonus: "$",
+        pellet: "+",
+        life: "*",
+        lost_life: ".",
+        bar_full: "=",
+        bar_empty: "-",
+        vertical: "|",
+        horizontal: "-",
+        top_left: "+",
+        top_right: "+",
+        bottom_left: "+",
+        bottom_right: "+",
+        cursor: "_",
+        previous: "<",
+        next: ">",
+        ellipsis: "...",
+    };
+
+    // Without colors the floor is left blank, so only walls are solid
+    pub const MONOCHROME: GlyphSet = GlyphSet {
+        floor: " ",
+        ..GlyphSet::UNICODE
+    };
+
+    // Apples are diamonds, so they look nothing like the snake's head even
+    // without their colors
+    pub const COLORBLIND: GlyphSet = GlyphSet {
+        fruits: ["◆", "♣", "◗", "⧗"],
+        ..GlyphSet::UNICODE
+    };
+
+    // Pointing the way the snake is going, so it reads at a glance
+    pub fn head(&self, direction: Direction) -> &'static str {
+        self.heads[direction as usize]
+    }
+
+    // A tail segment joining the segment towards the head with the one
+    // towards the end. Two ways out the same side can't be drawn as a pipe.
+    pub fn pipe(&self, toward_head: Direction, toward_end: Direction) -> &'static str {
+        match (toward_head, toward_end) {
+            (Direction::Left, Direction::Right) | (Direction::Right, Direction::Left) => {
+                self.pipes[0]
+            }
+            (Direction::Up, Direction::Down) | (Direction::Down, Direction::Up) => self.pipes[1],
+            (Direction::Down, Direction::Right) | (Direction::Right, Direction::Down) => {
+                self.pipes[2]
+            }
+            (Direction::Down, Direction::Left) | (Direction::Left, Direction::Down) => {
+                self.pipes[3]
+            }
+            (Direction::Up, Direction::Right) | (Direction::Right, Direction::Up) => self.pipes[4],
+            (Direction::Up, Direction::Left) | (Direction::Left, Direction::Up) => self.pipes[5],
+            _ => self.tail,
+        }
+    }
+
+    pub fn fruit(&self, kind: FruitKind) -> &'static str {
+        self.fruits[kind as usize]
+    }
+
+    pub fn effect(&self, kind: EffectKind) -> &'static str {
+        match kind {
+            EffectKind::Phasing => self.pellet,
+            EffectKind::SlowMotion => self.fruit(FruitKind::Hourglass),
+        }
+    }
+}
+
+impl fmt::Display for Theme {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name)
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::MINT
+    }
+}
+
+// A built-in theme's name, or else a theme file
+pub fn load_theme(path: &Path) -> io::Result<Theme> {
+    if let Some(theme) = path.to_str().and_then(Theme::named) {
+        return Ok(theme);
+    }
+    parse_theme(&fs::read_to_string(path)?)
+}
+
+// A small slice of TOML, one color per line and `#` comments:
+//
+//   primary = [88, 255, 158]
+//   background = [0, 0, 0]  # pure black
+//
+// Colors that are not listed keep their value from the default theme.
+pub fn parse_theme(text: &str) -> io::Result<Theme> {
+    let mut theme = Theme {
+        name: "custom",
+        ..Theme::default()
+    };
+    for (number, line) in text.lines().enumerate() {
+        let line = line.split_once('#').map_or(line, |(line, _)| line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        let error = |message: String| invalid(format!("line {}: {}", number + 1, message));
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| error(format!("expected 'name = [r, g, b]', got '{}'", line)))?;
+        let key = key.trim();
+        let color = theme
+            .color_mut(key)
+            .ok_or_else(|| error(format!("unknown color '{}'", key)))?;
+        *color = parse_rgb(value.trim()).ok_or_else(|| {
+            error(format!(
+                "'{}' must be three integers from 0 to 255, like [255, 128, 0]",
+                key
+            ))
+        })?;
+    }
+    Ok(theme)
+}
+
+fn parse_rgb(value: &str) -> Option<Color> {
+    let channels: Vec<u8> = value
+        .strip_prefix('[')?
+        .strip_suffix(']')?
+        .split(',')
+        .map(|channel| channel.trim().parse().ok())
+        .collect::<Option<_>>()?;
+    let [r, g, b] = channels[..] else {
+        return None;
+    };
+    Some(rgb(r, g, b))
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_cycles_through_every_theme() {
+        let mut theme = Theme::default();
+        let mut seen = Vec::new();
+        for _ in 0..Theme::ALL.len() {
+            seen.push(theme.name);
+            theme = theme.next();
+        }
+        assert_eq!(theme, Theme::default());
+        assert_eq!(seen, Theme::ALL.map(|theme| theme.name));
+    }
+
+    #[test]
+    fn test_choices_keep_the_current_theme_and_ascii() {
+        let choices = Theme::choices(Theme::CLASSIC);
+        assert_eq!(choices, Theme::ALL.to_vec());
+
+        let ascii = Theme {
+            glyphs: &GlyphSet::ASCII,
+            ..Theme::ansi_fallback()
+        };
+        let choices = Theme::choices(ascii);
+        assert_eq!(choices.len(), Theme::ALL.len() + 1);
+        assert_eq!(choices[0], ascii);
+        assert!(choices.iter().all(|theme| *theme.glyphs == GlyphSet::ASCII));
+        assert_eq!(ascii.next(), ascii);
+        let colorblind = Theme {
+            glyphs: &GlyphSet::ASCII,
+            ..Theme::COLORBLIND
+        };
+        assert_eq!(colorblind.next().glyphs, &GlyphSet::ASCII);
+        assert_eq!(Theme::COLORBLIND.next().glyphs, &GlyphSet::UNICODE);
+    }
+
+    #[test]
+    fn test_pipes_join_both_neighbors() {
+        let glyphs = GlyphSet::UNICODE;
+        let table = [
+            (Direction::Left, Direction::Right, "━"),
+            (Direction::Right, Direction::Left, "━"),
+            (Direction::Up, Direction::Down, "┃"),
+            (Direction::Down, Direction::Up, "┃"),
+            (Direction::Down, Direction::Right, "┏"),
+            (Direction::Right, Direction::Down, "┏"),
+            (Direction::Left, Direction::Down, "┓"),
+            (Direction::Up, Direction::Right, "┗"),
+            (Direction::Left, Direction::Up, "┛"),
+            (Direction::Up, Direction::Up, "█"),
+        ];
+        for (toward_head, toward_end, pipe) in table {
+            assert_eq!(
+                glyphs.pipe(toward_head, toward_end),
+                pipe,
+                "{:?} {:?}",
+                toward_head,
+                toward_end
+            );
+        }
+        assert_eq!(GlyphSet::ASCII.pipe(Direction::Up, Direction::Left), "+");
+    }
+
+    #[test]
+    fn test_heads_point_the_way() {
+        assert_eq!(GlyphSet::UNICODE.head(Direction::Left), "◀");
+        assert_eq!(GlyphSet::ASCII.head(Direction::Up), "^");
+        for glyphs in [GlyphSet::UNICODE, GlyphSet::ASCII, GlyphSet::COLORBLIND] {
+            let mut heads = glyphs.heads.to_vec();
+            heads.dedup();
+            assert_eq!(heads.len(), Direction::ALL.len());
+            assert!(!heads.contains(&glyphs.tail));
+        }
+    }
+
+    #[test]
+    fn test_lerp_color() {
+        let (a, b) = (rgb(0, 100, 255), rgb(200, 0, 55));
+        assert_eq!(lerp_color(a, b, 0.0), a);
+        assert_eq!(lerp_color(a, b, 1.0), b);
+        assert_eq!(lerp_color(a, b, 0.5), rgb(100, 50, 155));
+        assert_eq!(lerp_color(a, b, 2.0), b);
+        assert_eq!(lerp_color(Color::Green, b, 0.5), Color::Green);
+    }
+
+    #[test]
+    fn test_nearest_of_fewer_colors() {
+        assert_eq!(nearest_ansi256([255, 0, 0]), 196);
+        assert_eq!(nearest_ansi256([0, 0, 0]), 16);
+        assert_eq!(nearest_ansi256([128, 128, 128]), 244);
+        assert_eq!(nearest_ansi256([18, 18, 18]), 233);
+        // Mint green, between two of the cube's blue levels
+        assert_eq!(nearest_ansi256([88, 255, 158]), 85);
+
+        assert_eq!(nearest_ansi16([250, 10, 10]), Color::Red);
+        assert_eq!(nearest_ansi16([10, 10, 120]), Color::DarkBlue);
+        assert_eq!(nearest_ansi16([200, 200, 200]), Color::Grey);
+        assert_eq!(nearest_ansi16([18, 18, 18]), Color::Black);
+
+        // Named colors are left alone
+        assert_eq!(ColorSupport::Ansi256.nearest(Color::Cyan), Color::Cyan);
+        assert_eq!(ColorSupport::TrueColor.nearest(rgb(1, 2, 3)), rgb(1, 2, 3));
+    }
+
+    #[test]
+    fn test_themes_keep_their_colors_apart_in_256_colors() {
+        for theme in Theme::ALL {
+            let theme = theme.with_colors(ColorSupport::Ansi256);
+            let snake = [theme.primary, theme.secondary];
+            for color in [theme.accent, theme.cherry, theme.banana, theme.gold] {
+                assert!(matches!(color, Color::AnsiValue(_)));
+                assert!(!snake.contains(&color), "{}", theme.name);
+            }
+            assert_ne!(theme.primary, theme.secondary, "{}", theme.name);
+            assert_eq!(theme.next().colors, ColorSupport::Ansi256);
+        }
+    }
+
+    #[test]
+    fn test_monochrome_tells_things_apart_by_glyph() {
+        let theme = Theme::default().with_colors(ColorSupport::Monochrome);
+        assert_eq!(theme.primary, Color::Reset);
+        assert_eq!(theme.background, Color::Reset);
+        assert_eq!(*theme.glyphs, GlyphSet::MONOCHROME);
+        assert!(!theme.glyphs.pipes.contains(&theme.glyphs.wall));
+        assert_eq!(theme.next(), theme);
+
+        let ascii = Theme {
+            glyphs: &GlyphSet::ASCII,
+            ..Theme::default()
+        };
+        let ascii = ascii.with_colors(ColorSupport::Monochrome);
+        assert_eq!(*ascii.glyphs, GlyphSet::ASCII);
+    }
+
+    #[test]
+    fn test_snake_and_food_stand_out() {
+        for theme in Theme::ALL {
+            let foods = [theme.accent, theme.cherry, theme.banana, theme.gold];
+            for color in foods.into_iter().chain([theme.primary, theme.secondary]) {
+                assert_ne!(color, theme.background, "{}", theme.name);
+                assert_ne!(color, theme.surface, "{}", theme.name);
+            }
+            assert_ne!(theme.primary, theme.secondary, "{}", theme.name);
+            for food in foods {
+                assert_ne!(food, theme.primary, "{}", theme.name);
+                assert_ne!(food, theme.secondary, "{}", theme.name);
+            }
+        }
+    }
+
+    #[test]
+    fn test_built_in_themes_load_by_name() {
+        assert_eq!(load_theme(Path::new("light")).unwrap(), Theme::LIGHT);
+        assert_eq!(load_theme(Path::new("amber")).unwrap(), Theme::AMBER);
+        assert!(load_theme(Path::new("no-such-theme.toml")).is_err());
+    }
+
+    #[test]
+    fn test_parse_theme() {
+        let theme =
+            parse_theme("# my colors\n\nprimary = [1, 2, 3]\nbackground=[0,0,0]  # black\n")
+                .unwrap();
+        assert_eq!(theme.primary, rgb(1, 2, 3));
+        assert_eq!(theme.background, rgb(0, 0, 0));
+        // Anything missing comes from the default theme
+        assert_eq!(theme.accent, Theme::default().accent);
+        assert_eq!(theme.name, "custom");
+    }
+
+    #[test]
+    fn test_invalid_themes_name_the_key() {
+        let cases = [
+            ("primary = [1, 2]", "line 1: 'primary'"),
+            ("\nsurface = [1, 2, 256]", "line 2: 'surface'"),
+            ("accent = [-1, 0, 0]", "'accent'"),
+            ("gold = 255, 200, 60", "'gold'"),
+            ("purple = [1, 2, 3]", "unknown color 'purple'"),
+            ("primary", "expected 'name = [r, g, b]'"),
+        ];
+        for (text, expected) in cases {
+            let err = parse_theme(text).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+            assert!(err.to_string().contains(expected), "{}: {}", text, err);
+        }
+    }
+
+    // How a color looks with protanopia and deuteranopia, using the full
+    // severity matrices from Machado, Oliveira and Fernandes (2009)
+    fn simulate(color: Color) -> [[f64; 3]; 2] {
+        const MATRICES: [[[f64; 3]; 3]; 2] = [
+            [
+                [0.152286, 1.052583, -0.204868],
+                [0.114503, 0.786281, 0.099216],
+                [-0.003882, -0.048116, 1.051998],
+            ],
+            [
+                [0.367322, 0.860646, -0.227968],
+                [0.280085, 0.672501, 0.047413],
+                [-0.011820, 0.042940, 0.968881],
+            ],
+        ];
+        let Color::Rgb { r, g, b } = color else {
+            panic!("not an RGB color: {:?}", color);
+        };
+        let rgb = [r, g, b].map(f64::from);
+        MATRICES.map(|matrix| {
+            matrix.map(|row| {
+                row.iter()
+                    .zip(rgb)
+                    .map(|(weight, channel)| weight * channel)
+                    .sum()
+            })
+        })
+    }
+
+    #[test]
+    fn test_colorblind_snake_and_food_stay_apart() {
+        let theme = Theme::COLORBLIND;
+        let snake = [theme.primary, theme.secondary];
+        let foods = [theme.accent, theme.cherry, theme.banana, theme.gold];
+        let mut pairs = vec![(theme.primary, theme.secondary)];
+        pairs.extend(
+            snake
+                .iter()
+                .flat_map(|&part| foods.map(|food| (part, food))),
+        );
+
+        for (a, b) in pairs {
+            for (a, b) in simulate(a).iter().zip(simulate(b)) {
+                let distance = a
+                    .iter()
+                    .zip(b)
+                    .map(|(x, y)| (x - y).powi(2))
+                    .sum::<f64>()
+                    .sqrt();
+                assert!(distance > 80.0, "{:?} and {:?} are {}", a, b, distance);
+            }
+        }
+    }
+
+    #[test]
+    fn test_ansi_fallback_keeps_things_apart() {
+        let theme = Theme::ansi_fallback();
+        let colors = [
+            theme.background,
+            theme.surface,
+            theme.primary,
+            theme.secondary,
+            theme.accent,
+            theme.gold,
+            theme.cherry,
+            theme.banana,
+        ];
+        for (i, color) in colors.iter().enumerate() {
+            assert!(!matches!(color, Color::Rgb { .. } | Color::AnsiValue(_)));
+            assert!(!colors[i + 1..].contains(color), "{:?}", color);
+        }
+        assert_eq!(theme.next(), theme);
+    }
+}